@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Duration;
 
+use libsql::{params, Builder, Connection, Database};
 use metrics::{counter, gauge, histogram};
 use reqwest::Client;
 use serde::Serialize;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::Instant;
 use tracing::{error, info, warn};
 use uuid::Uuid;
@@ -18,6 +20,38 @@ pub struct UsageEvent {
 #[derive(Clone)]
 pub struct AutumnTracker {
     tx: mpsc::UnboundedSender<UsageEvent>,
+    control: mpsc::UnboundedSender<ControlCommand>,
+}
+
+/// Operator requests served by the flush loop, which owns the pending usage.
+enum ControlCommand {
+    Snapshot(oneshot::Sender<Vec<PendingEntry>>),
+    Flush(oneshot::Sender<FlushOutcome>),
+    /// Stops accepting usage, flushes what is queued and replies once done.
+    Shutdown(oneshot::Sender<()>),
+}
+
+#[derive(Serialize)]
+pub struct PendingEntry {
+    pub org_id: String,
+    pub feature_id: &'static str,
+    /// Usage not yet sealed into a batch.
+    pub accumulated_gb: f64,
+    pub in_flight: Option<InFlightBatch>,
+}
+
+#[derive(Serialize)]
+pub struct InFlightBatch {
+    pub value_gb: f64,
+    pub sequence: i64,
+    pub idempotency_key: String,
+}
+
+#[derive(Serialize)]
+pub struct FlushOutcome {
+    pub ok: bool,
+    pub pending_entries: usize,
+    pub pending_gb: f64,
 }
 
 #[derive(Serialize)]
@@ -25,23 +59,52 @@ struct TrackRequest<'a> {
     customer_id: &'a str,
     feature_id: &'a str,
     value: f64,
-    idempotency_key: String,
+    idempotency_key: &'a str,
 }
 
 impl AutumnTracker {
-    pub fn spawn(secret_key: String, api_url: &str, flush_interval_secs: u64) -> Self {
+    /// Recovers pending usage from `ledger` before starting the flush loop.
+    /// A ledger that cannot be read is an error: flushing would overwrite it.
+    pub async fn spawn(
+        secret_key: String,
+        api_url: &str,
+        flush_interval_secs: u64,
+        ledger: Option<UsageLedger>,
+    ) -> Result<Self, String> {
+        let mut pending = PendingUsage::default();
+        if let Some(ledger) = &ledger {
+            pending = ledger.load().await.map_err(|error| {
+                counter!("autumn_ledger_errors_total", "op" => "load").increment(1);
+                format!("Failed to load Autumn usage ledger: {error}")
+            })?;
+            if !pending.is_empty() {
+                info!(
+                    pending_entries = pending.len(),
+                    total_pending_gb = pending.total_gb(),
+                    "Recovered pending Autumn usage from ledger"
+                );
+            }
+        }
+
         let (tx, rx) = mpsc::unbounded_channel();
+        let (control, control_rx) = mpsc::unbounded_channel();
         let api_url = api_url.trim_end_matches('/').to_string();
         let flush_interval = Duration::from_secs(flush_interval_secs);
+        let durable = ledger.is_some();
 
-        tokio::spawn(flush_loop(rx, secret_key, api_url, flush_interval));
+        tokio::spawn(flush_loop(
+            rx,
+            control_rx,
+            secret_key,
+            api_url,
+            flush_interval,
+            ledger,
+            pending,
+        ));
 
-        info!(
-            flush_interval_secs,
-            "Autumn usage tracker started"
-        );
+        info!(flush_interval_secs, durable, "Autumn usage tracker started");
 
-        Self { tx }
+        Ok(Self { tx, control })
     }
 
     pub fn track(&self, org_id: &str, feature_id: &'static str, value_gb: f64) {
@@ -51,18 +114,369 @@ impl AutumnTracker {
             value_gb,
         });
     }
+
+    /// Returns the accumulated and in-flight usage, or `None` if the flush
+    /// loop has stopped.
+    pub async fn pending(&self) -> Option<Vec<PendingEntry>> {
+        let (reply, rx) = oneshot::channel();
+        self.control.send(ControlCommand::Snapshot(reply)).ok()?;
+        rx.await.ok()
+    }
+
+    /// Closes the usage channel and waits for the final flush. Usage tracked
+    /// afterwards is dropped.
+    pub async fn shutdown(&self) {
+        let (reply, rx) = oneshot::channel();
+        if self.control.send(ControlCommand::Shutdown(reply)).is_ok() {
+            let _ = rx.await;
+        }
+    }
+
+    /// Runs a flush round immediately instead of waiting for the next tick.
+    pub async fn flush(&self) -> Option<FlushOutcome> {
+        let (reply, rx) = oneshot::channel();
+        self.control.send(ControlCommand::Flush(reply)).ok()?;
+        rx.await.ok()
+    }
 }
 
 type AccumulatorKey = (String, &'static str); // (org_id, feature_id)
 
+/// Usage sealed for sending to Autumn. A batch keeps its value and
+/// idempotency key until Autumn accepts it, so a request that succeeded
+/// upstream but failed on our side is retried under the same key. Only one
+/// batch per key is in flight; usage arriving meanwhile waits in the
+/// accumulator for the next batch.
+#[derive(Clone, Debug, PartialEq)]
+struct UsageBatch {
+    value_gb: f64,
+    window_ms: i64,
+    sequence: i64,
+    idempotency_key: String,
+}
+
+impl UsageBatch {
+    fn seal(key: &AccumulatorKey, value_gb: f64, window_ms: i64, sequence: i64) -> Self {
+        Self {
+            value_gb,
+            window_ms,
+            sequence,
+            idempotency_key: batch_idempotency_key(key, window_ms, sequence),
+        }
+    }
+}
+
+/// Derives the idempotency key from the batch identity rather than the
+/// attempt, so every retry of a batch (including after a restart) is
+/// deduplicated by Autumn.
+fn batch_idempotency_key(key: &AccumulatorKey, window_ms: i64, sequence: i64) -> String {
+    let (org_id, feature_id) = key;
+    let identity = format!("maple-ingest:{org_id}:{feature_id}:{window_ms}:{sequence}");
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, identity.as_bytes()).to_string()
+}
+
+#[derive(Default)]
+struct PendingUsage {
+    /// Usage not yet sealed into a batch.
+    accumulator: HashMap<AccumulatorKey, f64>,
+    batches: HashMap<AccumulatorKey, UsageBatch>,
+    /// Next batch sequence per key.
+    sequences: HashMap<AccumulatorKey, i64>,
+}
+
+impl PendingUsage {
+    fn is_empty(&self) -> bool {
+        self.accumulator.is_empty() && self.batches.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.accumulator.len() + self.batches.len()
+    }
+
+    fn total_gb(&self) -> f64 {
+        self.accumulator
+            .values()
+            .chain(self.batches.values().map(|batch| &batch.value_gb))
+            .fold(0.0, |total, value_gb| total + value_gb)
+    }
+
+    fn add(&mut self, event: UsageEvent) {
+        *self
+            .accumulator
+            .entry((event.org_id, event.feature_id))
+            .or_insert(0.0) += event.value_gb;
+    }
+
+    fn snapshot(&self) -> Vec<PendingEntry> {
+        let mut keys: Vec<&AccumulatorKey> =
+            self.accumulator.keys().chain(self.batches.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        keys.into_iter()
+            .map(|key| PendingEntry {
+                org_id: key.0.clone(),
+                feature_id: key.1,
+                accumulated_gb: self.accumulator.get(key).copied().unwrap_or_default(),
+                in_flight: self.batches.get(key).map(|batch| InFlightBatch {
+                    value_gb: batch.value_gb,
+                    sequence: batch.sequence,
+                    idempotency_key: batch.idempotency_key.clone(),
+                }),
+            })
+            .collect()
+    }
+
+    /// Moves accumulated usage into a new batch for every key that has no
+    /// batch in flight.
+    fn seal(&mut self, window_ms: i64) {
+        let ready: Vec<AccumulatorKey> = self
+            .accumulator
+            .keys()
+            .filter(|key| !self.batches.contains_key(*key))
+            .cloned()
+            .collect();
+
+        for key in ready {
+            let Some(value_gb) = self.accumulator.remove(&key) else {
+                continue;
+            };
+            let sequence = self.sequences.entry(key.clone()).or_insert(0);
+            let batch = UsageBatch::seal(&key, value_gb, window_ms, *sequence);
+            *sequence += 1;
+            self.batches.insert(key, batch);
+        }
+    }
+}
+
+/// Local libsql tables mirroring the in-memory accumulator and in-flight
+/// batches so pending usage survives restarts. They are rewritten on every
+/// flush tick before usage is sent to Autumn, so a crash loses at most one
+/// flush interval of usage.
+pub struct UsageLedger {
+    _db: Database,
+    conn: Connection,
+}
+
+impl UsageLedger {
+    pub async fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|error| format!("Failed to create ledger directory: {error}"))?;
+        }
+
+        let db = Builder::new_local(path)
+            .build()
+            .await
+            .map_err(|error| error.to_string())?;
+        let conn = db.connect().map_err(|error| error.to_string())?;
+
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS autumn_pending_usage (
+                org_id TEXT NOT NULL,
+                feature_id TEXT NOT NULL,
+                value_gb REAL NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (org_id, feature_id)
+            );
+            CREATE TABLE IF NOT EXISTS autumn_usage_batches (
+                org_id TEXT NOT NULL,
+                feature_id TEXT NOT NULL,
+                value_gb REAL NOT NULL,
+                window_ms INTEGER NOT NULL,
+                sequence INTEGER NOT NULL,
+                idempotency_key TEXT NOT NULL,
+                PRIMARY KEY (org_id, feature_id)
+            );",
+        )
+        .await
+        .map_err(|error| error.to_string())?;
+
+        Ok(Self { _db: db, conn })
+    }
+
+    async fn load(&self) -> Result<PendingUsage, String> {
+        let mut pending = PendingUsage::default();
+
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT org_id, feature_id, value_gb FROM autumn_pending_usage",
+                (),
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        while let Some(row) = rows.next().await.map_err(|error| error.to_string())? {
+            let org_id: String = row.get(0).map_err(|error| error.to_string())?;
+            let feature_id: String = row.get(1).map_err(|error| error.to_string())?;
+            let value_gb: f64 = row.get(2).map_err(|error| error.to_string())?;
+
+            let Some(feature_id) = known_feature_id(&feature_id) else {
+                warn!(
+                    org_id,
+                    feature_id, "Ignoring ledger entry for unknown feature"
+                );
+                continue;
+            };
+            *pending
+                .accumulator
+                .entry((org_id, feature_id))
+                .or_insert(0.0) += value_gb;
+        }
+
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT org_id, feature_id, value_gb, window_ms, sequence, idempotency_key
+                 FROM autumn_usage_batches",
+                (),
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        while let Some(row) = rows.next().await.map_err(|error| error.to_string())? {
+            let org_id: String = row.get(0).map_err(|error| error.to_string())?;
+            let feature_id: String = row.get(1).map_err(|error| error.to_string())?;
+
+            let Some(feature_id) = known_feature_id(&feature_id) else {
+                warn!(
+                    org_id,
+                    feature_id, "Ignoring ledger batch for unknown feature"
+                );
+                continue;
+            };
+            let key = (org_id, feature_id);
+            let batch = UsageBatch {
+                value_gb: row.get(2).map_err(|error| error.to_string())?,
+                window_ms: row.get(3).map_err(|error| error.to_string())?,
+                sequence: row.get(4).map_err(|error| error.to_string())?,
+                idempotency_key: row.get(5).map_err(|error| error.to_string())?,
+            };
+            pending.sequences.insert(key.clone(), batch.sequence + 1);
+            pending.batches.insert(key, batch);
+        }
+
+        Ok(pending)
+    }
+
+    async fn persist(&self, pending: &PendingUsage) -> Result<(), String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or_default();
+
+        let tx = self
+            .conn
+            .transaction()
+            .await
+            .map_err(|error| error.to_string())?;
+
+        // Sealing moves usage out of the accumulator, so rewrite it wholesale.
+        tx.execute("DELETE FROM autumn_pending_usage", ())
+            .await
+            .map_err(|error| error.to_string())?;
+        for ((org_id, feature_id), value_gb) in &pending.accumulator {
+            tx.execute(
+                "INSERT INTO autumn_pending_usage (org_id, feature_id, value_gb, updated_at)
+                 VALUES (?, ?, ?, ?)",
+                params![org_id.as_str(), *feature_id, *value_gb, now],
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        }
+
+        // Batches flushed since the last persist may still have rows if
+        // removing them failed; the in-memory set is authoritative.
+        tx.execute("DELETE FROM autumn_usage_batches", ())
+            .await
+            .map_err(|error| error.to_string())?;
+        for ((org_id, feature_id), batch) in &pending.batches {
+            tx.execute(
+                "INSERT INTO autumn_usage_batches
+                 (org_id, feature_id, value_gb, window_ms, sequence, idempotency_key)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![
+                    org_id.as_str(),
+                    *feature_id,
+                    batch.value_gb,
+                    batch.window_ms,
+                    batch.sequence,
+                    batch.idempotency_key.as_str()
+                ],
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        }
+
+        tx.commit().await.map_err(|error| error.to_string())
+    }
+
+    async fn remove_batches(&self, keys: &[AccumulatorKey]) -> Result<(), String> {
+        let tx = self
+            .conn
+            .transaction()
+            .await
+            .map_err(|error| error.to_string())?;
+        for (org_id, feature_id) in keys {
+            tx.execute(
+                "DELETE FROM autumn_usage_batches WHERE org_id = ? AND feature_id = ?",
+                params![org_id.as_str(), *feature_id],
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        }
+        tx.commit().await.map_err(|error| error.to_string())
+    }
+}
+
+fn known_feature_id(feature_id: &str) -> Option<&'static str> {
+    match feature_id {
+        "traces" => Some("traces"),
+        "logs" => Some("logs"),
+        "metrics" => Some("metrics"),
+        _ => None,
+    }
+}
+
+async fn persist_ledger(ledger: Option<&UsageLedger>, pending: &PendingUsage) {
+    let Some(ledger) = ledger else {
+        return;
+    };
+    if let Err(error) = ledger.persist(pending).await {
+        counter!("autumn_ledger_errors_total", "op" => "persist").increment(1);
+        error!(error = %error, "Failed to persist Autumn usage ledger");
+    }
+}
+
+async fn remove_from_ledger(ledger: Option<&UsageLedger>, keys: &[AccumulatorKey]) {
+    let Some(ledger) = ledger else {
+        return;
+    };
+    if keys.is_empty() {
+        return;
+    }
+    if let Err(error) = ledger.remove_batches(keys).await {
+        counter!("autumn_ledger_errors_total", "op" => "remove").increment(1);
+        error!(error = %error, "Failed to remove flushed batches from Autumn usage ledger");
+    }
+}
+
+fn unix_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
 async fn flush_loop(
     mut rx: mpsc::UnboundedReceiver<UsageEvent>,
+    mut control_rx: mpsc::UnboundedReceiver<ControlCommand>,
     secret_key: String,
     api_url: String,
     flush_interval: Duration,
+    ledger: Option<UsageLedger>,
+    mut pending: PendingUsage,
 ) {
     let client = Client::new();
-    let mut accumulator: HashMap<AccumulatorKey, f64> = HashMap::new();
     let mut consecutive_failures: u64 = 0;
     let critical_threshold: u64 = (300 / flush_interval.as_secs().max(1)).max(1);
 
@@ -72,68 +486,12 @@ async fn flush_loop(
     loop {
         tokio::select! {
             _ = interval.tick() => {
-                if accumulator.is_empty() {
+                if pending.is_empty() {
                     continue;
                 }
 
                 let flush_start = Instant::now();
-                let mut all_ok = true;
-
-                // Collect entries to flush
-                let entries: Vec<(AccumulatorKey, f64)> = accumulator
-                    .iter()
-                    .map(|(k, v)| (k.clone(), *v))
-                    .collect();
-
-                let mut flushed_keys: Vec<AccumulatorKey> = Vec::new();
-
-                for ((org_id, feature_id), value_gb) in &entries {
-                    let body = TrackRequest {
-                        customer_id: org_id,
-                        feature_id,
-                        value: *value_gb,
-                        idempotency_key: Uuid::new_v4().to_string(),
-                    };
-
-                    let result: Result<reqwest::Response, reqwest::Error> = client
-                        .post(format!("{}/v1/track", api_url))
-                        .header("Authorization", format!("Bearer {}", secret_key))
-                        .json(&body)
-                        .send()
-                        .await;
-
-                    match result {
-                        Ok(resp) if resp.status().is_success() => {
-                            flushed_keys.push((org_id.clone(), feature_id));
-                        }
-                        Ok(resp) => {
-                            let status = resp.status();
-                            let body_text = resp.text().await.unwrap_or_default();
-                            warn!(
-                                org_id,
-                                feature_id,
-                                status = %status,
-                                body = %body_text,
-                                "Autumn track request failed"
-                            );
-                            all_ok = false;
-                        }
-                        Err(err) => {
-                            warn!(
-                                org_id,
-                                feature_id,
-                                error = %err,
-                                "Autumn track request failed"
-                            );
-                            all_ok = false;
-                        }
-                    }
-                }
-
-                // Remove successfully flushed entries
-                for key in &flushed_keys {
-                    accumulator.remove(key);
-                }
+                let all_ok = flush_round(&client, &secret_key, &api_url, &mut pending, ledger.as_ref()).await;
 
                 let flush_duration = flush_start.elapsed();
                 histogram!("autumn_track_flush_duration_seconds")
@@ -149,36 +507,68 @@ async fn flush_loop(
                         .increment(1);
 
                     if consecutive_failures >= critical_threshold {
-                        let total_pending_gb: f64 = accumulator.values().sum();
+                        let held_in = if ledger.is_some() { "the ledger" } else { "memory" };
                         error!(
                             consecutive_failures,
-                            pending_entries = accumulator.len(),
-                            total_pending_gb,
-                            "CRITICAL: Autumn tracking has failed for ~5 minutes. Usage data is accumulating in memory."
+                            pending_entries = pending.len(),
+                            total_pending_gb = pending.total_gb(),
+                            durable = ledger.is_some(),
+                            "CRITICAL: Autumn tracking has failed for ~5 minutes. Usage data is accumulating in {held_in}."
                         );
                     }
                 }
 
                 // Update pending gauge
-                let total_pending: f64 = accumulator.values().sum();
-                gauge!("autumn_track_pending_gb").set(total_pending);
+                gauge!("autumn_track_pending_gb").set(pending.total_gb());
+            }
+
+            Some(command) = control_rx.recv() => {
+                match command {
+                    ControlCommand::Snapshot(reply) => {
+                        let _ = reply.send(pending.snapshot());
+                    }
+                    ControlCommand::Flush(reply) => {
+                        info!(pending_entries = pending.len(), "Forced Autumn flush requested");
+                        let ok = pending.is_empty()
+                            || flush_round(&client, &secret_key, &api_url, &mut pending, ledger.as_ref()).await;
+                        counter!("autumn_track_flushes_total", "status" => if ok { "ok" } else { "error" })
+                            .increment(1);
+                        gauge!("autumn_track_pending_gb").set(pending.total_gb());
+                        let _ = reply.send(FlushOutcome {
+                            ok,
+                            pending_entries: pending.len(),
+                            pending_gb: pending.total_gb(),
+                        });
+                    }
+                    ControlCommand::Shutdown(reply) => {
+                        rx.close();
+                        while let Some(event) = rx.recv().await {
+                            pending.add(event);
+                        }
+                        if !pending.is_empty() {
+                            info!(
+                                pending_entries = pending.len(),
+                                "Autumn tracker shutting down, attempting final flush"
+                            );
+                            flush_all(&client, &secret_key, &api_url, &mut pending, ledger.as_ref()).await;
+                        }
+                        let _ = reply.send(());
+                        break;
+                    }
+                }
             }
 
             event = rx.recv() => {
                 match event {
-                    Some(event) => {
-                        *accumulator
-                            .entry((event.org_id, event.feature_id))
-                            .or_insert(0.0) += event.value_gb;
-                    }
+                    Some(event) => pending.add(event),
                     None => {
                         // Channel closed, do a final flush attempt
-                        if !accumulator.is_empty() {
+                        if !pending.is_empty() {
                             info!(
-                                pending_entries = accumulator.len(),
+                                pending_entries = pending.len(),
                                 "Autumn tracker shutting down, attempting final flush"
                             );
-                            flush_all(&client, &secret_key, &api_url, &mut accumulator).await;
+                            flush_all(&client, &secret_key, &api_url, &mut pending, ledger.as_ref()).await;
                         }
                         break;
                     }
@@ -188,23 +578,28 @@ async fn flush_loop(
     }
 }
 
-async fn flush_all(
+/// Seals ready usage into batches, persists them, then sends every batch
+/// once. Returns whether all sends succeeded.
+async fn flush_round(
     client: &Client,
     secret_key: &str,
     api_url: &str,
-    accumulator: &mut HashMap<AccumulatorKey, f64>,
-) {
-    let entries: Vec<(AccumulatorKey, f64)> = accumulator
-        .iter()
-        .map(|(k, v)| (k.clone(), *v))
-        .collect();
+    pending: &mut PendingUsage,
+    ledger: Option<&UsageLedger>,
+) -> bool {
+    pending.seal(unix_millis());
+    persist_ledger(ledger, pending).await;
+
+    let mut all_ok = true;
+    let mut flushed_keys: Vec<AccumulatorKey> = Vec::new();
 
-    for ((org_id, feature_id), value_gb) in &entries {
+    for (key, batch) in &pending.batches {
+        let (org_id, feature_id) = key;
         let body = TrackRequest {
             customer_id: org_id,
             feature_id,
-            value: *value_gb,
-            idempotency_key: Uuid::new_v4().to_string(),
+            value: batch.value_gb,
+            idempotency_key: &batch.idempotency_key,
         };
 
         let result: Result<reqwest::Response, reqwest::Error> = client
@@ -216,24 +611,191 @@ async fn flush_all(
 
         match result {
             Ok(resp) if resp.status().is_success() => {
-                accumulator.remove(&(org_id.clone(), feature_id));
+                flushed_keys.push(key.clone());
             }
             Ok(resp) => {
+                let status = resp.status();
+                let body_text = resp.text().await.unwrap_or_default();
                 warn!(
                     org_id,
                     feature_id,
-                    status = %resp.status(),
-                    "Final flush failed for entry"
+                    sequence = batch.sequence,
+                    status = %status,
+                    body = %body_text,
+                    "Autumn track request failed"
                 );
+                all_ok = false;
             }
             Err(err) => {
                 warn!(
                     org_id,
                     feature_id,
+                    sequence = batch.sequence,
                     error = %err,
-                    "Final flush failed for entry"
+                    "Autumn track request failed"
                 );
+                all_ok = false;
             }
         }
     }
+
+    // Remove successfully flushed batches
+    for key in &flushed_keys {
+        pending.batches.remove(key);
+    }
+    remove_from_ledger(ledger, &flushed_keys).await;
+
+    all_ok
+}
+
+/// Final flush on shutdown. A second round picks up usage that could not be
+/// sealed while an earlier batch for the same key was still in flight.
+async fn flush_all(
+    client: &Client,
+    secret_key: &str,
+    api_url: &str,
+    pending: &mut PendingUsage,
+    ledger: Option<&UsageLedger>,
+) {
+    for _ in 0..2 {
+        if flush_round(client, secret_key, api_url, pending, ledger).await && pending.is_empty() {
+            return;
+        }
+    }
+
+    warn!(
+        pending_entries = pending.len(),
+        total_pending_gb = pending.total_gb(),
+        durable = ledger.is_some(),
+        "Final flush left Autumn usage pending"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn ledger_round_trips_pending_usage_and_batches() {
+        let path =
+            std::env::temp_dir().join(format!("maple-ingest-ledger-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let ledger = UsageLedger::open(&path).await.unwrap();
+        let mut pending = PendingUsage::default();
+        pending
+            .accumulator
+            .insert(("org_a".to_string(), "traces"), 1.5);
+        pending.seal(1_000);
+        pending
+            .accumulator
+            .insert(("org_a".to_string(), "traces"), 0.5);
+        pending
+            .accumulator
+            .insert(("org_a".to_string(), "logs"), 0.25);
+        ledger.persist(&pending).await.unwrap();
+        drop(ledger);
+
+        let ledger = UsageLedger::open(&path).await.unwrap();
+        let recovered = ledger.load().await.unwrap();
+        let key = ("org_a".to_string(), "traces");
+        assert_eq!(recovered.batches.get(&key), pending.batches.get(&key));
+        assert_eq!(recovered.accumulator.get(&key), Some(&0.5));
+        assert_eq!(recovered.sequences.get(&key), Some(&1));
+
+        ledger.remove_batches(&[key]).await.unwrap();
+        assert!(ledger.load().await.unwrap().batches.is_empty());
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn unreadable_ledger_fails_startup_and_is_left_intact() {
+        let path = std::env::temp_dir().join(format!(
+            "maple-ingest-ledger-unreadable-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let ledger = UsageLedger::open(&path).await.unwrap();
+        let mut pending = PendingUsage::default();
+        pending
+            .accumulator
+            .insert(("org_a".to_string(), "logs"), 1.0);
+        ledger.persist(&pending).await.unwrap();
+        ledger
+            .conn
+            .execute_batch(
+                "DROP TABLE autumn_usage_batches;
+                 CREATE TABLE autumn_usage_batches (org_id TEXT);",
+            )
+            .await
+            .unwrap();
+
+        let tracker =
+            AutumnTracker::spawn("am_sk".to_string(), "http://127.0.0.1:1", 1, Some(ledger));
+        assert!(tracker.await.is_err());
+
+        let ledger = UsageLedger::open(&path).await.unwrap();
+        let mut rows = ledger
+            .conn
+            .query("SELECT value_gb FROM autumn_pending_usage", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<f64>(0).unwrap(), 1.0);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn persist_replaces_flushed_batches_left_behind_by_a_failed_remove() {
+        let path = std::env::temp_dir().join(format!(
+            "maple-ingest-ledger-stale-{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        let ledger = UsageLedger::open(&path).await.unwrap();
+        let key = ("org_a".to_string(), "metrics");
+        let mut pending = PendingUsage::default();
+        pending.accumulator.insert(key.clone(), 1.0);
+        pending.seal(1_000);
+        ledger.persist(&pending).await.unwrap();
+
+        // The batch flushed but its row was never removed.
+        pending.batches.remove(&key);
+        pending.accumulator.insert(key.clone(), 2.0);
+        pending.seal(2_000);
+        ledger.persist(&pending).await.unwrap();
+
+        let recovered = ledger.load().await.unwrap();
+        assert_eq!(recovered.batches.get(&key), pending.batches.get(&key));
+        assert_eq!(recovered.batches[&key].value_gb, 2.0);
+        assert_eq!(recovered.sequences.get(&key), Some(&2));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn sealed_batches_keep_their_idempotency_key_until_flushed() {
+        let key = ("org_a".to_string(), "logs");
+        let mut pending = PendingUsage::default();
+        pending.accumulator.insert(key.clone(), 1.0);
+        pending.seal(1_000);
+        let first = pending.batches[&key].clone();
+
+        // A retry tick must not reseal or rekey the in-flight batch.
+        pending.accumulator.insert(key.clone(), 2.0);
+        pending.seal(2_000);
+        assert_eq!(pending.batches[&key], first);
+        assert_eq!(pending.accumulator[&key], 2.0);
+
+        pending.batches.remove(&key);
+        pending.seal(3_000);
+        let second = &pending.batches[&key];
+        assert_eq!(second.sequence, first.sequence + 1);
+        assert_ne!(second.idempotency_key, first.idempotency_key);
+        assert_eq!(first.idempotency_key, batch_idempotency_key(&key, 1_000, 0));
+    }
 }
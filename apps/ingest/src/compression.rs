@@ -0,0 +1,231 @@
+use std::io::{Read, Write};
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+
+/// `Content-Encoding` values the gateway can decode and produce.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ContentEncoding {
+    Gzip,
+    /// zlib-wrapped deflate, as RFC 9110 defines it. Raw deflate streams,
+    /// which some clients send instead, are accepted on ingress.
+    Deflate,
+    Zstd,
+    /// Snappy block format, as used by the Collector and Prometheus remote write.
+    Snappy,
+    /// Snappy framing format.
+    SnappyFramed,
+}
+
+impl ContentEncoding {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "gzip" | "x-gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            "zstd" => Some(Self::Zstd),
+            "snappy" => Some(Self::Snappy),
+            "x-snappy-framed" => Some(Self::SnappyFramed),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Zstd => "zstd",
+            Self::Snappy => "snappy",
+            Self::SnappyFramed => "x-snappy-framed",
+        }
+    }
+
+    /// Decompresses `body`, giving up as soon as the output would exceed
+    /// `max_bytes` so a small bomb never expands fully in memory.
+    pub fn decode(self, body: &[u8], max_bytes: usize) -> Result<Vec<u8>, DecodeError> {
+        match self {
+            Self::Gzip => read_limited(GzDecoder::new(body), max_bytes),
+            Self::Deflate => match read_limited(ZlibDecoder::new(body), max_bytes) {
+                Err(DecodeError::Invalid(_)) => read_limited(DeflateDecoder::new(body), max_bytes),
+                result => result,
+            },
+            Self::Zstd => {
+                let decoder = zstd::stream::read::Decoder::new(body)
+                    .map_err(|error| DecodeError::Invalid(error.to_string()))?;
+                read_limited(decoder, max_bytes)
+            }
+            Self::Snappy => {
+                // The block format states its decoded length up front.
+                let decoded_len = snap::raw::decompress_len(body)
+                    .map_err(|error| DecodeError::Invalid(error.to_string()))?;
+                if decoded_len > max_bytes {
+                    return Err(DecodeError::TooLarge);
+                }
+                snap::raw::Decoder::new()
+                    .decompress_vec(body)
+                    .map_err(|error| DecodeError::Invalid(error.to_string()))
+            }
+            Self::SnappyFramed => read_limited(snap::read::FrameDecoder::new(body), max_bytes),
+        }
+    }
+
+    pub fn encode(self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder
+                    .write_all(payload)
+                    .map_err(|error| error.to_string())?;
+                encoder.finish().map_err(|error| error.to_string())
+            }
+            Self::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+                encoder
+                    .write_all(payload)
+                    .map_err(|error| error.to_string())?;
+                encoder.finish().map_err(|error| error.to_string())
+            }
+            Self::Zstd => zstd::stream::encode_all(payload, 0).map_err(|error| error.to_string()),
+            Self::Snappy => snap::raw::Encoder::new()
+                .compress_vec(payload)
+                .map_err(|error| error.to_string()),
+            Self::SnappyFramed => {
+                let mut encoder = snap::write::FrameEncoder::new(Vec::new());
+                encoder
+                    .write_all(payload)
+                    .map_err(|error| error.to_string())?;
+                encoder
+                    .into_inner()
+                    .map_err(|error| error.into_error().to_string())
+            }
+        }
+    }
+}
+
+pub enum DecodeError {
+    Invalid(String),
+    TooLarge,
+}
+
+fn read_limited(reader: impl Read, max_bytes: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut decompressed = Vec::new();
+    reader
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|error| DecodeError::Invalid(error.to_string()))?;
+
+    if decompressed.len() > max_bytes {
+        return Err(DecodeError::TooLarge);
+    }
+    Ok(decompressed)
+}
+
+/// Bounds on how far a request body may expand when decompressed.
+#[derive(Clone, Copy)]
+pub struct DecodeLimits {
+    pub max_decoded_bytes: usize,
+    /// Maximum decoded/encoded size ratio; `0` disables the ratio check.
+    pub max_ratio: usize,
+}
+
+impl DecodeLimits {
+    /// Small bodies may legitimately compress far better than the ratio
+    /// allows, so it only applies above this decoded size.
+    const RATIO_FLOOR_BYTES: usize = 1024 * 1024;
+
+    pub fn max_for(self, encoded_bytes: usize) -> usize {
+        if self.max_ratio == 0 {
+            return self.max_decoded_bytes;
+        }
+        let by_ratio = encoded_bytes
+            .saturating_mul(self.max_ratio)
+            .max(Self::RATIO_FLOOR_BYTES);
+        self.max_decoded_bytes.min(by_ratio)
+    }
+}
+
+/// What the gateway applies to forwarded payloads.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ForwardCompression {
+    /// Re-encode with whatever the client sent.
+    Passthrough,
+    None,
+    Fixed(ContentEncoding),
+}
+
+impl ForwardCompression {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "passthrough" => Some(Self::Passthrough),
+            "none" | "identity" => Some(Self::None),
+            other => ContentEncoding::parse(other).map(Self::Fixed),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Passthrough => "passthrough",
+            Self::None => "none",
+            Self::Fixed(encoding) => encoding.as_str(),
+        }
+    }
+
+    pub fn resolve(self, inbound: Option<ContentEncoding>) -> Option<ContentEncoding> {
+        match self {
+            Self::Passthrough => inbound,
+            Self::None => None,
+            Self::Fixed(encoding) => Some(encoding),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_encoding_round_trips() {
+        let payload = b"maple ingest payload ".repeat(64);
+        for encoding in [
+            ContentEncoding::Gzip,
+            ContentEncoding::Deflate,
+            ContentEncoding::Zstd,
+            ContentEncoding::Snappy,
+            ContentEncoding::SnappyFramed,
+        ] {
+            let encoded = encoding.encode(&payload).unwrap();
+            let decoded = encoding.decode(&encoded, payload.len()).ok().unwrap();
+            assert_eq!(decoded, payload, "{encoding:?}");
+            assert!(
+                matches!(
+                    encoding.decode(&encoded, payload.len() - 1),
+                    Err(DecodeError::TooLarge)
+                ),
+                "{encoding:?} ignored the decoded-size limit"
+            );
+            assert_eq!(ContentEncoding::parse(encoding.as_str()), Some(encoding));
+        }
+    }
+
+    #[test]
+    fn deflate_accepts_raw_streams() {
+        let payload = b"raw deflate without a zlib header".to_vec();
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&payload).unwrap();
+        let raw = encoder.finish().unwrap();
+
+        let decoded = ContentEncoding::Deflate.decode(&raw, 1024).ok().unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn ratio_limit_applies_above_the_floor() {
+        let limits = DecodeLimits {
+            max_decoded_bytes: 64 * 1024 * 1024,
+            max_ratio: 100,
+        };
+        assert_eq!(limits.max_for(1_000), DecodeLimits::RATIO_FLOOR_BYTES);
+        assert_eq!(limits.max_for(100_000), 10_000_000);
+        assert_eq!(limits.max_for(10_000_000), 64 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,728 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::header::CONTENT_TYPE;
+use axum::http::uri::PathAndQuery;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use bytes::{Buf, BufMut, Bytes};
+use metrics::{counter, gauge, histogram};
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{
+    LogsService, LogsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::metrics_service_server::{
+    MetricsService, MetricsServiceServer,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::{
+    TraceService, TraceServiceServer,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use prost::Message;
+use tonic::codec::{Codec, CompressionEncoding, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::server::Router;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint, Server};
+use tonic::{Code, Request, Response, Status};
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::compression::ContentEncoding;
+use crate::ratelimit::ThrottleLimit;
+use crate::retry::Outcome;
+use crate::sampling::SamplingPolicy;
+use crate::validate;
+use crate::{
+    authenticate, authorize_key, count_log_items, count_metric_items, count_trace_items,
+    deliver_enriched, enforce_quota, enforce_rate_limit, prepare_logs_request,
+    prepare_metrics_request, prepare_trace_request, record_request_error, record_request_ok,
+    record_sampled_out, sampling_policy, upstream_status_bucket, ApiError, AppState, EnrichResult,
+    InFlightGuard, PayloadFormat, ResolvedIngestKey, Signal, QUOTA_WARNING_HEADER,
+};
+
+/// OTLP/gRPC receiver. Shares authentication, enrichment and forwarding with
+/// the HTTP routes; only the transport and the error mapping differ.
+#[derive(Clone)]
+pub struct GrpcIngest {
+    state: Arc<AppState>,
+}
+
+impl GrpcIngest {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub fn into_server(self, max_message_bytes: usize) -> Router {
+        let traces = TraceServiceServer::new(self.clone())
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd)
+            .accept_compressed(CompressionEncoding::Deflate)
+            .max_decoding_message_size(max_message_bytes);
+        let logs = LogsServiceServer::new(self.clone())
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd)
+            .accept_compressed(CompressionEncoding::Deflate)
+            .max_decoding_message_size(max_message_bytes);
+        let metrics = MetricsServiceServer::new(self)
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd)
+            .accept_compressed(CompressionEncoding::Deflate)
+            .max_decoding_message_size(max_message_bytes);
+
+        Server::builder()
+            .add_service(traces)
+            .add_service(logs)
+            .add_service(metrics)
+    }
+
+    /// Authenticates, enriches and forwards one export call, returning the
+    /// collector's response body on success along with any quota warning.
+    async fn ingest<F>(
+        &self,
+        signal: Signal,
+        metadata: MetadataMap,
+        decoded_bytes: usize,
+        enrich: F,
+    ) -> Result<Response<Vec<u8>>, Status>
+    where
+        F: FnOnce(&ResolvedIngestKey, Option<&SamplingPolicy>) -> EnrichResult + Send,
+    {
+        let start = Instant::now();
+
+        gauge!("ingest_requests_in_flight").increment(1.0);
+        let _guard = InFlightGuard;
+
+        let span = tracing::info_span!(
+            "ingest",
+            signal = signal.path(),
+            protocol = "grpc",
+            decoded_bytes,
+            org_id = tracing::field::Empty,
+            key_type = tracing::field::Empty,
+        );
+
+        async move {
+            let headers = metadata.into_headers();
+            let result = self
+                .ingest_inner(signal, &headers, decoded_bytes, enrich)
+                .await;
+            let duration = start.elapsed();
+            let duration_ms = duration.as_millis() as u64;
+
+            match result {
+                Ok((status, body, item_count, org_id, billable_bytes, soft_limited)) => {
+                    record_request_ok(&self.state, signal, duration, &org_id, billable_bytes);
+                    info!(
+                        status = status.as_u16(),
+                        duration_ms, item_count, "Request processed"
+                    );
+
+                    if status.is_success() {
+                        let mut response = Response::new(body);
+                        if soft_limited {
+                            response.metadata_mut().insert(
+                                QUOTA_WARNING_HEADER,
+                                MetadataValue::from_static("soft-limit-reached"),
+                            );
+                        }
+                        Ok(response)
+                    } else {
+                        // The body is an OTLP/HTTP error payload, not text.
+                        Err(Status::new(
+                            grpc_code_for_http_status(status),
+                            format!("Upstream collector responded with HTTP {}", status.as_u16()),
+                        ))
+                    }
+                }
+                Err((error, error_kind)) => {
+                    record_request_error(signal, duration, error_kind);
+                    let code = grpc_code_for_http_status(error.status);
+                    match error.retry_after_secs {
+                        // RESOURCE_EXHAUSTED is only retryable for OTLP clients
+                        // when it carries RetryInfo.
+                        Some(retry_after_secs) => {
+                            let details =
+                                rpc_status_with_retry_info(code, &error.message, retry_after_secs)
+                                    .encode_to_vec();
+                            Err(Status::with_details(code, error.message, details.into()))
+                        }
+                        None => Err(Status::new(code, error.message)),
+                    }
+                }
+            }
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn ingest_inner<F>(
+        &self,
+        signal: Signal,
+        headers: &axum::http::HeaderMap,
+        decoded_bytes: usize,
+        enrich: F,
+    ) -> Result<(StatusCode, Vec<u8>, usize, String, usize, bool), (ApiError, &'static str)>
+    where
+        F: FnOnce(&ResolvedIngestKey, Option<&SamplingPolicy>) -> EnrichResult,
+    {
+        let resolved_key = authenticate(&self.state, headers).await?;
+        authorize_key(&self.state, &resolved_key, headers, signal)?;
+        enforce_rate_limit(
+            &self.state,
+            &resolved_key,
+            signal,
+            ThrottleLimit::Requests,
+            1,
+        )
+        .await?;
+        let soft_limited = enforce_quota(&self.state, &resolved_key, signal).await?;
+        // The server-wide message limit is the private one; tonic has already
+        // decoded the request, so the public limit is applied here.
+        let (_, decode_limits) = self.state.config.body_limits(resolved_key.key_type);
+        if decoded_bytes > decode_limits.max_decoded_bytes {
+            return Err((
+                ApiError::payload_too_large("Request body too large"),
+                "decoded_too_large",
+            ));
+        }
+        enforce_rate_limit(
+            &self.state,
+            &resolved_key,
+            signal,
+            ThrottleLimit::Bytes,
+            decoded_bytes,
+        )
+        .await?;
+
+        let sampling_policy = sampling_policy(&self.state, &resolved_key, signal).await;
+        let enrich_result = enrich(&resolved_key, sampling_policy.as_deref());
+
+        debug!(item_count = enrich_result.item_count, "Payload enriched");
+        counter!(
+            "ingest_items_total",
+            "signal" => signal.path(),
+            "org_id" => resolved_key.org_id.clone()
+        )
+        .increment(enrich_result.item_count as u64);
+        if !enrich_result.rejections.is_empty() {
+            warn!(
+                rejected = enrich_result.rejections.count,
+                "Dropped invalid items"
+            );
+            enrich_result.rejections.record(signal);
+        }
+        record_sampled_out(&resolved_key, enrich_result.sampled_out);
+
+        let billable_bytes = enrich_result.billable_bytes(decoded_bytes);
+        let EnrichResult {
+            payload,
+            item_count,
+            rejections,
+            sampled_out,
+        } = enrich_result;
+
+        if item_count == 0 && (!rejections.is_empty() || sampled_out > 0) {
+            // Nothing is left to forward.
+            let body = if rejections.is_empty() {
+                Vec::new()
+            } else {
+                validate::partial_success_body(signal, PayloadFormat::Protobuf, &[], &rejections)
+            };
+            return Ok((
+                StatusCode::OK,
+                body,
+                item_count,
+                resolved_key.org_id,
+                billable_bytes,
+                soft_limited,
+            ));
+        }
+
+        // tonic has already decompressed the request, so there is nothing to
+        // pass through; only an explicitly configured encoding applies.
+        let outbound_encoding = self.state.config.forward_compression.resolve(None);
+        let response = deliver_enriched(
+            &self.state,
+            signal,
+            PayloadFormat::Protobuf,
+            outbound_encoding.map(ContentEncoding::as_str),
+            Bytes::from(payload),
+            &resolved_key,
+        )
+        .await?;
+
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map_err(|_| {
+                (
+                    ApiError::service_unavailable("Telemetry backend unavailable"),
+                    "forward",
+                )
+            })?;
+
+        let body = if status.is_success() && !rejections.is_empty() {
+            validate::partial_success_body(signal, PayloadFormat::Protobuf, &body, &rejections)
+        } else {
+            body.to_vec()
+        };
+
+        Ok((
+            status,
+            body,
+            item_count,
+            resolved_key.org_id,
+            billable_bytes,
+            soft_limited,
+        ))
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for GrpcIngest {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let (metadata, _, mut message) = request.into_parts();
+        let decoded_bytes = message.encoded_len();
+
+        let response = self
+            .ingest(
+                Signal::Traces,
+                metadata,
+                decoded_bytes,
+                |resolved_key, sampling_policy| {
+                    let (rejections, sampled_out) = prepare_trace_request(
+                        &self.state,
+                        &mut message,
+                        resolved_key,
+                        sampling_policy,
+                    );
+                    EnrichResult {
+                        item_count: count_trace_items(&message),
+                        payload: message.encode_to_vec(),
+                        rejections,
+                        sampled_out,
+                    }
+                },
+            )
+            .await?;
+
+        Ok(response
+            .map(|body| ExportTraceServiceResponse::decode(body.as_slice()).unwrap_or_default()))
+    }
+}
+
+#[tonic::async_trait]
+impl LogsService for GrpcIngest {
+    async fn export(
+        &self,
+        request: Request<ExportLogsServiceRequest>,
+    ) -> Result<Response<ExportLogsServiceResponse>, Status> {
+        let (metadata, _, mut message) = request.into_parts();
+        let decoded_bytes = message.encoded_len();
+
+        let response = self
+            .ingest(Signal::Logs, metadata, decoded_bytes, |resolved_key, _| {
+                let rejections = prepare_logs_request(&self.state, &mut message, resolved_key);
+                EnrichResult {
+                    item_count: count_log_items(&message),
+                    payload: message.encode_to_vec(),
+                    rejections,
+                    sampled_out: 0,
+                }
+            })
+            .await?;
+
+        Ok(response
+            .map(|body| ExportLogsServiceResponse::decode(body.as_slice()).unwrap_or_default()))
+    }
+}
+
+#[tonic::async_trait]
+impl MetricsService for GrpcIngest {
+    async fn export(
+        &self,
+        request: Request<ExportMetricsServiceRequest>,
+    ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
+        let (metadata, _, mut message) = request.into_parts();
+        let decoded_bytes = message.encoded_len();
+
+        let response = self
+            .ingest(
+                Signal::Metrics,
+                metadata,
+                decoded_bytes,
+                |resolved_key, _| {
+                    let rejections = prepare_metrics_request(&mut message, resolved_key);
+                    EnrichResult {
+                        item_count: count_metric_items(&message),
+                        payload: message.encode_to_vec(),
+                        rejections,
+                        sampled_out: 0,
+                    }
+                },
+            )
+            .await?;
+
+        Ok(response
+            .map(|body| ExportMetricsServiceResponse::decode(body.as_slice()).unwrap_or_default()))
+    }
+}
+
+/// Forwards enriched payloads to the collector over one persistent gRPC
+/// channel instead of OTLP/HTTP. Payloads are already-encoded protobuf, so
+/// they go out through [`RawCodec`] without a second decode.
+pub struct GrpcForwarder {
+    channel: Channel,
+    route: String,
+    endpoint: String,
+}
+
+impl GrpcForwarder {
+    pub fn connect_lazy(
+        route: &str,
+        endpoint_url: &str,
+        timeout: Duration,
+    ) -> Result<Self, String> {
+        let mut endpoint = Endpoint::from_shared(endpoint_url.to_string())
+            .map_err(|error| format!("Invalid endpoint for route '{route}': {error}"))?
+            .timeout(timeout)
+            .connect_timeout(timeout)
+            .tcp_keepalive(Some(Duration::from_secs(30)))
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .keep_alive_while_idle(true);
+
+        if endpoint_url.starts_with("https://") {
+            endpoint = endpoint
+                .tls_config(ClientTlsConfig::new().with_webpki_roots())
+                .map_err(|error| format!("Invalid gRPC TLS config: {error}"))?;
+        }
+
+        Ok(Self {
+            channel: endpoint.connect_lazy(),
+            route: route.to_string(),
+            endpoint: endpoint_url.to_string(),
+        })
+    }
+
+    /// Sends one export call, bounded by `timeout`, and maps the outcome the
+    /// same way the HTTP forwarder does: 5xx-equivalent codes become a 503,
+    /// anything else is passed back to the client with the equivalent HTTP
+    /// status.
+    pub async fn forward(
+        &self,
+        signal: Signal,
+        payload_format: PayloadFormat,
+        content_encoding: Option<&str>,
+        payload: Bytes,
+        resolved_key: &ResolvedIngestKey,
+        timeout: Duration,
+    ) -> (Result<axum::response::Response, ApiError>, Outcome) {
+        let outbound_bytes = payload.len();
+
+        debug!(endpoint = %self.endpoint, outbound_bytes, "Forwarding to collector over gRPC");
+
+        let mut client = tonic::client::Grpc::new(self.channel.clone());
+        // gRPC has no snappy codec; those payloads go out uncompressed.
+        let compression = match content_encoding.and_then(ContentEncoding::parse) {
+            Some(ContentEncoding::Gzip) => Some(CompressionEncoding::Gzip),
+            Some(ContentEncoding::Zstd) => Some(CompressionEncoding::Zstd),
+            Some(ContentEncoding::Deflate) => Some(CompressionEncoding::Deflate),
+            Some(ContentEncoding::Snappy | ContentEncoding::SnappyFramed) | None => None,
+        };
+        if let Some(compression) = compression {
+            client = client.send_compressed(compression);
+        }
+
+        let forward_start = Instant::now();
+        let mut request = Request::new(payload);
+        request.set_timeout(timeout);
+        let result = match client.ready().await {
+            Ok(()) => client
+                .unary(
+                    request,
+                    PathAndQuery::from_static(export_path(signal)),
+                    RawCodec,
+                )
+                .await
+                .map(Response::into_inner),
+            Err(error) => Err(Status::unavailable(error.to_string())),
+        };
+
+        let forward_duration = forward_start.elapsed();
+        histogram!("ingest_forward_duration_seconds", "signal" => signal.path(), "route" => self.route.clone())
+            .record(forward_duration.as_secs_f64());
+
+        let upstream_status = match &result {
+            Ok(_) => StatusCode::OK,
+            Err(status) => http_status_for_grpc_code(status.code()),
+        };
+        counter!(
+            "ingest_forward_responses_total",
+            "signal" => signal.path(),
+            "route" => self.route.clone(),
+            "upstream_status" => upstream_status_bucket(upstream_status.as_u16())
+        )
+        .increment(1);
+
+        debug!(
+            upstream_status = upstream_status.as_u16(),
+            forward_ms = forward_duration.as_millis() as u64,
+            "Collector response"
+        );
+
+        let outcome = Outcome::from_status(upstream_status.as_u16(), None);
+        let result = match result {
+            Ok(body) => {
+                let body = export_response_body(signal, payload_format, body);
+                axum::response::Response::builder()
+                    .status(StatusCode::OK)
+                    .header(CONTENT_TYPE, payload_format.content_type())
+                    .body(axum::body::Body::from(body))
+                    .map_err(|_| ApiError::service_unavailable("Telemetry backend unavailable"))
+            }
+            Err(status) if upstream_status.is_server_error() => {
+                error!(
+                    grpc_code = ?status.code(),
+                    error = %status.message(),
+                    signal = signal.path(),
+                    org_id = %resolved_key.org_id,
+                    key_id = %resolved_key.key_id,
+                    route = %self.route,
+                    endpoint = %self.endpoint,
+                    "Collector forwarding failed"
+                );
+                Err(ApiError::service_unavailable(
+                    "Telemetry backend unavailable",
+                ))
+            }
+            Err(status) => Ok(ApiError::new(upstream_status, status.message()).into_response()),
+        };
+        (result, outcome)
+    }
+}
+
+fn export_path(signal: Signal) -> &'static str {
+    match signal {
+        Signal::Traces => "/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+        Signal::Logs => "/opentelemetry.proto.collector.logs.v1.LogsService/Export",
+        Signal::Metrics => "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export",
+    }
+}
+
+/// Renders the collector's protobuf `Export*ServiceResponse` in the format
+/// the client used.
+fn export_response_body(signal: Signal, payload_format: PayloadFormat, body: Bytes) -> Vec<u8> {
+    if let PayloadFormat::Protobuf = payload_format {
+        return body.to_vec();
+    }
+
+    let json = match signal {
+        Signal::Traces => ExportTraceServiceResponse::decode(body)
+            .ok()
+            .and_then(|response| serde_json::to_vec(&response).ok()),
+        Signal::Logs => ExportLogsServiceResponse::decode(body)
+            .ok()
+            .and_then(|response| serde_json::to_vec(&response).ok()),
+        Signal::Metrics => ExportMetricsServiceResponse::decode(body)
+            .ok()
+            .and_then(|response| serde_json::to_vec(&response).ok()),
+    };
+
+    json.unwrap_or_else(|| b"{}".to_vec())
+}
+
+/// Pass-through codec for payloads that are already protobuf-encoded.
+#[derive(Clone, Copy, Default)]
+struct RawCodec;
+
+impl Codec for RawCodec {
+    type Encode = Bytes;
+    type Decode = Bytes;
+    type Encoder = RawCodec;
+    type Decoder = RawCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        RawCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        RawCodec
+    }
+}
+
+impl Encoder for RawCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for RawCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(Some(src.copy_to_bytes(src.remaining())))
+    }
+}
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+/// `google.rpc.Status`, as carried in `grpc-status-details-bin` and in OTLP/HTTP
+/// error bodies.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    pub code: i32,
+    #[prost(string, tag = "2")]
+    pub message: String,
+    #[prost(message, repeated, tag = "3")]
+    pub details: Vec<ProtoAny>,
+}
+
+/// `google.protobuf.Any`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProtoAny {
+    #[prost(string, tag = "1")]
+    pub type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    pub value: Vec<u8>,
+}
+
+/// `google.rpc.RetryInfo`.
+#[derive(Clone, PartialEq, prost::Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<ProtoDuration>,
+}
+
+/// `google.protobuf.Duration`.
+#[derive(Clone, PartialEq, prost::Message)]
+struct ProtoDuration {
+    #[prost(int64, tag = "1")]
+    seconds: i64,
+    #[prost(int32, tag = "2")]
+    nanos: i32,
+}
+
+pub fn rpc_status_with_retry_info(code: Code, message: &str, retry_after_secs: u64) -> RpcStatus {
+    let retry_info = RetryInfo {
+        retry_delay: Some(ProtoDuration {
+            seconds: retry_after_secs as i64,
+            nanos: 0,
+        }),
+    };
+
+    RpcStatus {
+        code: code as i32,
+        message: message.to_string(),
+        details: vec![ProtoAny {
+            type_url: RETRY_INFO_TYPE_URL.to_string(),
+            value: retry_info.encode_to_vec(),
+        }],
+    }
+}
+
+/// Renders a throttling `google.rpc.Status` in the format the client used.
+/// The JSON form follows the proto3 JSON mapping for `Any` and `Duration`.
+pub fn throttled_body(
+    payload_format: PayloadFormat,
+    message: &str,
+    retry_after_secs: u64,
+) -> Vec<u8> {
+    let code = Code::ResourceExhausted;
+    match payload_format {
+        PayloadFormat::Protobuf => {
+            rpc_status_with_retry_info(code, message, retry_after_secs).encode_to_vec()
+        }
+        PayloadFormat::Json => serde_json::to_vec(&serde_json::json!({
+            "code": code as i32,
+            "message": message,
+            "details": [{
+                "@type": RETRY_INFO_TYPE_URL,
+                "retryDelay": format!("{retry_after_secs}s"),
+            }],
+        }))
+        .unwrap_or_default(),
+    }
+}
+
+fn http_status_for_grpc_code(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound | Code::Unimplemented => StatusCode::NOT_FOUND,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unavailable | Code::Aborted | Code::DeadlineExceeded => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Maps HTTP statuses onto gRPC codes following the OTLP spec, so clients
+/// retry on the same conditions over either transport.
+fn grpc_code_for_http_status(status: StatusCode) -> Code {
+    match status.as_u16() {
+        200..=299 => Code::Ok,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::Unimplemented,
+        429 => Code::ResourceExhausted,
+        400..=499 => Code::InvalidArgument,
+        502..=504 => Code::Unavailable,
+        _ => Code::Internal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retryable_http_statuses_map_to_retryable_grpc_codes() {
+        assert_eq!(
+            grpc_code_for_http_status(StatusCode::SERVICE_UNAVAILABLE),
+            Code::Unavailable
+        );
+        assert_eq!(
+            grpc_code_for_http_status(StatusCode::TOO_MANY_REQUESTS),
+            Code::ResourceExhausted
+        );
+        assert_eq!(
+            grpc_code_for_http_status(StatusCode::UNAUTHORIZED),
+            Code::Unauthenticated
+        );
+        assert_eq!(
+            grpc_code_for_http_status(StatusCode::PAYLOAD_TOO_LARGE),
+            Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn collector_grpc_failures_map_to_server_errors() {
+        assert!(http_status_for_grpc_code(Code::Unavailable).is_server_error());
+        assert!(http_status_for_grpc_code(Code::Internal).is_server_error());
+        assert!(http_status_for_grpc_code(Code::DeadlineExceeded).is_server_error());
+        assert_eq!(
+            http_status_for_grpc_code(Code::InvalidArgument),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
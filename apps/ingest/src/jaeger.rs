@@ -0,0 +1,641 @@
+use std::collections::HashMap;
+
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::span::{Event, Link, SpanKind};
+use opentelemetry_proto::tonic::trace::v1::status::StatusCode;
+use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+use prost::Message;
+
+use crate::receiver::{
+    bool_attribute, bytes_attribute, double_attribute, int_attribute, string_attribute,
+    UNKNOWN_SERVICE,
+};
+use crate::thrift::{self, Value};
+use crate::ApiError;
+
+/// Jaeger's `Process`: the service and its tags, which become the resource.
+#[derive(PartialEq)]
+struct Process {
+    service_name: String,
+    tags: Vec<KeyValue>,
+}
+
+/// A span from either Jaeger encoding, with IDs as OTLP-sized bytes and
+/// times in nanoseconds.
+struct JaegerSpan {
+    trace_id: Vec<u8>,
+    span_id: Vec<u8>,
+    parent_span_id: Vec<u8>,
+    /// `(trace_id, span_id)` of references that are not the parent.
+    links: Vec<(Vec<u8>, Vec<u8>)>,
+    operation_name: String,
+    start_ns: u64,
+    duration_ns: u64,
+    tags: Vec<KeyValue>,
+    logs: Vec<(u64, Vec<KeyValue>)>,
+}
+
+/// Translates a Jaeger batch into an OTLP trace request. Thrift batches use
+/// the binary protocol unless the content type asks for compact;
+/// `application/x-protobuf` is `jaeger.api_v2.Batch` from `model.proto`.
+pub fn translate(content_type: &str, payload: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let batches = if content_type.contains("protobuf") {
+        let batch = proto::Batch::decode(payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Jaeger protobuf: {error}")))?;
+        from_proto(batch)
+    } else if content_type.contains("thrift") {
+        let protocol = if content_type.contains("compact") {
+            thrift::Protocol::Compact
+        } else {
+            thrift::Protocol::Binary
+        };
+        let batch = thrift::decode_struct(protocol, payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Jaeger Thrift: {error}")))?;
+        vec![from_thrift(&batch)]
+    } else {
+        return Err(ApiError::unsupported_media_type(
+            "Unsupported content-type, expected application/x-thrift, application/vnd.apache.thrift.compact or application/x-protobuf",
+        ));
+    };
+
+    let request = ExportTraceServiceRequest {
+        resource_spans: batches
+            .into_iter()
+            .filter(|(_, spans)| !spans.is_empty())
+            .map(|(process, spans)| to_resource_spans(process, spans))
+            .collect(),
+    };
+    Ok(request.encode_to_vec())
+}
+
+fn to_resource_spans(process: Process, spans: Vec<JaegerSpan>) -> ResourceSpans {
+    let service_name = if process.service_name.is_empty() {
+        UNKNOWN_SERVICE.to_string()
+    } else {
+        process.service_name
+    };
+    let mut attributes = vec![string_attribute("service.name", service_name)];
+    attributes.extend(process.tags);
+
+    ResourceSpans {
+        resource: Some(Resource {
+            attributes,
+            ..Default::default()
+        }),
+        scope_spans: vec![ScopeSpans {
+            scope: Some(InstrumentationScope {
+                name: "jaeger".to_string(),
+                ..Default::default()
+            }),
+            spans: spans.into_iter().map(to_otlp_span).collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn to_otlp_span(span: JaegerSpan) -> Span {
+    let mut kind = SpanKind::Internal;
+    let mut status = None;
+    let mut attributes = Vec::with_capacity(span.tags.len());
+    for tag in span.tags {
+        let text = match tag.value.as_ref().and_then(|value| value.value.as_ref()) {
+            Some(any_value::Value::StringValue(text)) => Some(text.clone()),
+            Some(any_value::Value::BoolValue(flag)) => Some(flag.to_string()),
+            _ => None,
+        };
+        match (tag.key.as_str(), text) {
+            ("span.kind", Some(text)) => {
+                kind = match text.as_str() {
+                    "client" => SpanKind::Client,
+                    "server" => SpanKind::Server,
+                    "producer" => SpanKind::Producer,
+                    "consumer" => SpanKind::Consumer,
+                    _ => SpanKind::Internal,
+                };
+            }
+            ("error", Some(text)) => {
+                if text == "true" {
+                    status.get_or_insert_with(Status::default).code = StatusCode::Error as i32;
+                }
+            }
+            ("otel.status_code", Some(text)) => {
+                let code = match text.to_ascii_uppercase().as_str() {
+                    "ERROR" => StatusCode::Error,
+                    "OK" => StatusCode::Ok,
+                    _ => StatusCode::Unset,
+                };
+                status.get_or_insert_with(Status::default).code = code as i32;
+            }
+            ("otel.status_description", Some(text)) => {
+                status.get_or_insert_with(Status::default).message = text;
+            }
+            _ => attributes.push(tag),
+        }
+    }
+
+    Span {
+        trace_id: span.trace_id,
+        span_id: span.span_id,
+        parent_span_id: span.parent_span_id,
+        name: span.operation_name,
+        kind: kind as i32,
+        start_time_unix_nano: span.start_ns,
+        end_time_unix_nano: span.start_ns.saturating_add(span.duration_ns),
+        attributes,
+        events: span
+            .logs
+            .into_iter()
+            .map(|(time_unix_nano, fields)| to_event(time_unix_nano, fields))
+            .collect(),
+        links: span
+            .links
+            .into_iter()
+            .map(|(trace_id, span_id)| Link {
+                trace_id,
+                span_id,
+                ..Default::default()
+            })
+            .collect(),
+        status,
+        ..Default::default()
+    }
+}
+
+/// A span log becomes an event named by its `event` field, or "log".
+fn to_event(time_unix_nano: u64, mut fields: Vec<KeyValue>) -> Event {
+    let position = fields.iter().position(|field| {
+        field.key == "event"
+            && matches!(
+                field.value.as_ref().and_then(|value| value.value.as_ref()),
+                Some(any_value::Value::StringValue(_))
+            )
+    });
+    let name = position
+        .and_then(|position| match fields.remove(position).value?.value {
+            Some(any_value::Value::StringValue(name)) => Some(name),
+            _ => None,
+        })
+        .unwrap_or_else(|| "log".to_string());
+
+    Event {
+        time_unix_nano,
+        name,
+        attributes: fields,
+        ..Default::default()
+    }
+}
+
+/// The first child-of reference in the same trace is the parent; all other
+/// references become links.
+fn split_references(
+    trace_id: &[u8],
+    parent_span_id: &mut Vec<u8>,
+    references: Vec<(bool, Vec<u8>, Vec<u8>)>,
+) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut links = Vec::new();
+    for (child_of, ref_trace_id, ref_span_id) in references {
+        if ref_span_id == *parent_span_id && ref_trace_id == trace_id {
+            continue;
+        }
+        if child_of && parent_span_id.is_empty() && ref_trace_id == trace_id {
+            *parent_span_id = ref_span_id;
+        } else {
+            links.push((ref_trace_id, ref_span_id));
+        }
+    }
+    links
+}
+
+fn from_thrift(batch: &Value) -> (Process, Vec<JaegerSpan>) {
+    let process = batch.field(1);
+    let process = Process {
+        service_name: process
+            .and_then(|process| process.field(1))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        tags: thrift_tags(process.and_then(|process| process.field(2))),
+    };
+
+    let spans = batch
+        .field(2)
+        .map(Value::as_list)
+        .unwrap_or_default()
+        .iter()
+        .map(thrift_span)
+        .collect();
+    (process, spans)
+}
+
+fn thrift_span(span: &Value) -> JaegerSpan {
+    let int = |value: &Value, id| value.field(id).and_then(Value::as_int).unwrap_or_default();
+    let trace_id = thrift_trace_id(int(span, 1), int(span, 2));
+    let mut parent_span_id = match int(span, 4) {
+        0 => Vec::new(),
+        id => id.to_be_bytes().to_vec(),
+    };
+    let references = span
+        .field(6)
+        .map(Value::as_list)
+        .unwrap_or_default()
+        .iter()
+        .map(|reference| {
+            (
+                int(reference, 1) == 0,
+                thrift_trace_id(int(reference, 2), int(reference, 3)),
+                int(reference, 4).to_be_bytes().to_vec(),
+            )
+        })
+        .collect();
+    let links = split_references(&trace_id, &mut parent_span_id, references);
+
+    JaegerSpan {
+        span_id: int(span, 3).to_be_bytes().to_vec(),
+        trace_id,
+        parent_span_id,
+        links,
+        operation_name: span
+            .field(5)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        start_ns: (int(span, 8) as u64).saturating_mul(1_000),
+        duration_ns: (int(span, 9) as u64).saturating_mul(1_000),
+        tags: thrift_tags(span.field(10)),
+        logs: span
+            .field(11)
+            .map(Value::as_list)
+            .unwrap_or_default()
+            .iter()
+            .map(|log| {
+                (
+                    (int(log, 1) as u64).saturating_mul(1_000),
+                    thrift_tags(log.field(2)),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn thrift_trace_id(low: i64, high: i64) -> Vec<u8> {
+    let mut trace_id = high.to_be_bytes().to_vec();
+    trace_id.extend_from_slice(&low.to_be_bytes());
+    trace_id
+}
+
+fn thrift_tags(tags: Option<&Value>) -> Vec<KeyValue> {
+    tags.map(Value::as_list)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|tag| {
+            let key = tag.field(1).and_then(Value::as_str)?;
+            // Tag.vType: STRING, DOUBLE, BOOL, LONG, BINARY.
+            Some(match tag.field(2).and_then(Value::as_int) {
+                Some(1) => match tag.field(4) {
+                    Some(Value::Double(value)) => double_attribute(key, *value),
+                    _ => return None,
+                },
+                Some(2) => match tag.field(5) {
+                    Some(Value::Bool(value)) => bool_attribute(key, *value),
+                    _ => return None,
+                },
+                Some(3) => int_attribute(key, tag.field(6).and_then(Value::as_int)?),
+                Some(4) => match tag.field(7) {
+                    Some(Value::Binary(value)) => bytes_attribute(key, value.clone()),
+                    _ => return None,
+                },
+                _ => string_attribute(key, tag.field(3).and_then(Value::as_str)?),
+            })
+        })
+        .collect()
+}
+
+fn from_proto(batch: proto::Batch) -> Vec<(Process, Vec<JaegerSpan>)> {
+    let batch_process = batch.process.unwrap_or_default();
+    // Spans may carry their own process, overriding the batch's. Processes
+    // are grouped by their encoded form, since converted tags are not `Hash`.
+    let mut positions = HashMap::from([(batch_process.encode_to_vec(), 0)]);
+    let mut batches = vec![(proto_process(Some(batch_process)), Vec::new())];
+    for mut span in batch.spans {
+        let position = match span.process.take() {
+            Some(process) => *positions.entry(process.encode_to_vec()).or_insert_with(|| {
+                batches.push((proto_process(Some(process)), Vec::new()));
+                batches.len() - 1
+            }),
+            None => 0,
+        };
+        batches[position].1.push(proto_span(span));
+    }
+    batches
+}
+
+fn proto_process(process: Option<proto::Process>) -> Process {
+    let process = process.unwrap_or_default();
+    Process {
+        service_name: process.service_name,
+        tags: proto_tags(process.tags),
+    }
+}
+
+fn proto_span(span: proto::Span) -> JaegerSpan {
+    let mut parent_span_id = Vec::new();
+    let references = span
+        .references
+        .into_iter()
+        .map(|reference| {
+            (
+                reference.ref_type == 0,
+                reference.trace_id,
+                reference.span_id,
+            )
+        })
+        .collect();
+    let links = split_references(&span.trace_id, &mut parent_span_id, references);
+
+    JaegerSpan {
+        trace_id: span.trace_id,
+        span_id: span.span_id,
+        parent_span_id,
+        links,
+        operation_name: span.operation_name,
+        start_ns: span
+            .start_time
+            .map(proto::Timestamp::nanos)
+            .unwrap_or_default(),
+        duration_ns: span
+            .duration
+            .map(proto::Timestamp::nanos)
+            .unwrap_or_default(),
+        tags: proto_tags(span.tags),
+        logs: span
+            .logs
+            .into_iter()
+            .map(|log| {
+                (
+                    log.timestamp
+                        .map(proto::Timestamp::nanos)
+                        .unwrap_or_default(),
+                    proto_tags(log.fields),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn proto_tags(tags: Vec<proto::KeyValue>) -> Vec<KeyValue> {
+    tags.into_iter()
+        .map(|tag| match tag.v_type {
+            // ValueType: STRING, BOOL, INT64, FLOAT64, BINARY.
+            1 => bool_attribute(&tag.key, tag.v_bool),
+            2 => int_attribute(&tag.key, tag.v_int64),
+            3 => double_attribute(&tag.key, tag.v_float64),
+            4 => bytes_attribute(&tag.key, tag.v_binary),
+            _ => string_attribute(&tag.key, tag.v_str),
+        })
+        .collect()
+}
+
+/// The parts of `jaeger.api_v2` (`model.proto`) a batch needs, declared by
+/// hand.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Batch {
+        #[prost(message, repeated, tag = "1")]
+        pub spans: Vec<Span>,
+        #[prost(message, optional, tag = "2")]
+        pub process: Option<Process>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Span {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub span_id: Vec<u8>,
+        #[prost(string, tag = "3")]
+        pub operation_name: String,
+        #[prost(message, repeated, tag = "4")]
+        pub references: Vec<SpanRef>,
+        #[prost(message, optional, tag = "6")]
+        pub start_time: Option<Timestamp>,
+        #[prost(message, optional, tag = "7")]
+        pub duration: Option<Timestamp>,
+        #[prost(message, repeated, tag = "8")]
+        pub tags: Vec<KeyValue>,
+        #[prost(message, repeated, tag = "9")]
+        pub logs: Vec<Log>,
+        #[prost(message, optional, tag = "10")]
+        pub process: Option<Process>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SpanRef {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub span_id: Vec<u8>,
+        /// CHILD_OF = 0, FOLLOWS_FROM = 1.
+        #[prost(int32, tag = "3")]
+        pub ref_type: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Process {
+        #[prost(string, tag = "1")]
+        pub service_name: String,
+        #[prost(message, repeated, tag = "2")]
+        pub tags: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Log {
+        #[prost(message, optional, tag = "1")]
+        pub timestamp: Option<Timestamp>,
+        #[prost(message, repeated, tag = "2")]
+        pub fields: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct KeyValue {
+        #[prost(string, tag = "1")]
+        pub key: String,
+        #[prost(int32, tag = "2")]
+        pub v_type: i32,
+        #[prost(string, tag = "3")]
+        pub v_str: String,
+        #[prost(bool, tag = "4")]
+        pub v_bool: bool,
+        #[prost(int64, tag = "5")]
+        pub v_int64: i64,
+        #[prost(double, tag = "6")]
+        pub v_float64: f64,
+        #[prost(bytes = "vec", tag = "7")]
+        pub v_binary: Vec<u8>,
+    }
+
+    /// Wire-compatible with both `google.protobuf.Timestamp` and `Duration`.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Timestamp {
+        #[prost(int64, tag = "1")]
+        pub seconds: i64,
+        #[prost(int32, tag = "2")]
+        pub nanos: i32,
+    }
+
+    impl Timestamp {
+        pub fn nanos(self) -> u64 {
+            (self.seconds.max(0) as u64)
+                .saturating_mul(1_000_000_000)
+                .saturating_add(self.nanos.max(0) as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes thrift binary protocol field headers and scalars.
+    #[derive(Default)]
+    struct Binary(Vec<u8>);
+
+    impl Binary {
+        fn field(&mut self, field_type: u8, id: i16) -> &mut Self {
+            self.0.push(field_type);
+            self.0.extend_from_slice(&id.to_be_bytes());
+            self
+        }
+        fn i64(&mut self, id: i16, value: i64) -> &mut Self {
+            self.field(10, id);
+            self.0.extend_from_slice(&value.to_be_bytes());
+            self
+        }
+        fn i32(&mut self, id: i16, value: i32) -> &mut Self {
+            self.field(8, id);
+            self.0.extend_from_slice(&value.to_be_bytes());
+            self
+        }
+        fn string(&mut self, id: i16, value: &str) -> &mut Self {
+            self.field(11, id);
+            self.0
+                .extend_from_slice(&(value.len() as i32).to_be_bytes());
+            self.0.extend_from_slice(value.as_bytes());
+            self
+        }
+        fn list(&mut self, id: i16, element_type: u8, size: i32) -> &mut Self {
+            self.field(15, id);
+            self.0.push(element_type);
+            self.0.extend_from_slice(&size.to_be_bytes());
+            self
+        }
+        fn stop(&mut self) -> &mut Self {
+            self.0.push(0);
+            self
+        }
+    }
+
+    fn string_tag(out: &mut Binary, key: &str, value: &str) {
+        out.string(1, key).i32(2, 0).string(3, value).stop();
+    }
+
+    #[test]
+    fn thrift_batches_map_process_tags_kind_and_logs() {
+        let mut out = Binary::default();
+        out.field(12, 1).string(1, "checkout");
+        out.list(2, 12, 1);
+        string_tag(&mut out, "hostname", "web-1");
+        out.stop();
+        out.list(2, 12, 1);
+        out.i64(1, 2)
+            .i64(2, 1)
+            .i64(3, 7)
+            .i64(4, 0)
+            .string(5, "charge");
+        out.list(6, 12, 1)
+            .i32(1, 0)
+            .i64(2, 2)
+            .i64(3, 1)
+            .i64(4, 5)
+            .stop();
+        out.i32(7, 1).i64(8, 1_700_000_000_000_000).i64(9, 250);
+        out.list(10, 12, 2);
+        string_tag(&mut out, "span.kind", "client");
+        out.string(1, "retries").i32(2, 3).i64(6, 2).stop();
+        out.list(11, 12, 1).i64(1, 1_700_000_000_000_100);
+        out.list(2, 12, 1);
+        string_tag(&mut out, "event", "card declined");
+        out.stop();
+        out.stop();
+        out.stop();
+
+        let payload = translate("application/x-thrift", &out.0).ok().unwrap();
+        let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+        let resource = &request.resource_spans[0];
+        let attributes = &resource.resource.as_ref().unwrap().attributes;
+        assert_eq!(attributes[0], string_attribute("service.name", "checkout"));
+        assert_eq!(attributes[1], string_attribute("hostname", "web-1"));
+
+        let span = &resource.scope_spans[0].spans[0];
+        assert_eq!(
+            span.trace_id,
+            [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 2]
+        );
+        assert_eq!(span.parent_span_id, 5i64.to_be_bytes());
+        assert_eq!(span.kind, SpanKind::Client as i32);
+        assert_eq!(span.end_time_unix_nano - span.start_time_unix_nano, 250_000);
+        assert_eq!(span.attributes, vec![int_attribute("retries", 2)]);
+        assert_eq!(span.events[0].name, "card declined");
+    }
+
+    #[test]
+    fn protobuf_batches_use_span_processes_and_links() {
+        let batch = proto::Batch {
+            process: Some(proto::Process {
+                service_name: "api".to_string(),
+                ..Default::default()
+            }),
+            spans: vec![
+                proto::Span {
+                    trace_id: vec![1; 16],
+                    span_id: vec![2; 8],
+                    operation_name: "GET /".to_string(),
+                    references: vec![proto::SpanRef {
+                        trace_id: vec![9; 16],
+                        span_id: vec![3; 8],
+                        ref_type: 1,
+                    }],
+                    tags: vec![proto::KeyValue {
+                        key: "error".to_string(),
+                        v_type: 1,
+                        v_bool: true,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                proto::Span {
+                    trace_id: vec![1; 16],
+                    span_id: vec![4; 8],
+                    process: Some(proto::Process {
+                        service_name: "worker".to_string(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+        };
+        let payload = translate("application/x-protobuf", &batch.encode_to_vec())
+            .ok()
+            .unwrap();
+        let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+
+        assert_eq!(request.resource_spans.len(), 2);
+        let span = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert!(span.parent_span_id.is_empty());
+        assert_eq!(span.links[0].span_id, vec![3; 8]);
+        assert_eq!(span.status.as_ref().unwrap().code, StatusCode::Error as i32);
+        assert!(translate("text/plain", b"").is_err());
+    }
+}
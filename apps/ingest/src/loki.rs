@@ -0,0 +1,344 @@
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+
+use crate::receiver::{string_attribute, UNKNOWN_SERVICE};
+use crate::ApiError;
+
+type Labels = Vec<(String, String)>;
+
+/// A stream from either encoding: its labels and `(timestamp_ns, line,
+/// structured metadata)` entries.
+struct Stream {
+    labels: Labels,
+    entries: Vec<(u64, String, Labels)>,
+}
+
+/// Translates a Loki push request into OTLP logs, one resource per stream.
+/// Protobuf bodies arrive snappy-decoded; anything else is read as JSON.
+pub fn translate(content_type: &str, payload: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let streams = if content_type.contains("protobuf") {
+        proto::PushRequest::decode(payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Loki protobuf: {error}")))?
+            .streams
+            .into_iter()
+            .map(Stream::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        serde_json::from_slice::<json::PushRequest>(payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Loki JSON: {error}")))?
+            .streams
+            .into_iter()
+            .map(Stream::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let request = ExportLogsServiceRequest {
+        resource_logs: streams.into_iter().map(to_resource_logs).collect(),
+    };
+    Ok(request.encode_to_vec())
+}
+
+/// Stream labels become resource attributes. `service_name`, or failing
+/// that `job`, also names the service.
+fn to_resource_logs(stream: Stream) -> ResourceLogs {
+    let service_name = ["service_name", "job"]
+        .iter()
+        .find_map(|name| {
+            stream
+                .labels
+                .iter()
+                .find(|(label, value)| label == name && !value.is_empty())
+        })
+        .map(|(_, value)| value.clone())
+        .unwrap_or_else(|| UNKNOWN_SERVICE.to_string());
+
+    let mut attributes = vec![string_attribute("service.name", service_name)];
+    attributes.extend(
+        stream
+            .labels
+            .into_iter()
+            .filter(|(name, _)| name != "service_name")
+            .map(|(name, value)| string_attribute(&name, value)),
+    );
+
+    ResourceLogs {
+        resource: Some(Resource {
+            attributes,
+            ..Default::default()
+        }),
+        scope_logs: vec![ScopeLogs {
+            scope: Some(InstrumentationScope {
+                name: "loki".to_string(),
+                ..Default::default()
+            }),
+            log_records: stream
+                .entries
+                .into_iter()
+                .map(|(time_unix_nano, line, metadata)| LogRecord {
+                    time_unix_nano,
+                    body: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue(line)),
+                    }),
+                    attributes: metadata
+                        .into_iter()
+                        .map(|(name, value)| string_attribute(&name, value))
+                        .collect::<Vec<KeyValue>>(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// Parses a stream selector such as `{job="api", env="prod"}`.
+fn parse_labels(selector: &str) -> Result<Labels, String> {
+    let inner = selector
+        .trim()
+        .strip_prefix('{')
+        .and_then(|rest| rest.strip_suffix('}'))
+        .ok_or_else(|| format!("labels must be wrapped in braces: {selector}"))?;
+
+    let mut labels = Vec::new();
+    let mut chars = inner.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        if chars.peek().is_none() {
+            return Ok(labels);
+        }
+
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+            name.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if name.is_empty() || chars.next() != Some('=') {
+            return Err(format!("invalid labels: {selector}"));
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.next() != Some('"') {
+            return Err(format!("label values must be quoted: {selector}"));
+        }
+
+        let mut value = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c) => value.push(c),
+                    None => return Err(format!("unterminated label value: {selector}")),
+                },
+                Some(c) => value.push(c),
+                None => return Err(format!("unterminated label value: {selector}")),
+            }
+        }
+        labels.push((name, value));
+    }
+}
+
+impl TryFrom<proto::StreamAdapter> for Stream {
+    type Error = ApiError;
+
+    fn try_from(stream: proto::StreamAdapter) -> Result<Self, ApiError> {
+        Ok(Self {
+            labels: parse_labels(&stream.labels).map_err(ApiError::bad_request)?,
+            entries: stream
+                .entries
+                .into_iter()
+                .map(|entry| {
+                    let timestamp = entry.timestamp.unwrap_or_default();
+                    (
+                        (timestamp.seconds.max(0) as u64)
+                            .saturating_mul(1_000_000_000)
+                            .saturating_add(timestamp.nanos.max(0) as u64),
+                        entry.line,
+                        entry
+                            .structured_metadata
+                            .into_iter()
+                            .map(|pair| (pair.name, pair.value))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        })
+    }
+}
+
+impl TryFrom<json::Stream> for Stream {
+    type Error = ApiError;
+
+    fn try_from(stream: json::Stream) -> Result<Self, ApiError> {
+        Ok(Self {
+            labels: stream.stream.into_iter().collect(),
+            entries: stream
+                .values
+                .into_iter()
+                .map(|json::Entry(timestamp, line, metadata)| {
+                    let timestamp = timestamp.parse::<u64>().map_err(|_| {
+                        ApiError::bad_request(format!("Invalid Loki timestamp: {timestamp}"))
+                    })?;
+                    Ok((
+                        timestamp,
+                        line,
+                        metadata.unwrap_or_default().into_iter().collect(),
+                    ))
+                })
+                .collect::<Result<Vec<_>, ApiError>>()?,
+        })
+    }
+}
+
+mod json {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct PushRequest {
+        pub streams: Vec<Stream>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Stream {
+        #[serde(default)]
+        pub stream: BTreeMap<String, String>,
+        pub values: Vec<Entry>,
+    }
+
+    /// `[<unix ns as a string>, <line>, <structured metadata>?]`.
+    #[derive(Deserialize)]
+    pub struct Entry(
+        pub String,
+        pub String,
+        #[serde(default)] pub Option<BTreeMap<String, String>>,
+    );
+}
+
+/// `logproto.PushRequest`, declared by hand.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct PushRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub streams: Vec<StreamAdapter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StreamAdapter {
+        #[prost(string, tag = "1")]
+        pub labels: String,
+        #[prost(message, repeated, tag = "2")]
+        pub entries: Vec<EntryAdapter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct EntryAdapter {
+        #[prost(message, optional, tag = "1")]
+        pub timestamp: Option<Timestamp>,
+        #[prost(string, tag = "2")]
+        pub line: String,
+        #[prost(message, repeated, tag = "3")]
+        pub structured_metadata: Vec<LabelPairAdapter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct LabelPairAdapter {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Timestamp {
+        #[prost(int64, tag = "1")]
+        pub seconds: i64,
+        #[prost(int32, tag = "2")]
+        pub nanos: i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_streams_become_resources_with_metadata_attributes() {
+        let body = serde_json::json!({
+            "streams": [{
+                "stream": { "job": "api", "env": "prod" },
+                "values": [
+                    ["1700000000000000001", "GET /users 200", { "trace_id": "abc" }],
+                    ["1700000000000000002", "GET /orders 500"]
+                ]
+            }]
+        });
+        let payload = translate("application/json", body.to_string().as_bytes())
+            .ok()
+            .unwrap();
+        let request = ExportLogsServiceRequest::decode(payload.as_slice()).unwrap();
+
+        let resource = &request.resource_logs[0];
+        let attributes = &resource.resource.as_ref().unwrap().attributes;
+        assert_eq!(attributes[0], string_attribute("service.name", "api"));
+        assert!(attributes.contains(&string_attribute("env", "prod")));
+
+        let records = &resource.scope_logs[0].log_records;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].time_unix_nano, 1_700_000_000_000_000_001);
+        assert_eq!(
+            records[0].attributes,
+            vec![string_attribute("trace_id", "abc")]
+        );
+        assert!(records[1].attributes.is_empty());
+
+        let bad = br#"{"streams":[{"stream":{},"values":[["soon","line"]]}]}"#;
+        assert!(translate("application/json", bad).is_err());
+    }
+
+    #[test]
+    fn protobuf_stream_selectors_are_parsed() {
+        let request = proto::PushRequest {
+            streams: vec![proto::StreamAdapter {
+                labels: r#"{service_name="checkout", msg="say \"hi\""}"#.to_string(),
+                entries: vec![proto::EntryAdapter {
+                    timestamp: Some(proto::Timestamp {
+                        seconds: 1_700_000_000,
+                        nanos: 5,
+                    }),
+                    line: "charged".to_string(),
+                    ..Default::default()
+                }],
+            }],
+        };
+        let payload = translate("application/x-protobuf", &request.encode_to_vec())
+            .ok()
+            .unwrap();
+        let request = ExportLogsServiceRequest::decode(payload.as_slice()).unwrap();
+        let attributes = &request.resource_logs[0]
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes;
+        assert_eq!(
+            attributes,
+            &vec![
+                string_attribute("service.name", "checkout"),
+                string_attribute("msg", "say \"hi\""),
+            ]
+        );
+        assert_eq!(
+            request.resource_logs[0].scope_logs[0].log_records[0].time_unix_nano,
+            1_700_000_000_000_000_005
+        );
+
+        assert!(parse_labels("job=\"api\"").is_err());
+        assert!(parse_labels("{job=\"api}").is_err());
+        assert_eq!(parse_labels("{}").unwrap(), vec![]);
+    }
+}
@@ -1,27 +1,51 @@
+// The effective-config `json!` in admin.rs outgrows the default macro depth.
+#![recursion_limit = "256"]
+
 #[global_allocator]
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
+mod admin;
 mod autumn;
-
-use std::io::{Read, Write};
+mod compression;
+mod grpc;
+mod jaeger;
+mod loki;
+mod mirror;
+mod quota;
+mod ratelimit;
+mod readiness;
+mod receiver;
+mod redact;
+mod remote_write;
+mod retry;
+mod routing;
+mod sampling;
+mod splunk;
+mod spool;
+mod thrift;
+mod validate;
+mod zipkin;
+
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use autumn::AutumnTracker;
+use autumn::{AutumnTracker, UsageLedger};
 use axum::body::Bytes;
 use axum::extract::DefaultBodyLimit;
-use axum::extract::State;
-use axum::http::header::{HeaderName, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE};
-use axum::http::{HeaderMap, Method, StatusCode};
+use axum::extract::{Query, State};
+use axum::http::header::{
+    HeaderName, AUTHORIZATION, CONTENT_ENCODING, CONTENT_TYPE, ORIGIN, RETRY_AFTER,
+};
+use axum::http::{HeaderMap, HeaderValue, Method, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum::Router;
+use axum::{Json, Router};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
-use flate2::read::GzDecoder;
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use compression::{ContentEncoding, DecodeError, DecodeLimits, ForwardCompression};
 use hmac::{Hmac, Mac};
 use libsql::{params, Builder, Database};
 use metrics::{counter, gauge, histogram};
@@ -32,22 +56,45 @@ use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
 use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
 use opentelemetry_proto::tonic::resource::v1::Resource;
 use prost::Message;
+use receiver::Receiver;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use sha2::Sha256;
+use tokio::sync::watch;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{debug, error, info, warn, Span};
+use validate::Rejections;
 
 const INGEST_SOURCE: &str = "maple-ingest-gateway";
+const QUOTA_WARNING_HEADER: &str = "x-maple-quota-warning";
+/// Upper bound on the final Autumn flush once requests have drained.
+const AUTUMN_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
 
 type HmacSha256 = Hmac<Sha256>;
 
 #[derive(Clone)]
 struct AppConfig {
     port: u16,
+    grpc_enabled: bool,
+    grpc_port: u16,
+    admin_port: Option<u16>,
+    admin_bind: IpAddr,
+    admin_token: Option<String>,
     forward_endpoint: String,
+    forward_protocol: ForwardProtocol,
+    forward_compression: ForwardCompression,
     forward_timeout: Duration,
+    retry_policy: retry::RetryPolicy,
+    breaker: retry::BreakerConfig,
     max_request_body_bytes: usize,
+    decode_limits: DecodeLimits,
+    max_log_body_bytes: usize,
+    public_max_request_body_bytes: usize,
+    public_max_decoded_body_bytes: usize,
+    public_key_signals: HashSet<&'static str>,
+    shutdown_timeout: Duration,
+    ready_collector_cache_ttl: Duration,
     require_tls: bool,
     db_url: Option<String>,
     db_auth_token: Option<String>,
@@ -55,20 +102,78 @@ struct AppConfig {
     autumn_secret_key: Option<String>,
     autumn_api_url: String,
     autumn_flush_interval_secs: u64,
+    autumn_ledger_path: Option<PathBuf>,
+    buffer_dir: Option<PathBuf>,
+    buffer_max_bytes: u64,
+    buffer_max_age: Duration,
+    buffer_segment_bytes: u64,
+    quotas_enabled: bool,
+    sampling_enabled: bool,
+    quota_sync_interval: Duration,
+    key_poll_interval: Option<Duration>,
+    key_negative_cache_ttl: Option<Duration>,
+    rate_limits: ratelimit::RateLimits,
+    redaction_rules_path: Option<PathBuf>,
+    redaction_hash_key: Option<String>,
+    routes_path: Option<PathBuf>,
+    mirrors_path: Option<PathBuf>,
 }
 
 impl AppConfig {
     fn from_env() -> Result<Self, String> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Builds the config from `var`, which returns a variable's value if set.
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
         let port = parse_u16(
             "INGEST_PORT",
-            std::env::var("INGEST_PORT")
-                .ok()
-                .or_else(|| std::env::var("PORT").ok()),
+            var("INGEST_PORT").or_else(|| var("PORT")),
             3474,
         )?;
 
-        let forward_endpoint = std::env::var("INGEST_FORWARD_OTLP_ENDPOINT")
-            .unwrap_or_else(|_| "http://127.0.0.1:4318".to_string())
+        let grpc_enabled = parse_bool("INGEST_GRPC_ENABLED", var("INGEST_GRPC_ENABLED"), true)?;
+
+        let grpc_port = parse_u16("INGEST_GRPC_PORT", var("INGEST_GRPC_PORT"), 4317)?;
+
+        if grpc_enabled && grpc_port == port {
+            return Err("INGEST_GRPC_PORT must differ from INGEST_PORT".to_string());
+        }
+
+        let admin_port = var("INGEST_ADMIN_PORT")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(|raw| parse_u16("INGEST_ADMIN_PORT", Some(raw), 0))
+            .transpose()?;
+
+        if admin_port.is_some_and(|admin_port| {
+            admin_port == port || (grpc_enabled && admin_port == grpc_port)
+        }) {
+            return Err(
+                "INGEST_ADMIN_PORT must differ from INGEST_PORT and INGEST_GRPC_PORT".to_string(),
+            );
+        }
+
+        let admin_bind = var("INGEST_ADMIN_BIND")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(|raw| {
+                raw.parse::<IpAddr>()
+                    .map_err(|_| format!("INGEST_ADMIN_BIND must be an IP address, got: {raw}"))
+            })
+            .transpose()?
+            .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+
+        let admin_token = var("INGEST_ADMIN_TOKEN")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        if admin_port.is_some() && admin_token.is_none() {
+            return Err("INGEST_ADMIN_TOKEN is required when INGEST_ADMIN_PORT is set".to_string());
+        }
+
+        let forward_endpoint = var("INGEST_FORWARD_OTLP_ENDPOINT")
+            .unwrap_or_else(|| "http://127.0.0.1:4318".to_string())
             .trim()
             .trim_end_matches('/')
             .to_string();
@@ -77,24 +182,143 @@ impl AppConfig {
             return Err("INGEST_FORWARD_OTLP_ENDPOINT is required".to_string());
         }
 
+        let forward_protocol =
+            parse_forward_protocol("INGEST_FORWARD_PROTOCOL", var("INGEST_FORWARD_PROTOCOL"))?;
+
+        let forward_compression = parse_forward_compression(
+            "INGEST_FORWARD_COMPRESSION",
+            var("INGEST_FORWARD_COMPRESSION"),
+        )?;
+
+        if forward_protocol == ForwardProtocol::Grpc
+            && matches!(
+                forward_compression,
+                ForwardCompression::Fixed(ContentEncoding::Snappy | ContentEncoding::SnappyFramed)
+            )
+        {
+            return Err(
+                "INGEST_FORWARD_COMPRESSION=snappy is not supported with INGEST_FORWARD_PROTOCOL=grpc"
+                    .to_string(),
+            );
+        }
+
         let forward_timeout_ms = parse_u64(
             "INGEST_FORWARD_TIMEOUT_MS",
-            std::env::var("INGEST_FORWARD_TIMEOUT_MS").ok(),
+            var("INGEST_FORWARD_TIMEOUT_MS"),
             10_000,
         )?;
 
+        let forward_max_attempts = parse_u64(
+            "INGEST_FORWARD_MAX_ATTEMPTS",
+            var("INGEST_FORWARD_MAX_ATTEMPTS"),
+            3,
+        )?;
+
+        if forward_max_attempts == 0 {
+            return Err("INGEST_FORWARD_MAX_ATTEMPTS must be at least 1".to_string());
+        }
+
+        let forward_retry_base_ms = parse_u64(
+            "INGEST_FORWARD_RETRY_BASE_MS",
+            var("INGEST_FORWARD_RETRY_BASE_MS"),
+            100,
+        )?;
+
+        let forward_retry_max_ms = parse_u64(
+            "INGEST_FORWARD_RETRY_MAX_MS",
+            var("INGEST_FORWARD_RETRY_MAX_MS"),
+            2_000,
+        )?;
+
+        let breaker_failure_threshold = parse_u64(
+            "INGEST_BREAKER_FAILURE_THRESHOLD",
+            var("INGEST_BREAKER_FAILURE_THRESHOLD"),
+            5,
+        )?;
+
+        let breaker_cooldown_secs = parse_u64(
+            "INGEST_BREAKER_COOLDOWN_SECS",
+            var("INGEST_BREAKER_COOLDOWN_SECS"),
+            30,
+        )?;
+
         let max_request_body_bytes = parse_usize(
             "INGEST_MAX_REQUEST_BODY_BYTES",
-            std::env::var("INGEST_MAX_REQUEST_BODY_BYTES").ok(),
+            var("INGEST_MAX_REQUEST_BODY_BYTES"),
             20 * 1024 * 1024,
         )?;
 
-        let require_tls = parse_bool(
-            "INGEST_REQUIRE_TLS",
-            std::env::var("INGEST_REQUIRE_TLS").ok(),
-            false,
+        let max_decoded_body_bytes = parse_usize(
+            "INGEST_MAX_DECODED_BODY_BYTES",
+            var("INGEST_MAX_DECODED_BODY_BYTES"),
+            100 * 1024 * 1024,
+        )?;
+
+        if max_decoded_body_bytes < max_request_body_bytes {
+            return Err(
+                "INGEST_MAX_DECODED_BODY_BYTES must be at least INGEST_MAX_REQUEST_BODY_BYTES"
+                    .to_string(),
+            );
+        }
+
+        let max_compression_ratio = parse_usize(
+            "INGEST_MAX_COMPRESSION_RATIO",
+            var("INGEST_MAX_COMPRESSION_RATIO"),
+            100,
+        )?;
+
+        let max_log_body_bytes = parse_usize(
+            "INGEST_MAX_LOG_BODY_BYTES",
+            var("INGEST_MAX_LOG_BODY_BYTES"),
+            1024 * 1024,
+        )?;
+
+        let public_max_request_body_bytes = parse_usize(
+            "INGEST_PUBLIC_MAX_REQUEST_BODY_BYTES",
+            var("INGEST_PUBLIC_MAX_REQUEST_BODY_BYTES"),
+            1024 * 1024,
+        )?;
+
+        let public_max_decoded_body_bytes = parse_usize(
+            "INGEST_PUBLIC_MAX_DECODED_BODY_BYTES",
+            var("INGEST_PUBLIC_MAX_DECODED_BODY_BYTES"),
+            10 * 1024 * 1024,
+        )?;
+
+        if public_max_request_body_bytes > max_request_body_bytes
+            || public_max_decoded_body_bytes > max_decoded_body_bytes
+        {
+            return Err(
+                "INGEST_PUBLIC_MAX_*_BODY_BYTES must not exceed the private key limits".to_string(),
+            );
+        }
+
+        if public_max_decoded_body_bytes < public_max_request_body_bytes {
+            return Err(
+                "INGEST_PUBLIC_MAX_DECODED_BODY_BYTES must be at least INGEST_PUBLIC_MAX_REQUEST_BODY_BYTES"
+                    .to_string(),
+            );
+        }
+
+        let public_key_signals = parse_signals(
+            "INGEST_PUBLIC_KEY_SIGNALS",
+            var("INGEST_PUBLIC_KEY_SIGNALS"),
+        )?;
+
+        let shutdown_timeout_secs = parse_u64(
+            "INGEST_SHUTDOWN_TIMEOUT_SECS",
+            var("INGEST_SHUTDOWN_TIMEOUT_SECS"),
+            25,
+        )?;
+
+        let ready_collector_cache_secs = parse_u64(
+            "INGEST_READY_COLLECTOR_CACHE_SECS",
+            var("INGEST_READY_COLLECTOR_CACHE_SECS"),
+            5,
         )?;
 
+        let require_tls = parse_bool("INGEST_REQUIRE_TLS", var("INGEST_REQUIRE_TLS"), false)?;
+
         if require_tls && !forward_endpoint.starts_with("https://") {
             return Err(
                 "INGEST_REQUIRE_TLS=true requires an https INGEST_FORWARD_OTLP_ENDPOINT"
@@ -102,18 +326,16 @@ impl AppConfig {
             );
         }
 
-        let db_url = std::env::var("MAPLE_DB_URL")
-            .ok()
+        let db_url = var("MAPLE_DB_URL")
             .map(|v| v.trim().to_string())
             .filter(|v| !v.is_empty());
 
-        let db_auth_token = std::env::var("MAPLE_DB_AUTH_TOKEN")
-            .ok()
+        let db_auth_token = var("MAPLE_DB_AUTH_TOKEN")
             .map(|v| v.trim().to_string())
             .filter(|v| !v.is_empty());
 
-        let lookup_hmac_key = std::env::var("MAPLE_INGEST_KEY_LOOKUP_HMAC_KEY")
-            .map_err(|_| "MAPLE_INGEST_KEY_LOOKUP_HMAC_KEY is required".to_string())?
+        let lookup_hmac_key = var("MAPLE_INGEST_KEY_LOOKUP_HMAC_KEY")
+            .ok_or_else(|| "MAPLE_INGEST_KEY_LOOKUP_HMAC_KEY is required".to_string())?
             .trim()
             .to_string();
 
@@ -121,28 +343,169 @@ impl AppConfig {
             return Err("MAPLE_INGEST_KEY_LOOKUP_HMAC_KEY is required".to_string());
         }
 
-        let autumn_secret_key = std::env::var("AUTUMN_SECRET_KEY")
-            .ok()
+        let autumn_secret_key = var("AUTUMN_SECRET_KEY")
             .map(|v| v.trim().to_string())
             .filter(|v| !v.is_empty());
 
-        let autumn_api_url = std::env::var("AUTUMN_API_URL")
-            .unwrap_or_else(|_| "https://api.useautumn.com".to_string())
+        let autumn_api_url = var("AUTUMN_API_URL")
+            .unwrap_or_else(|| "https://api.useautumn.com".to_string())
             .trim()
             .trim_end_matches('/')
             .to_string();
 
         let autumn_flush_interval_secs = parse_u64(
             "AUTUMN_FLUSH_INTERVAL_SECS",
-            std::env::var("AUTUMN_FLUSH_INTERVAL_SECS").ok(),
+            var("AUTUMN_FLUSH_INTERVAL_SECS"),
+            1,
+        )?;
+
+        let autumn_ledger_path = var("AUTUMN_LEDGER_PATH")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        let buffer_dir = var("INGEST_BUFFER_DIR")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        let routes_path = var("INGEST_ROUTES_FILE")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        let mirrors_path = var("INGEST_MIRRORS_FILE")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        let redaction_rules_path = var("INGEST_REDACTION_RULES_FILE")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+
+        let redaction_hash_key = var("INGEST_REDACTION_HASH_KEY")
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty());
+
+        let buffer_max_bytes = parse_u64(
+            "INGEST_BUFFER_MAX_BYTES",
+            var("INGEST_BUFFER_MAX_BYTES"),
+            1024 * 1024 * 1024,
+        )?;
+
+        let buffer_max_age_secs = parse_u64(
+            "INGEST_BUFFER_MAX_AGE_SECS",
+            var("INGEST_BUFFER_MAX_AGE_SECS"),
+            24 * 60 * 60,
+        )?;
+
+        let buffer_segment_bytes = parse_u64(
+            "INGEST_BUFFER_SEGMENT_BYTES",
+            var("INGEST_BUFFER_SEGMENT_BYTES"),
+            16 * 1024 * 1024,
+        )?;
+
+        let quotas_enabled =
+            parse_bool("INGEST_QUOTAS_ENABLED", var("INGEST_QUOTAS_ENABLED"), true)?;
+
+        let sampling_enabled = parse_bool(
+            "INGEST_SAMPLING_ENABLED",
+            var("INGEST_SAMPLING_ENABLED"),
+            true,
+        )?;
+
+        let quota_sync_interval_secs = parse_u64(
+            "INGEST_QUOTA_SYNC_INTERVAL_SECS",
+            var("INGEST_QUOTA_SYNC_INTERVAL_SECS"),
+            10,
+        )?;
+
+        if quota_sync_interval_secs == 0 {
+            return Err("INGEST_QUOTA_SYNC_INTERVAL_SECS must be greater than 0".to_string());
+        }
+
+        let key_poll_interval_ms = parse_u64(
+            "INGEST_KEY_POLL_INTERVAL_MS",
+            var("INGEST_KEY_POLL_INTERVAL_MS"),
+            2_000,
+        )?;
+
+        let key_negative_cache_ttl_secs = parse_u64(
+            "INGEST_KEY_NEGATIVE_CACHE_TTL_SECS",
+            var("INGEST_KEY_NEGATIVE_CACHE_TTL_SECS"),
+            30,
+        )?;
+
+        let rate_limit_burst_secs = parse_u64(
+            "INGEST_RATE_LIMIT_BURST_SECS",
+            var("INGEST_RATE_LIMIT_BURST_SECS"),
             1,
         )?;
 
+        if rate_limit_burst_secs == 0 {
+            return Err("INGEST_RATE_LIMIT_BURST_SECS must be greater than 0".to_string());
+        }
+
+        let rate_limits = ratelimit::RateLimits {
+            per_key: ratelimit::RateLimit {
+                requests_per_sec: parse_u64(
+                    "INGEST_RATE_LIMIT_KEY_RPS",
+                    var("INGEST_RATE_LIMIT_KEY_RPS"),
+                    0,
+                )?,
+                bytes_per_sec: parse_u64(
+                    "INGEST_RATE_LIMIT_KEY_BYTES_PER_SEC",
+                    var("INGEST_RATE_LIMIT_KEY_BYTES_PER_SEC"),
+                    0,
+                )?,
+            },
+            per_org: ratelimit::RateLimit {
+                requests_per_sec: parse_u64(
+                    "INGEST_RATE_LIMIT_ORG_RPS",
+                    var("INGEST_RATE_LIMIT_ORG_RPS"),
+                    0,
+                )?,
+                bytes_per_sec: parse_u64(
+                    "INGEST_RATE_LIMIT_ORG_BYTES_PER_SEC",
+                    var("INGEST_RATE_LIMIT_ORG_BYTES_PER_SEC"),
+                    0,
+                )?,
+            },
+            burst: Duration::from_secs(rate_limit_burst_secs),
+        };
+
         Ok(Self {
             port,
+            grpc_enabled,
+            grpc_port,
+            admin_port,
+            admin_bind,
+            admin_token,
             forward_endpoint,
+            forward_protocol,
+            forward_compression,
             forward_timeout: Duration::from_millis(forward_timeout_ms),
+            retry_policy: retry::RetryPolicy {
+                max_attempts: forward_max_attempts.min(u32::MAX as u64) as u32,
+                base_backoff: Duration::from_millis(forward_retry_base_ms),
+                max_backoff: Duration::from_millis(forward_retry_max_ms),
+            },
+            breaker: retry::BreakerConfig {
+                failure_threshold: breaker_failure_threshold.min(u32::MAX as u64) as u32,
+                cooldown: Duration::from_secs(breaker_cooldown_secs),
+            },
             max_request_body_bytes,
+            decode_limits: DecodeLimits {
+                max_decoded_bytes: max_decoded_body_bytes,
+                max_ratio: max_compression_ratio,
+            },
+            max_log_body_bytes,
+            public_max_request_body_bytes,
+            public_max_decoded_body_bytes,
+            public_key_signals,
+            shutdown_timeout: Duration::from_secs(shutdown_timeout_secs),
+            ready_collector_cache_ttl: Duration::from_secs(ready_collector_cache_secs.max(1)),
             require_tls,
             db_url,
             db_auth_token,
@@ -150,22 +513,106 @@ impl AppConfig {
             autumn_secret_key,
             autumn_api_url,
             autumn_flush_interval_secs,
+            autumn_ledger_path,
+            buffer_dir,
+            buffer_max_bytes,
+            buffer_max_age: Duration::from_secs(buffer_max_age_secs),
+            buffer_segment_bytes,
+            quotas_enabled,
+            sampling_enabled,
+            quota_sync_interval: Duration::from_secs(quota_sync_interval_secs),
+            key_poll_interval: (key_poll_interval_ms > 0)
+                .then(|| Duration::from_millis(key_poll_interval_ms)),
+            key_negative_cache_ttl: (key_negative_cache_ttl_secs > 0)
+                .then(|| Duration::from_secs(key_negative_cache_ttl_secs)),
+            rate_limits,
+            redaction_rules_path,
+            redaction_hash_key,
+            routes_path,
+            mirrors_path,
         })
     }
 }
 
+impl AppConfig {
+    /// Encoded and decoded body limits for a key type. Public keys ship in
+    /// browser bundles, so they get the tighter ones.
+    fn body_limits(&self, key_type: IngestKeyType) -> (usize, DecodeLimits) {
+        match key_type {
+            IngestKeyType::Private => (self.max_request_body_bytes, self.decode_limits),
+            IngestKeyType::Public => (
+                self.public_max_request_body_bytes,
+                DecodeLimits {
+                    max_decoded_bytes: self.public_max_decoded_body_bytes,
+                    ..self.decode_limits
+                },
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ForwardProtocol {
+    Http,
+    Grpc,
+}
+
+impl ForwardProtocol {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Grpc => "grpc",
+        }
+    }
+}
+
+#[derive(Clone)]
 struct IngestKeyResolver {
     db: Arc<Database>,
     lookup_hmac_key: String,
     cache: Cache<String, ResolvedIngestKey>,
+    /// Lookup hashes that matched no row, so repeated bad keys skip the database.
+    negative_cache: Option<Cache<String, ()>>,
+}
+
+/// Cheap fingerprint of `org_ingest_keys`, so polls that see no write skip
+/// reading the whole table.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct IngestKeyTableVersion {
+    max_updated_at: i64,
+    row_count: i64,
+}
+
+/// What the revocation poller last read from `org_ingest_keys`.
+struct IngestKeySnapshot {
+    version: IngestKeyTableVersion,
+    keys: HashMap<String, OrgIngestKeys>,
+}
+
+/// The parts of an org's row that cached keys are resolved from.
+#[derive(PartialEq)]
+struct OrgIngestKeys {
+    /// Public and private key hashes.
+    key_hashes: [String; 2],
+    allowed_origins_json: Option<String>,
 }
 
 struct AppState {
     config: AppConfig,
     http_client: Client,
+    routes: routing::RouteTable,
+    mirrors: Option<mirror::Mirrors>,
+    readiness: readiness::ReadinessProbe,
+    spool: Option<Arc<spool::Spool>>,
     resolver: IngestKeyResolver,
+    quotas: Option<Arc<quota::QuotaEnforcer>>,
+    rate_limiter: Option<ratelimit::RateLimiter>,
+    redactor: Option<redact::Redactor>,
+    sampling: Option<sampling::SamplingPolicies>,
     metrics_handle: metrics_exporter_prometheus::PrometheusHandle,
     autumn_tracker: Option<AutumnTracker>,
+    /// Flips to `true` once shutdown has begun draining requests.
+    shutdown: watch::Receiver<bool>,
 }
 
 #[derive(Clone)]
@@ -173,6 +620,8 @@ struct ResolvedIngestKey {
     org_id: String,
     key_type: IngestKeyType,
     key_id: String,
+    /// Origins a public key may be used from; `None` allows any.
+    allowed_origins: Option<Arc<[String]>>,
 }
 
 #[derive(Clone, Copy)]
@@ -210,6 +659,22 @@ impl Signal {
 struct EnrichResult {
     payload: Vec<u8>,
     item_count: usize,
+    rejections: Rejections,
+    /// Spans dropped by the org's trace sampling policy.
+    sampled_out: usize,
+}
+
+impl EnrichResult {
+    /// Share of the decoded request that is forwarded and billed; rejected
+    /// items and sampled-out spans are not charged for.
+    fn billable_bytes(&self, decoded_bytes: usize) -> usize {
+        let dropped = self.sampled_out + self.rejections.count.max(0) as usize;
+        if dropped == 0 {
+            return decoded_bytes;
+        }
+        let total = self.item_count + dropped;
+        (decoded_bytes as u128 * self.item_count as u128 / total as u128) as usize
+    }
 }
 
 struct InFlightGuard;
@@ -228,6 +693,7 @@ struct ErrorBody {
 struct ApiError {
     status: StatusCode,
     message: String,
+    retry_after_secs: Option<u64>,
 }
 
 impl ApiError {
@@ -235,6 +701,7 @@ impl ApiError {
         Self {
             status,
             message: message.into(),
+            retry_after_secs: None,
         }
     }
 
@@ -250,6 +717,10 @@ impl ApiError {
         Self::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, message)
     }
 
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
     fn payload_too_large(message: impl Into<String>) -> Self {
         Self::new(StatusCode::PAYLOAD_TOO_LARGE, message)
     }
@@ -257,17 +728,30 @@ impl ApiError {
     fn service_unavailable(message: impl Into<String>) -> Self {
         Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
     }
+
+    fn too_many_requests(message: impl Into<String>, retry_after_secs: u64) -> Self {
+        Self {
+            retry_after_secs: Some(retry_after_secs),
+            ..Self::new(StatusCode::TOO_MANY_REQUESTS, message)
+        }
+    }
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        (
+        let mut response = (
             self.status,
             axum::Json(ErrorBody {
                 error: self.message,
             }),
         )
-            .into_response()
+            .into_response();
+        if let Some(retry_after_secs) = self.retry_after_secs {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+        }
+        response
     }
 }
 
@@ -317,29 +801,143 @@ async fn main() {
         }
     };
 
-    let autumn_tracker = config.autumn_secret_key.as_ref().map(|key| {
-        AutumnTracker::spawn(
-            key.clone(),
-            &config.autumn_api_url,
-            config.autumn_flush_interval_secs,
-        )
-    });
+    let routes = match routing::RouteTable::from_config(&config) {
+        Ok(routes) => routes,
+        Err(error) => {
+            eprintln!("Forward route init error: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    for route in routes.iter() {
+        info!(
+            route = %route.name,
+            endpoint = %route.endpoint,
+            protocol = route.protocol.as_str(),
+            timeout_ms = route.timeout.as_millis() as u64,
+            require_tls = route.require_tls,
+            "Forward route configured"
+        );
+    }
+
+    let mirrors = match &config.mirrors_path {
+        Some(path) => match mirror::Mirrors::load(path, &http_client, config.require_tls) {
+            Ok(mirrors) => {
+                info!(mirrors = ?mirrors.names().collect::<Vec<_>>(), "Mirroring enabled");
+                Some(mirrors)
+            }
+            Err(error) => {
+                eprintln!("Mirror init error: {error}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let spool = match &config.buffer_dir {
+        Some(dir) => {
+            let limits = spool::SpoolLimits {
+                max_bytes: config.buffer_max_bytes,
+                max_age: config.buffer_max_age,
+                segment_bytes: config.buffer_segment_bytes,
+            };
+            match spool::Spool::open(dir, limits) {
+                Ok(spool) => Some(Arc::new(spool)),
+                Err(error) => {
+                    eprintln!("Forwarding buffer init error: {error}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    let redactor = match &config.redaction_rules_path {
+        Some(path) => match redact::Redactor::load(path, config.redaction_hash_key.as_deref()) {
+            Ok(redactor) => Some(redactor),
+            Err(error) => {
+                eprintln!("Redaction rules error: {error}");
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let autumn_tracker = match &config.autumn_secret_key {
+        Some(key) => {
+            let ledger = match &config.autumn_ledger_path {
+                Some(path) => match UsageLedger::open(path).await {
+                    Ok(ledger) => Some(ledger),
+                    Err(error) => {
+                        eprintln!("Autumn usage ledger init error: {error}");
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+
+            match AutumnTracker::spawn(
+                key.clone(),
+                &config.autumn_api_url,
+                config.autumn_flush_interval_secs,
+                ledger,
+            )
+            .await
+            {
+                Ok(tracker) => Some(tracker),
+                Err(error) => {
+                    eprintln!("Autumn usage ledger init error: {error}");
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
 
     let ingest_key_cache = Cache::builder()
         .time_to_live(Duration::from_secs(60))
         .max_capacity(1_000)
+        .support_invalidation_closures()
         .build();
 
+    let negative_key_cache = config.key_negative_cache_ttl.map(|ttl| {
+        Cache::builder()
+            .time_to_live(ttl)
+            .max_capacity(10_000)
+            .build()
+    });
+
+    let database = Arc::new(database);
+    let quotas = config
+        .quotas_enabled
+        .then(|| Arc::new(quota::QuotaEnforcer::new(database.clone())));
+    let sampling = config
+        .sampling_enabled
+        .then(|| sampling::SamplingPolicies::new(database.clone()));
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
     let state = Arc::new(AppState {
         resolver: IngestKeyResolver {
-            db: Arc::new(database),
+            db: database,
             lookup_hmac_key: config.lookup_hmac_key.clone(),
             cache: ingest_key_cache,
+            negative_cache: negative_key_cache,
         },
+        quotas: quotas.clone(),
+        rate_limiter: (!config.rate_limits.is_unlimited())
+            .then(|| ratelimit::RateLimiter::new(config.rate_limits)),
+        redactor,
+        sampling,
         http_client,
+        routes,
+        mirrors,
+        readiness: readiness::ReadinessProbe::new(config.ready_collector_cache_ttl),
+        spool: spool.clone(),
         config: config.clone(),
         metrics_handle: prometheus_handle,
         autumn_tracker,
+        shutdown: shutdown_rx.clone(),
     });
 
     let cors = CorsLayer::new()
@@ -350,14 +948,94 @@ async fn main() {
             CONTENT_TYPE,
             CONTENT_ENCODING,
             HeaderName::from_static("x-maple-ingest-key"),
-        ]);
+        ])
+        .expose_headers([RETRY_AFTER, HeaderName::from_static(QUOTA_WARNING_HEADER)]);
+
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Shutdown signal received, draining in-flight requests");
+        let _ = shutdown_tx.send(true);
+    });
+
+    if let Some(spool) = spool {
+        spool::spawn_replay(spool, state.clone());
+    }
+
+    if let Some(quotas) = quotas {
+        quota::spawn_sync(quotas, config.quota_sync_interval);
+    }
+
+    if let Some(interval) = config.key_poll_interval {
+        state.resolver.spawn_revocation_poller(interval);
+    }
+
+    let mut grpc_task = None;
+    if config.grpc_enabled {
+        let grpc_addr = std::net::SocketAddr::from(([0, 0, 0, 0], config.grpc_port));
+        let grpc_server = grpc::GrpcIngest::new(state.clone())
+            .into_server(config.decode_limits.max_decoded_bytes)
+            .serve_with_shutdown(grpc_addr, shutdown_requested(shutdown_rx.clone()));
+
+        grpc_task = Some(tokio::spawn(async move {
+            if let Err(error) = grpc_server.await {
+                eprintln!("Ingest gRPC server failed: {error}");
+                std::process::exit(1);
+            }
+        }));
+
+        info!(
+            grpc_port = config.grpc_port,
+            "Maple ingest gRPC server listening"
+        );
+    }
+
+    if let (Some(admin_port), Some(admin_token)) = (config.admin_port, &config.admin_token) {
+        let admin_app = admin::router(state.clone(), admin_token);
+        let admin_listener =
+            match tokio::net::TcpListener::bind((config.admin_bind, admin_port)).await {
+                Ok(listener) => listener,
+                Err(error) => {
+                    eprintln!("Failed to bind ingest admin server: {error}");
+                    std::process::exit(1);
+                }
+            };
+
+        let admin_shutdown = shutdown_requested(shutdown_rx.clone());
+        tokio::spawn(async move {
+            if let Err(error) = axum::serve(admin_listener, admin_app)
+                .with_graceful_shutdown(admin_shutdown)
+                .await
+            {
+                eprintln!("Ingest admin server failed: {error}");
+                std::process::exit(1);
+            }
+        });
+
+        info!(
+            admin_bind = %config.admin_bind,
+            admin_port,
+            "Maple ingest admin server listening"
+        );
+    }
+
+    let autumn_tracker = state.autumn_tracker.clone();
+    let quotas = state.quotas.clone();
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/livez", get(readiness::livez))
+        .route("/readyz", get(readiness::readyz))
         .route("/metrics", get(serve_metrics))
         .route("/v1/traces", post(handle_traces))
         .route("/v1/logs", post(handle_logs))
         .route("/v1/metrics", post(handle_metrics))
+        .route("/api/v2/spans", post(handle_zipkin_spans))
+        .route("/api/traces", post(handle_jaeger_traces))
+        .route("/api/v1/write", post(handle_remote_write))
+        .route("/loki/api/v1/push", post(handle_loki_push))
+        .route("/services/collector", post(handle_splunk_event))
+        .route("/services/collector/event", post(handle_splunk_event))
+        .route("/services/collector/raw", post(handle_splunk_raw))
         .layer(cors)
         .layer(DefaultBodyLimit::max(config.max_request_body_bytes))
         .with_state(state);
@@ -373,19 +1051,113 @@ async fn main() {
     info!(
         port = config.port,
         forward_endpoint = %config.forward_endpoint,
+        forward_protocol = config.forward_protocol.as_str(),
+        forward_compression = config.forward_compression.as_str(),
+        buffer_dir = ?config.buffer_dir,
+        quotas_enabled = config.quotas_enabled,
+        rate_limited = !config.rate_limits.is_unlimited(),
         require_tls = config.require_tls,
         max_body_bytes = config.max_request_body_bytes,
         "Maple ingest server listening"
     );
 
-    if let Err(error) = axum::serve(listener, app).await {
-        eprintln!("Ingest server failed: {error}");
-        std::process::exit(1);
+    let servers = async {
+        if let Err(error) = axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_requested(shutdown_rx.clone()))
+            .await
+        {
+            eprintln!("Ingest server failed: {error}");
+            std::process::exit(1);
+        }
+        if let Some(grpc_task) = grpc_task {
+            let _ = grpc_task.await;
+        }
+    };
+    let drain_deadline = async {
+        shutdown_requested(shutdown_rx.clone()).await;
+        tokio::time::sleep(config.shutdown_timeout).await;
+    };
+
+    tokio::select! {
+        _ = servers => info!("In-flight requests drained"),
+        _ = drain_deadline => warn!(
+            timeout_secs = config.shutdown_timeout.as_secs(),
+            "Shutdown deadline reached with requests still in flight"
+        ),
+    }
+
+    if let Some(quotas) = quotas {
+        if let Err(error) = quotas.sync().await {
+            warn!(error = %error, "Final ingest quota usage sync failed");
+        }
+    }
+
+    if let Some(tracker) = autumn_tracker {
+        if tokio::time::timeout(AUTUMN_SHUTDOWN_TIMEOUT, tracker.shutdown())
+            .await
+            .is_err()
+        {
+            warn!(
+                timeout_secs = AUTUMN_SHUTDOWN_TIMEOUT.as_secs(),
+                "Timed out waiting for the final Autumn flush"
+            );
+        }
+    }
+
+    info!("Maple ingest server stopped");
+}
+
+/// Resolves on SIGINT or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(error) = tokio::signal::ctrl_c().await {
+            error!(error = %error, "Failed to listen for SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(error) => {
+                error!(error = %error, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
     }
 }
 
-async fn health() -> &'static str {
-    "OK"
+async fn shutdown_requested(mut shutdown_rx: watch::Receiver<bool>) {
+    let _ = shutdown_rx.wait_for(|requested| *requested).await;
+}
+
+/// Liveness plus each route's circuit breaker. Stays 200 while a breaker is
+/// open, since restarting the gateway would not bring the collector back.
+async fn health(State(state): State<Arc<AppState>>) -> Json<serde_json::Value> {
+    let breakers: serde_json::Map<String, serde_json::Value> = state
+        .routes
+        .iter()
+        .map(|route| (route.name.clone(), route.breaker.state().into()))
+        .collect();
+    let degraded = breakers
+        .values()
+        .any(|breaker_state| breaker_state != "closed");
+
+    Json(serde_json::json!({
+        "status": if degraded { "degraded" } else { "ok" },
+        "circuit_breakers": breakers,
+    }))
 }
 
 async fn serve_metrics(State(state): State<Arc<AppState>>) -> String {
@@ -397,7 +1169,7 @@ async fn handle_traces(
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    handle_signal(state, headers, body, Signal::Traces).await
+    handle_signal(state, headers, body, Signal::Traces, Receiver::Otlp).await
 }
 
 async fn handle_logs(
@@ -405,7 +1177,7 @@ async fn handle_logs(
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    handle_signal(state, headers, body, Signal::Logs).await
+    handle_signal(state, headers, body, Signal::Logs, Receiver::Otlp).await
 }
 
 async fn handle_metrics(
@@ -413,7 +1185,70 @@ async fn handle_metrics(
     headers: HeaderMap,
     body: Bytes,
 ) -> Response {
-    handle_signal(state, headers, body, Signal::Metrics).await
+    handle_signal(state, headers, body, Signal::Metrics, Receiver::Otlp).await
+}
+
+/// Zipkin v2 spans, JSON or protobuf, translated to OTLP traces.
+async fn handle_zipkin_spans(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(state, headers, body, Signal::Traces, Receiver::Zipkin).await
+}
+
+/// Jaeger batches, Thrift (binary or compact) or `model.proto`, translated
+/// to OTLP traces.
+async fn handle_jaeger_traces(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(state, headers, body, Signal::Traces, Receiver::Jaeger).await
+}
+
+/// Prometheus remote write v1 or v2, translated to OTLP metrics.
+async fn handle_remote_write(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(state, headers, body, Signal::Metrics, Receiver::RemoteWrite).await
+}
+
+/// Loki push requests, snappy protobuf or JSON, translated to OTLP logs.
+async fn handle_loki_push(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(state, headers, body, Signal::Logs, Receiver::Loki).await
+}
+
+/// Splunk HEC JSON events, translated to OTLP logs.
+async fn handle_splunk_event(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(state, headers, body, Signal::Logs, Receiver::SplunkEvent).await
+}
+
+/// Splunk HEC raw lines, with event metadata in the query string.
+async fn handle_splunk_raw(
+    State(state): State<Arc<AppState>>,
+    Query(metadata): Query<splunk::Metadata>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    handle_signal(
+        state,
+        headers,
+        body,
+        Signal::Logs,
+        Receiver::SplunkRaw(metadata),
+    )
+    .await
 }
 
 async fn handle_signal(
@@ -421,6 +1256,7 @@ async fn handle_signal(
     headers: HeaderMap,
     body: Bytes,
     signal: Signal,
+    receiver: Receiver,
 ) -> Response {
     let start = Instant::now();
     let body_bytes = body.len();
@@ -431,28 +1267,21 @@ async fn handle_signal(
     let span = tracing::info_span!(
         "ingest",
         signal = signal.path(),
+        receiver = receiver.label(),
         body_bytes,
         org_id = tracing::field::Empty,
         key_type = tracing::field::Empty,
     );
     let _enter = span.enter();
 
-    let result = handle_signal_inner(&state, &headers, body, signal).await;
+    let result = handle_signal_inner(&state, &headers, body, signal, &receiver).await;
     let duration = start.elapsed();
     let duration_ms = duration.as_millis() as u64;
 
     match result {
         Ok((response, item_count, org_id, decoded_bytes)) => {
             let status_code = response.status().as_u16();
-            histogram!("ingest_request_duration_seconds", "signal" => signal.path(), "status" => "ok")
-                .record(duration.as_secs_f64());
-            counter!("ingest_requests_total", "signal" => signal.path(), "status" => "ok", "error_kind" => "none")
-                .increment(1);
-            if let Some(tracker) = &state.autumn_tracker {
-                let feature_id = signal.path();
-                let value_gb = decoded_bytes as f64 / 1_000_000_000.0;
-                tracker.track(&org_id, feature_id, value_gb);
-            }
+            record_request_ok(&state, signal, duration, &org_id, decoded_bytes);
             info!(
                 status = status_code,
                 duration_ms,
@@ -462,59 +1291,93 @@ async fn handle_signal(
             response
         }
         Err((error, error_kind)) => {
-            histogram!("ingest_request_duration_seconds", "signal" => signal.path(), "status" => "error")
-                .record(duration.as_secs_f64());
-            counter!("ingest_requests_total", "signal" => signal.path(), "status" => "error", "error_kind" => error_kind)
-                .increment(1);
-            error.into_response()
+            record_request_error(signal, duration, error_kind);
+            match error.retry_after_secs {
+                Some(retry_after_secs) => throttled_response(&headers, error, retry_after_secs),
+                None => error.into_response(),
+            }
         }
     }
 }
 
+/// OTLP/HTTP throttling response: 429 with `Retry-After` and a `google.rpc.Status`
+/// body carrying `RetryInfo`, encoded like the request.
+fn throttled_response(headers: &HeaderMap, error: ApiError, retry_after_secs: u64) -> Response {
+    let payload_format = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| detect_payload_format(&value.to_ascii_lowercase()).ok())
+        .unwrap_or(PayloadFormat::Json);
+
+    (
+        error.status,
+        [
+            (
+                CONTENT_TYPE,
+                HeaderValue::from_static(payload_format.content_type()),
+            ),
+            (RETRY_AFTER, HeaderValue::from(retry_after_secs)),
+        ],
+        grpc::throttled_body(payload_format, &error.message, retry_after_secs),
+    )
+        .into_response()
+}
+
+fn record_request_ok(
+    state: &AppState,
+    signal: Signal,
+    duration: Duration,
+    org_id: &str,
+    decoded_bytes: usize,
+) {
+    histogram!("ingest_request_duration_seconds", "signal" => signal.path(), "status" => "ok")
+        .record(duration.as_secs_f64());
+    counter!("ingest_requests_total", "signal" => signal.path(), "status" => "ok", "error_kind" => "none")
+        .increment(1);
+    if let Some(tracker) = &state.autumn_tracker {
+        let feature_id = signal.path();
+        let value_gb = decoded_bytes as f64 / 1_000_000_000.0;
+        tracker.track(org_id, feature_id, value_gb);
+    }
+    if let Some(quotas) = &state.quotas {
+        quotas.record(org_id, signal.path(), decoded_bytes as u64);
+    }
+}
+
+fn record_request_error(signal: Signal, duration: Duration, error_kind: &'static str) {
+    histogram!("ingest_request_duration_seconds", "signal" => signal.path(), "status" => "error")
+        .record(duration.as_secs_f64());
+    counter!("ingest_requests_total", "signal" => signal.path(), "status" => "error", "error_kind" => error_kind)
+        .increment(1);
+}
+
 /// Returns Ok((response, item_count, org_id, decoded_bytes)) or Err((ApiError, error_kind_label))
 async fn handle_signal_inner(
     state: &AppState,
     headers: &HeaderMap,
     body: Bytes,
     signal: Signal,
+    receiver: &Receiver,
 ) -> Result<(Response, usize, String, usize), (ApiError, &'static str)> {
     // --- Auth ---
-    let ingest_key = extract_ingest_key(headers).ok_or_else(|| {
-        warn!("Missing ingest key");
-        (ApiError::unauthorized("Missing ingest key"), "auth")
-    })?;
-
-    let key_resolve_start = Instant::now();
-    let resolved_key = state
-        .resolver
-        .resolve_ingest_key(&ingest_key)
-        .await
-        .map_err(|error| {
-            error!(error = %error, "Ingest key resolution failed");
-            (
-                ApiError::service_unavailable("Ingest authentication unavailable"),
-                "auth",
-            )
-        })?
-        .ok_or_else(|| {
-            warn!("Unknown ingest key");
-            (ApiError::unauthorized("Invalid ingest key"), "auth")
-        })?;
-    histogram!("ingest_key_resolution_duration_seconds")
-        .record(key_resolve_start.elapsed().as_secs_f64());
-
-    Span::current().record("org_id", &resolved_key.org_id.as_str());
-    Span::current().record("key_type", resolved_key.key_type.as_str());
-    debug!(
-        resolve_ms = key_resolve_start.elapsed().as_millis() as u64,
-        "Authenticated"
-    );
+    let resolved_key = authenticate(state, headers).await?;
+    authorize_key(state, &resolved_key, headers, signal)?;
+    enforce_rate_limit(
+        state,
+        &resolved_key,
+        signal,
+        ratelimit::ThrottleLimit::Requests,
+        1,
+    )
+    .await?;
+    let soft_limited = enforce_quota(state, &resolved_key, signal).await?;
 
     // --- Payload validation ---
-    if body.len() > state.config.max_request_body_bytes {
+    let (max_body_bytes, decode_limits) = state.config.body_limits(resolved_key.key_type);
+    if body.len() > max_body_bytes {
         warn!(
             body_bytes = body.len(),
-            max_bytes = state.config.max_request_body_bytes,
+            max_bytes = max_body_bytes,
             "Payload too large"
         );
         return Err((
@@ -529,25 +1392,38 @@ async fn handle_signal_inner(
         .unwrap_or("application/x-protobuf")
         .to_ascii_lowercase();
 
-    let payload_format = detect_payload_format(&content_type).map_err(|e| {
-        warn!(content_type = %content_type, "Unsupported content type");
-        (e, "unsupported_media")
-    })?;
+    // Other receivers' payloads are translated to OTLP protobuf below.
+    let payload_format = match receiver {
+        Receiver::Otlp => detect_payload_format(&content_type).map_err(|e| {
+            warn!(content_type = %content_type, "Unsupported content type");
+            (e, "unsupported_media")
+        })?,
+        _ => PayloadFormat::Protobuf,
+    };
 
     let content_encoding = headers
         .get(CONTENT_ENCODING)
         .and_then(|value| value.to_str().ok())
         .map(|value| value.trim().to_ascii_lowercase())
-        .filter(|value| !value.is_empty() && value != "identity");
+        .filter(|value| !value.is_empty() && value != "identity")
+        .or_else(|| {
+            receiver
+                .implicit_content_encoding(&content_type)
+                .map(str::to_string)
+        });
 
     histogram!("ingest_request_body_bytes", "signal" => signal.path())
         .record(body.len() as f64);
 
     // --- Decode ---
-    let decoded_payload = decode_payload(&body, content_encoding.as_deref()).map_err(|e| {
-        warn!(body_bytes = body.len(), "Failed to decode payload");
-        (e, "decode")
-    })?;
+    let decoded_payload = decode_payload(&body, content_encoding.as_deref(), decode_limits)
+        .map_err(|(e, error_kind)| {
+            warn!(
+                body_bytes = body.len(),
+                error_kind, "Failed to decode payload"
+            );
+            (e, error_kind)
+        })?;
 
     let encoding_label = content_encoding.as_deref().unwrap_or("identity");
     debug!(
@@ -558,9 +1434,42 @@ async fn handle_signal_inner(
     histogram!("ingest_decoded_body_bytes", "signal" => signal.path())
         .record(decoded_payload.len() as f64);
 
+    // --- Translate ---
+    let (decoded_payload, accepted_headers) = receiver
+        .translate(&content_type, decoded_payload)
+        .map_err(|e| {
+            warn!(receiver = receiver.label(), error = %e.message, "Failed to translate payload");
+            (e, "translate")
+        })?;
+
+    // --- Rate limit ---
+    // Charged in decoded bytes, like billing and quotas, so a limit means the
+    // same whether the client compresses or uses gRPC.
+    enforce_rate_limit(
+        state,
+        &resolved_key,
+        signal,
+        ratelimit::ThrottleLimit::Bytes,
+        decoded_payload.len(),
+    )
+    .await?;
+
     // --- Enrich ---
-    let enrich_result =
-        enrich_payload(signal, payload_format, &decoded_payload, &resolved_key).map_err(|e| {
+    let output_format = state
+        .routes
+        .route_for(signal, &resolved_key)
+        .output_format(payload_format);
+    let sampling_policy = sampling_policy(state, &resolved_key, signal).await;
+    let enrich_result = enrich_payload(
+        signal,
+        payload_format,
+        output_format,
+        &decoded_payload,
+        &resolved_key,
+        state,
+        sampling_policy.as_deref(),
+    )
+    .map_err(|e| {
             warn!(
                 format = payload_format.label(),
                 "Invalid OTLP payload"
@@ -575,35 +1484,334 @@ async fn handle_signal_inner(
         "org_id" => resolved_key.org_id.clone()
     )
     .increment(enrich_result.item_count as u64);
+    if !enrich_result.rejections.is_empty() {
+        warn!(
+            rejected = enrich_result.rejections.count,
+            "Dropped invalid items"
+        );
+        enrich_result.rejections.record(signal);
+    }
+    record_sampled_out(&resolved_key, enrich_result.sampled_out);
 
-    let decoded_bytes = decoded_payload.len();
+    let decoded_bytes = enrich_result.billable_bytes(decoded_payload.len());
+    let EnrichResult {
+        payload,
+        item_count,
+        rejections,
+        sampled_out,
+    } = enrich_result;
 
     // --- Encode & Forward ---
-    let outbound_body =
-        encode_payload(&enrich_result.payload, content_encoding.as_deref()).map_err(|e| {
-            (e, "encode")
-        })?;
+    let mut response = if item_count == 0 && !rejections.is_empty() {
+        // Nothing valid is left to forward.
+        partial_success_response(signal, payload_format, &[], &rejections)
+    } else if item_count == 0 && sampled_out > 0 {
+        full_success_response(payload_format)
+    } else {
+        let outbound_encoding = state
+            .config
+            .forward_compression
+            .resolve(content_encoding.as_deref().and_then(ContentEncoding::parse));
+        let response = deliver_enriched(
+            state,
+            signal,
+            payload_format,
+            outbound_encoding.map(ContentEncoding::as_str),
+            Bytes::from(payload),
+            &resolved_key,
+        )
+        .await?;
 
-    let response = forward_to_collector(
-        state,
-        signal,
-        payload_format.content_type(),
-        content_encoding.as_deref(),
-        outbound_body,
-        &resolved_key,
+        if rejections.is_empty() || !response.status().is_success() {
+            response
+        } else {
+            let upstream = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap_or_default();
+            partial_success_response(signal, payload_format, &upstream, &rejections)
+        }
+    };
+
+    if response.status().is_success() {
+        if let Some(accepted) = receiver.accepted_response(accepted_headers) {
+            response = accepted;
+        }
+    }
+
+    if soft_limited {
+        response.headers_mut().insert(
+            HeaderName::from_static(QUOTA_WARNING_HEADER),
+            HeaderValue::from_static("soft-limit-reached"),
+        );
+    }
+
+    Ok((
+        response,
+        item_count,
+        resolved_key.org_id.clone(),
+        decoded_bytes,
+    ))
+}
+
+/// The org's trace sampling policy, if sampling applies to this request.
+async fn sampling_policy(
+    state: &AppState,
+    resolved_key: &ResolvedIngestKey,
+    signal: Signal,
+) -> Option<Arc<sampling::SamplingPolicy>> {
+    if !matches!(signal, Signal::Traces) {
+        return None;
+    }
+    state
+        .sampling
+        .as_ref()?
+        .policy_for(&resolved_key.org_id)
+        .await
+}
+
+fn record_sampled_out(resolved_key: &ResolvedIngestKey, sampled_out: usize) {
+    if sampled_out == 0 {
+        return;
+    }
+    debug!(sampled_out, "Dropped spans by sampling policy");
+    counter!(
+        "ingest_spans_sampled_out_total",
+        "org_id" => resolved_key.org_id.clone()
     )
-    .await
-    .map_err(|e| (e, "forward"))?;
+    .increment(sampled_out as u64);
+}
+
+/// An empty `Export*ServiceResponse`, i.e. full success, for payloads the
+/// gateway accepted without a collector response to relay.
+fn full_success_response(payload_format: PayloadFormat) -> Response {
+    let body = match payload_format {
+        PayloadFormat::Protobuf => Vec::new(),
+        PayloadFormat::Json => b"{}".to_vec(),
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, payload_format.content_type())
+        .body(axum::body::Body::from(body))
+        .unwrap_or_else(|_| StatusCode::OK.into_response())
+}
+
+/// A 200 `Export*ServiceResponse` reporting the items the gateway dropped.
+fn partial_success_response(
+    signal: Signal,
+    payload_format: PayloadFormat,
+    upstream: &[u8],
+    rejections: &Rejections,
+) -> Response {
+    (
+        StatusCode::OK,
+        [(
+            CONTENT_TYPE,
+            HeaderValue::from_static(payload_format.content_type()),
+        )],
+        validate::partial_success_body(signal, payload_format, upstream, rejections),
+    )
+        .into_response()
+}
+
+/// Applies the restrictions public keys carry because they ship in browser
+/// bundles: the org's origin allow-list and the signals public keys may send.
+fn authorize_key(
+    state: &AppState,
+    resolved_key: &ResolvedIngestKey,
+    headers: &HeaderMap,
+    signal: Signal,
+) -> Result<(), (ApiError, &'static str)> {
+    if !matches!(resolved_key.key_type, IngestKeyType::Public) {
+        return Ok(());
+    }
+
+    if !state.config.public_key_signals.contains(signal.path()) {
+        warn!(
+            signal = signal.path(),
+            "Signal not allowed for public ingest keys"
+        );
+        return Err((
+            ApiError::forbidden(format!("Public ingest keys cannot send {}", signal.path())),
+            "signal_not_allowed",
+        ));
+    }
+
+    if let Some(allowed_origins) = &resolved_key.allowed_origins {
+        let origin = headers.get(ORIGIN).and_then(|value| value.to_str().ok());
+        if !origin.is_some_and(|origin| origin_allowed(allowed_origins, origin)) {
+            warn!(origin = ?origin, "Origin not allowed for public ingest key");
+            return Err((
+                ApiError::forbidden("Origin not allowed for this ingest key"),
+                "origin_not_allowed",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+/// Entries match the `Origin` header exactly, or any subdomain when written
+/// as `https://*.example.com`.
+fn origin_allowed(allowed_origins: &[String], origin: &str) -> bool {
+    let origin = normalize_origin(origin);
+    allowed_origins.iter().any(|allowed| {
+        if *allowed == origin {
+            return true;
+        }
+        let Some((scheme, domain)) = allowed.split_once("://*.") else {
+            return false;
+        };
+        origin
+            .strip_prefix(scheme)
+            .and_then(|rest| rest.strip_prefix("://"))
+            .and_then(|host| host.strip_suffix(domain))
+            .is_some_and(|subdomain| subdomain.len() > 1 && subdomain.ends_with('.'))
+    })
+}
+
+/// Resolves the ingest key carried in `headers` (HTTP headers or gRPC metadata).
+async fn authenticate(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<ResolvedIngestKey, (ApiError, &'static str)> {
+    let ingest_key = extract_ingest_key(headers).ok_or_else(|| {
+        warn!("Missing ingest key");
+        (ApiError::unauthorized("Missing ingest key"), "auth")
+    })?;
+
+    let key_resolve_start = Instant::now();
+    let resolved_key = state
+        .resolver
+        .resolve_ingest_key(&ingest_key)
+        .await
+        .map_err(|error| {
+            error!(error = %error, "Ingest key resolution failed");
+            (
+                ApiError::service_unavailable("Ingest authentication unavailable"),
+                "auth",
+            )
+        })?
+        .ok_or_else(|| {
+            warn!("Unknown ingest key");
+            (ApiError::unauthorized("Invalid ingest key"), "auth")
+        })?;
+    histogram!("ingest_key_resolution_duration_seconds")
+        .record(key_resolve_start.elapsed().as_secs_f64());
+
+    Span::current().record("org_id", resolved_key.org_id.as_str());
+    Span::current().record("key_type", resolved_key.key_type.as_str());
+    debug!(
+        resolve_ms = key_resolve_start.elapsed().as_millis() as u64,
+        "Authenticated"
+    );
+
+    Ok(resolved_key)
+}
+
+/// Charges `amount` against the key's and the org's `limit` token buckets.
+async fn enforce_rate_limit(
+    state: &AppState,
+    resolved_key: &ResolvedIngestKey,
+    signal: Signal,
+    limit: ratelimit::ThrottleLimit,
+    amount: usize,
+) -> Result<(), (ApiError, &'static str)> {
+    let Some(rate_limiter) = &state.rate_limiter else {
+        return Ok(());
+    };
+
+    rate_limiter
+        .acquire(
+            &resolved_key.key_id,
+            &resolved_key.org_id,
+            limit,
+            amount as u64,
+        )
+        .await
+        .map_err(|throttled| {
+            let retry_after_secs = throttled.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            debug!(
+                scope = throttled.scope.as_str(),
+                limit = throttled.limit.as_str(),
+                retry_after_secs,
+                "Request throttled"
+            );
+            counter!(
+                "ingest_throttled_total",
+                "signal" => signal.path(),
+                "scope" => throttled.scope.as_str(),
+                "limit" => throttled.limit.as_str()
+            )
+            .increment(1);
+            (
+                ApiError::too_many_requests(
+                    format!(
+                        "Rate limit exceeded for ingest {} ({})",
+                        throttled.scope.as_str(),
+                        throttled.limit.as_str()
+                    ),
+                    retry_after_secs,
+                ),
+                "rate_limited",
+            )
+        })
+}
 
-    Ok((response, enrich_result.item_count, resolved_key.org_id.clone(), decoded_bytes))
+/// Rejects the request once one of the org's quota budgets is exhausted.
+/// Returns whether a soft limit has been crossed.
+async fn enforce_quota(
+    state: &AppState,
+    resolved_key: &ResolvedIngestKey,
+    signal: Signal,
+) -> Result<bool, (ApiError, &'static str)> {
+    let Some(quotas) = &state.quotas else {
+        return Ok(false);
+    };
+
+    match quotas.check(&resolved_key.org_id, signal.path()).await {
+        Ok(status) => Ok(status.soft_limited),
+        Err(exceeded) => {
+            warn!(
+                scope = %exceeded.scope,
+                period = exceeded.period.as_str(),
+                retry_after_secs = exceeded.retry_after_secs,
+                "Ingest quota exhausted"
+            );
+            counter!(
+                "ingest_quota_rejected_total",
+                "signal" => signal.path(),
+                "period" => exceeded.period.as_str()
+            )
+            .increment(1);
+            Err((
+                ApiError::too_many_requests(
+                    format!(
+                        "Ingest quota exhausted for {} {} budget",
+                        exceeded.scope,
+                        exceeded.period.as_str()
+                    ),
+                    exceeded.retry_after_secs,
+                ),
+                "quota",
+            ))
+        }
+    }
 }
 
+/// `Splunk` is the scheme Splunk HEC clients use for their token.
 fn extract_ingest_key(headers: &HeaderMap) -> Option<String> {
     if let Some(value) = headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()) {
-        if value.len() > 7 && value[..7].eq_ignore_ascii_case("Bearer ") {
-            let token = value[7..].trim();
-            if !token.is_empty() {
-                return Some(token.to_string());
+        for scheme in ["Bearer ", "Splunk "] {
+            if value.len() > scheme.len() && value[..scheme.len()].eq_ignore_ascii_case(scheme) {
+                let token = value[scheme.len()..].trim();
+                if !token.is_empty() {
+                    return Some(token.to_string());
+                }
             }
         }
     }
@@ -652,99 +1860,141 @@ fn detect_payload_format(content_type: &str) -> Result<PayloadFormat, ApiError>
     ))
 }
 
-fn decode_payload(body: &Bytes, content_encoding: Option<&str>) -> Result<Vec<u8>, ApiError> {
-    match content_encoding {
-        None => Ok(body.to_vec()),
-        Some("gzip") => {
-            let mut decoder = GzDecoder::new(body.as_ref());
-            let mut decompressed = Vec::new();
-            decoder
-                .read_to_end(&mut decompressed)
-                .map_err(|_| ApiError::bad_request("Invalid gzip body"))?;
-            Ok(decompressed)
-        }
-        Some(_) => Err(ApiError::unsupported_media_type(
-            "Unsupported content-encoding",
-        )),
-    }
+fn decode_payload(
+    body: &Bytes,
+    content_encoding: Option<&str>,
+    limits: DecodeLimits,
+) -> Result<Vec<u8>, (ApiError, &'static str)> {
+    let Some(content_encoding) = content_encoding else {
+        return Ok(body.to_vec());
+    };
+    let encoding = ContentEncoding::parse(content_encoding).ok_or_else(|| {
+        (
+            ApiError::unsupported_media_type("Unsupported content-encoding"),
+            "decode",
+        )
+    })?;
+
+    encoding
+        .decode(body, limits.max_for(body.len()))
+        .map_err(|error| match error {
+            DecodeError::TooLarge => (
+                ApiError::payload_too_large("Decoded request body too large"),
+                "decoded_too_large",
+            ),
+            DecodeError::Invalid(reason) => {
+                warn!(encoding = encoding.as_str(), %reason, "Invalid compressed body");
+                (
+                    ApiError::bad_request(format!("Invalid {} body", encoding.as_str())),
+                    "decode",
+                )
+            }
+        })
 }
 
 fn encode_payload(payload: &[u8], content_encoding: Option<&str>) -> Result<Vec<u8>, ApiError> {
-    match content_encoding {
-        None => Ok(payload.to_vec()),
-        Some("gzip") => {
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            encoder
-                .write_all(payload)
-                .map_err(|_| ApiError::service_unavailable("Failed to encode gzip payload"))?;
-            encoder
-                .finish()
-                .map_err(|_| ApiError::service_unavailable("Failed to encode gzip payload"))
-        }
-        Some(_) => Err(ApiError::unsupported_media_type(
-            "Unsupported content-encoding",
-        )),
-    }
+    let Some(content_encoding) = content_encoding else {
+        return Ok(payload.to_vec());
+    };
+    let encoding = ContentEncoding::parse(content_encoding)
+        .ok_or_else(|| ApiError::unsupported_media_type("Unsupported content-encoding"))?;
+
+    encoding.encode(payload).map_err(|_| {
+        ApiError::service_unavailable(format!("Failed to encode {} payload", encoding.as_str()))
+    })
 }
 
 fn enrich_payload(
     signal: Signal,
     payload_format: PayloadFormat,
+    output_format: PayloadFormat,
     payload: &[u8],
     resolved_key: &ResolvedIngestKey,
+    state: &AppState,
+    sampling_policy: Option<&sampling::SamplingPolicy>,
 ) -> Result<EnrichResult, ApiError> {
-    match (signal, payload_format) {
-        (Signal::Traces, PayloadFormat::Protobuf) => {
-            let mut request = ExportTraceServiceRequest::decode(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP traces protobuf payload"))?;
-            enrich_trace_request(&mut request, resolved_key);
-            let item_count = count_trace_items(&request);
-            Ok(EnrichResult { payload: request.encode_to_vec(), item_count })
-        }
-        (Signal::Logs, PayloadFormat::Protobuf) => {
-            let mut request = ExportLogsServiceRequest::decode(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP logs protobuf payload"))?;
-            enrich_logs_request(&mut request, resolved_key);
-            let item_count = count_log_items(&request);
-            Ok(EnrichResult { payload: request.encode_to_vec(), item_count })
-        }
-        (Signal::Metrics, PayloadFormat::Protobuf) => {
-            let mut request = ExportMetricsServiceRequest::decode(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP metrics protobuf payload"))?;
-            enrich_metrics_request(&mut request, resolved_key);
-            let item_count = count_metric_items(&request);
-            Ok(EnrichResult { payload: request.encode_to_vec(), item_count })
-        }
-        (Signal::Traces, PayloadFormat::Json) => {
-            let mut request: ExportTraceServiceRequest = serde_json::from_slice(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP traces JSON payload"))?;
-            enrich_trace_request(&mut request, resolved_key);
+    match signal {
+        Signal::Traces => {
+            let mut request: ExportTraceServiceRequest =
+                decode_otlp_request(signal, payload_format, payload)?;
+            let (rejections, sampled_out) =
+                prepare_trace_request(state, &mut request, resolved_key, sampling_policy);
             let item_count = count_trace_items(&request);
-            let payload = serde_json::to_vec(&request)
-                .map_err(|_| ApiError::service_unavailable("Failed to serialize traces payload"))?;
-            Ok(EnrichResult { payload, item_count })
+            let payload = encode_otlp_request(signal, output_format, &request)?;
+            Ok(EnrichResult {
+                payload,
+                item_count,
+                rejections,
+                sampled_out,
+            })
         }
-        (Signal::Logs, PayloadFormat::Json) => {
-            let mut request: ExportLogsServiceRequest = serde_json::from_slice(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP logs JSON payload"))?;
-            enrich_logs_request(&mut request, resolved_key);
+        Signal::Logs => {
+            let mut request: ExportLogsServiceRequest =
+                decode_otlp_request(signal, payload_format, payload)?;
+            let rejections = prepare_logs_request(state, &mut request, resolved_key);
             let item_count = count_log_items(&request);
-            let payload = serde_json::to_vec(&request)
-                .map_err(|_| ApiError::service_unavailable("Failed to serialize logs payload"))?;
-            Ok(EnrichResult { payload, item_count })
+            let payload = encode_otlp_request(signal, output_format, &request)?;
+            Ok(EnrichResult {
+                payload,
+                item_count,
+                rejections,
+                sampled_out: 0,
+            })
         }
-        (Signal::Metrics, PayloadFormat::Json) => {
-            let mut request: ExportMetricsServiceRequest = serde_json::from_slice(payload)
-                .map_err(|_| ApiError::bad_request("Invalid OTLP metrics JSON payload"))?;
-            enrich_metrics_request(&mut request, resolved_key);
+        Signal::Metrics => {
+            let mut request: ExportMetricsServiceRequest =
+                decode_otlp_request(signal, payload_format, payload)?;
+            let rejections = prepare_metrics_request(&mut request, resolved_key);
             let item_count = count_metric_items(&request);
-            let payload = serde_json::to_vec(&request)
-                .map_err(|_| ApiError::service_unavailable("Failed to serialize metrics payload"))?;
-            Ok(EnrichResult { payload, item_count })
+            let payload = encode_otlp_request(signal, output_format, &request)?;
+            Ok(EnrichResult {
+                payload,
+                item_count,
+                rejections,
+                sampled_out: 0,
+            })
         }
     }
 }
 
+fn decode_otlp_request<T>(
+    signal: Signal,
+    payload_format: PayloadFormat,
+    payload: &[u8],
+) -> Result<T, ApiError>
+where
+    T: Message + Default + DeserializeOwned,
+{
+    let decoded = match payload_format {
+        PayloadFormat::Protobuf => T::decode(payload).ok(),
+        PayloadFormat::Json => serde_json::from_slice(payload).ok(),
+    };
+
+    decoded.ok_or_else(|| {
+        ApiError::bad_request(format!(
+            "Invalid OTLP {} {} payload",
+            signal.path(),
+            payload_format.label()
+        ))
+    })
+}
+
+fn encode_otlp_request<T>(
+    signal: Signal,
+    output_format: PayloadFormat,
+    request: &T,
+) -> Result<Vec<u8>, ApiError>
+where
+    T: Message + Serialize,
+{
+    match output_format {
+        PayloadFormat::Protobuf => Ok(request.encode_to_vec()),
+        PayloadFormat::Json => serde_json::to_vec(request).map_err(|_| {
+            ApiError::service_unavailable(format!("Failed to serialize {} payload", signal.path()))
+        }),
+    }
+}
+
 fn count_trace_items(request: &ExportTraceServiceRequest) -> usize {
     request
         .resource_spans
@@ -772,6 +2022,47 @@ fn count_metric_items(request: &ExportMetricsServiceRequest) -> usize {
         .sum()
 }
 
+// Validation, sampling, redaction and enrichment, in that order, shared by the
+// HTTP and gRPC receivers so both forward exactly the same data.
+
+/// Returns the rejected items and the number of spans sampled out.
+fn prepare_trace_request(
+    state: &AppState,
+    request: &mut ExportTraceServiceRequest,
+    resolved_key: &ResolvedIngestKey,
+    sampling_policy: Option<&sampling::SamplingPolicy>,
+) -> (Rejections, usize) {
+    let rejections = validate::validate_traces(request);
+    let sampled_out = sampling_policy.map_or(0, |policy| policy.apply(request));
+    if let Some(redactor) = &state.redactor {
+        redactor.redact_traces(request, &resolved_key.org_id);
+    }
+    enrich_trace_request(request, resolved_key);
+    (rejections, sampled_out)
+}
+
+fn prepare_logs_request(
+    state: &AppState,
+    request: &mut ExportLogsServiceRequest,
+    resolved_key: &ResolvedIngestKey,
+) -> Rejections {
+    let rejections = validate::validate_logs(request, state.config.max_log_body_bytes);
+    if let Some(redactor) = &state.redactor {
+        redactor.redact_logs(request, &resolved_key.org_id);
+    }
+    enrich_logs_request(request, resolved_key);
+    rejections
+}
+
+fn prepare_metrics_request(
+    request: &mut ExportMetricsServiceRequest,
+    resolved_key: &ResolvedIngestKey,
+) -> Rejections {
+    let rejections = validate::validate_metrics(request);
+    enrich_metrics_request(request, resolved_key);
+    rejections
+}
+
 fn enrich_trace_request(request: &mut ExportTraceServiceRequest, resolved_key: &ResolvedIngestKey) {
     for resource_span in &mut request.resource_spans {
         let resource = resource_span.resource.get_or_insert_with(Resource::default);
@@ -823,15 +2114,179 @@ fn upsert_string_attribute(attributes: &mut Vec<KeyValue>, key: &str, value: &st
     });
 }
 
+/// Forwards an enriched payload, falling back to the on-disk buffer (when
+/// configured) if the collector is unavailable.
+async fn deliver_enriched(
+    state: &AppState,
+    signal: Signal,
+    payload_format: PayloadFormat,
+    content_encoding: Option<&str>,
+    payload: Bytes,
+    resolved_key: &ResolvedIngestKey,
+) -> Result<Response, (ApiError, &'static str)> {
+    if let Some(mirrors) = &state.mirrors {
+        let forwarded_format = state
+            .routes
+            .route_for(signal, resolved_key)
+            .output_format(payload_format);
+        mirrors.mirror(
+            signal,
+            forwarded_format,
+            content_encoding,
+            &payload,
+            resolved_key,
+        );
+    }
+
+    let Some(spool) = &state.spool else {
+        return forward_enriched(
+            state,
+            signal,
+            payload_format,
+            content_encoding,
+            payload,
+            resolved_key,
+        )
+        .await;
+    };
+
+    let result = forward_enriched(
+        state,
+        signal,
+        payload_format,
+        content_encoding,
+        payload.clone(),
+        resolved_key,
+    )
+    .await;
+
+    match result {
+        Err((error, "forward" | "circuit_open"))
+            if error.status == StatusCode::SERVICE_UNAVAILABLE =>
+        {
+            let spooled = spool::SpooledPayload {
+                signal,
+                payload_format,
+                content_encoding: content_encoding.map(str::to_string),
+                resolved_key: resolved_key.clone(),
+                enqueued_at_ms: spool::unix_millis(),
+                payload,
+            };
+
+            match spool.push(spooled).await {
+                Ok(()) => {
+                    debug!("Collector unavailable, payload buffered to disk");
+                    Ok(full_success_response(payload_format))
+                }
+                Err(spool_error) => {
+                    error!(error = %spool_error, "Failed to buffer payload");
+                    Err((error, "forward"))
+                }
+            }
+        }
+        result => result,
+    }
+}
+
+/// Sends an enriched, uncompressed payload to the route matching the signal
+/// and key, over that route's protocol. `payload_format` is the format the
+/// client used, which is also the format the response is rendered in.
+///
+/// Connect errors, 502/503/504 and 429s are retried with jittered backoff
+/// until the route's timeout runs out; while the route's circuit breaker is
+/// open nothing is sent and the payload fails fast with a 503.
+async fn forward_enriched(
+    state: &AppState,
+    signal: Signal,
+    payload_format: PayloadFormat,
+    content_encoding: Option<&str>,
+    payload: Bytes,
+    resolved_key: &ResolvedIngestKey,
+) -> Result<Response, (ApiError, &'static str)> {
+    let route = state.routes.route_for(signal, resolved_key);
+    let outbound_body = match route.grpc_forwarder() {
+        Some(_) => Bytes::new(),
+        None => Bytes::from(encode_payload(&payload, content_encoding).map_err(|e| (e, "encode"))?),
+    };
+
+    let policy = state.config.retry_policy;
+    let deadline = Instant::now() + route.timeout;
+    let mut attempt = 1;
+    loop {
+        if !route.breaker.allow() {
+            counter!("ingest_forward_circuit_open_total", "signal" => signal.path(), "route" => route.name.clone())
+                .increment(1);
+            return Err((
+                ApiError::service_unavailable("Telemetry backend unavailable"),
+                "circuit_open",
+            ));
+        }
+
+        let attempt_timeout = deadline.saturating_duration_since(Instant::now());
+        let (result, outcome) = match route.grpc_forwarder() {
+            Some(forwarder) => {
+                forwarder
+                    .forward(
+                        signal,
+                        payload_format,
+                        content_encoding,
+                        payload.clone(),
+                        resolved_key,
+                        attempt_timeout,
+                    )
+                    .await
+            }
+            None => {
+                forward_to_collector(
+                    state,
+                    route,
+                    signal,
+                    payload_format.content_type(),
+                    content_encoding,
+                    outbound_body.clone(),
+                    resolved_key,
+                    attempt_timeout,
+                )
+                .await
+            }
+        };
+        route.breaker.record(outcome);
+
+        let delay = match outcome {
+            retry::Outcome::Unavailable => policy.backoff(attempt),
+            retry::Outcome::Throttled(retry_after) => {
+                retry_after.unwrap_or_else(|| policy.backoff(attempt))
+            }
+            retry::Outcome::Delivered | retry::Outcome::Failed => {
+                return result.map_err(|e| (e, "forward"));
+            }
+        };
+        if attempt >= policy.max_attempts || Instant::now() + delay >= deadline {
+            return result.map_err(|e| (e, "forward"));
+        }
+
+        counter!("ingest_forward_retries_total", "signal" => signal.path(), "route" => route.name.clone())
+            .increment(1);
+        debug!(attempt, delay_ms = delay.as_millis() as u64, outcome = ?outcome, "Retrying forward");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// One OTLP/HTTP attempt, bounded by `timeout`, and what it says about the
+/// collector's health.
+#[allow(clippy::too_many_arguments)]
 async fn forward_to_collector(
     state: &AppState,
+    route: &routing::Route,
     signal: Signal,
     content_type: &str,
     content_encoding: Option<&str>,
-    body: Vec<u8>,
+    body: Bytes,
     resolved_key: &ResolvedIngestKey,
-) -> Result<Response, ApiError> {
-    let url = format!("{}/v1/{}", state.config.forward_endpoint, signal.path());
+    timeout: Duration,
+) -> (Result<Response, ApiError>, retry::Outcome) {
+    let url = format!("{}/v1/{}", route.endpoint, signal.path());
     let outbound_bytes = body.len();
 
     debug!(url = %url, outbound_bytes, "Forwarding to collector");
@@ -839,6 +2294,7 @@ async fn forward_to_collector(
     let mut request_builder = state
         .http_client
         .request(Method::POST, &url)
+        .timeout(timeout)
         .header(CONTENT_TYPE, content_type)
         .body(body);
 
@@ -847,35 +2303,39 @@ async fn forward_to_collector(
     }
 
     let forward_start = Instant::now();
-    let response = request_builder.send().await.map_err(|error| {
-        let forward_duration = forward_start.elapsed();
-        histogram!("ingest_forward_duration_seconds", "signal" => signal.path())
-            .record(forward_duration.as_secs_f64());
-        counter!("ingest_forward_responses_total", "signal" => signal.path(), "upstream_status" => "error")
-            .increment(1);
-        error!(
-            error = %error,
-            signal = signal.path(),
-            org_id = %resolved_key.org_id,
-            key_id = %resolved_key.key_id,
-            url = %url,
-            "Collector forwarding failed"
-        );
-        ApiError::service_unavailable("Telemetry backend unavailable")
-    })?;
+    let response = match request_builder.send().await {
+        Ok(response) => response,
+        Err(error) => {
+            let forward_duration = forward_start.elapsed();
+            histogram!("ingest_forward_duration_seconds", "signal" => signal.path(), "route" => route.name.clone())
+                .record(forward_duration.as_secs_f64());
+            counter!("ingest_forward_responses_total", "signal" => signal.path(), "route" => route.name.clone(), "upstream_status" => "error")
+                .increment(1);
+            error!(
+                error = %error,
+                signal = signal.path(),
+                org_id = %resolved_key.org_id,
+                key_id = %resolved_key.key_id,
+                route = %route.name,
+                url = %url,
+                "Collector forwarding failed"
+            );
+            return (
+                Err(ApiError::service_unavailable(
+                    "Telemetry backend unavailable",
+                )),
+                retry::Outcome::Unavailable,
+            );
+        }
+    };
 
     let forward_duration = forward_start.elapsed();
-    histogram!("ingest_forward_duration_seconds", "signal" => signal.path())
+    histogram!("ingest_forward_duration_seconds", "signal" => signal.path(), "route" => route.name.clone())
         .record(forward_duration.as_secs_f64());
 
     let upstream_status_code = response.status().as_u16();
-    let status_bucket = match upstream_status_code {
-        200..=299 => "2xx",
-        400..=499 => "4xx",
-        500..=599 => "5xx",
-        _ => "other",
-    };
-    counter!("ingest_forward_responses_total", "signal" => signal.path(), "upstream_status" => status_bucket)
+    let status_bucket = upstream_status_bucket(upstream_status_code);
+    counter!("ingest_forward_responses_total", "signal" => signal.path(), "route" => route.name.clone(), "upstream_status" => status_bucket)
         .increment(1);
 
     debug!(
@@ -884,38 +2344,71 @@ async fn forward_to_collector(
         "Collector response"
     );
 
+    let retry_after = response.headers().get(RETRY_AFTER).cloned();
+    let outcome = retry::Outcome::from_status(
+        upstream_status_code,
+        retry::parse_retry_after(retry_after.as_ref()),
+    );
+
     if response.status().is_server_error() {
         error!(
             upstream_status = upstream_status_code,
             signal = signal.path(),
             org_id = %resolved_key.org_id,
+            route = %route.name,
             "Collector returned error"
         );
-        return Err(ApiError::service_unavailable("Telemetry backend unavailable"));
+        return (
+            Err(ApiError::service_unavailable(
+                "Telemetry backend unavailable",
+            )),
+            outcome,
+        );
     }
 
     let status = StatusCode::from_u16(upstream_status_code).unwrap_or(StatusCode::BAD_GATEWAY);
 
     let upstream_content_type = response.headers().get(CONTENT_TYPE).cloned();
-    let upstream_body = response.bytes().await.map_err(|error| {
-        error!(
-            error = %error,
-            signal = signal.path(),
-            org_id = %resolved_key.org_id,
-            key_id = %resolved_key.key_id,
-            "Failed reading collector response"
-        );
-        ApiError::service_unavailable("Telemetry backend unavailable")
-    })?;
+    let upstream_body = match response.bytes().await {
+        Ok(upstream_body) => upstream_body,
+        Err(error) => {
+            error!(
+                error = %error,
+                signal = signal.path(),
+                org_id = %resolved_key.org_id,
+                key_id = %resolved_key.key_id,
+                "Failed reading collector response"
+            );
+            return (
+                Err(ApiError::service_unavailable(
+                    "Telemetry backend unavailable",
+                )),
+                retry::Outcome::Unavailable,
+            );
+        }
+    };
 
     let mut response = Response::builder().status(status);
     if let Some(content_type) = upstream_content_type {
         response = response.header(CONTENT_TYPE, content_type);
     }
+    if let Some(retry_after) = retry_after {
+        response = response.header(RETRY_AFTER, retry_after);
+    }
 
-    response
+    let response = response
         .body(axum::body::Body::from(upstream_body))
-        .map_err(|_| ApiError::service_unavailable("Telemetry backend unavailable"))
+        .map_err(|_| ApiError::service_unavailable("Telemetry backend unavailable"));
+    (response, outcome)
+}
+
+fn upstream_status_bucket(status_code: u16) -> &'static str {
+    match status_code {
+        200..=299 => "2xx",
+        400..=499 => "4xx",
+        500..=599 => "5xx",
+        _ => "other",
+    }
 }
 
 impl IngestKeyResolver {
@@ -930,13 +2423,20 @@ impl IngestKeyResolver {
         };
 
         let key_hash = hash_ingest_key(raw_key, &self.lookup_hmac_key)?;
+        if let Some(negative_cache) = &self.negative_cache {
+            if negative_cache.contains_key(&key_hash) {
+                counter!("ingest_key_negative_cache_hits_total").increment(1);
+                return Ok(None);
+            }
+        }
+
         let hash_column = match key_type {
             IngestKeyType::Public => "public_key_hash",
             IngestKeyType::Private => "private_key_hash",
         };
 
         let query = format!(
-            "SELECT org_id FROM org_ingest_keys WHERE {hash_column} = ? LIMIT 1"
+            "SELECT org_id, public_key_allowed_origins_json FROM org_ingest_keys WHERE {hash_column} = ? LIMIT 1"
         );
 
         let conn = self.db.connect().map_err(|error| error.to_string())?;
@@ -946,21 +2446,166 @@ impl IngestKeyResolver {
             .map_err(|error| error.to_string())?;
 
         let Some(row) = rows.next().await.map_err(|error| error.to_string())? else {
+            if let Some(negative_cache) = &self.negative_cache {
+                negative_cache.insert(key_hash, ()).await;
+            }
             return Ok(None);
         };
 
         let org_id: String = row.get(0).map_err(|error| error.to_string())?;
+        let allowed_origins_json: Option<String> = row.get(1).map_err(|error| error.to_string())?;
+
+        // The allow-list only restricts the public key; a malformed one fails
+        // closed rather than silently allowing every origin.
+        let allowed_origins = match (key_type, allowed_origins_json) {
+            (IngestKeyType::Public, Some(raw)) => {
+                let origins: Vec<String> = serde_json::from_str(&raw).map_err(|error| {
+                    format!("invalid public_key_allowed_origins_json for {org_id}: {error}")
+                })?;
+                Some(
+                    origins
+                        .iter()
+                        .map(|origin| normalize_origin(origin))
+                        .collect(),
+                )
+            }
+            _ => None,
+        };
 
         let resolved = ResolvedIngestKey {
             org_id,
             key_type,
             key_id: key_hash.chars().take(16).collect(),
+            allowed_origins,
         };
 
         self.cache.insert(raw_key.to_string(), resolved.clone()).await;
 
         Ok(Some(resolved))
     }
+
+    /// Polls `org_ingest_keys` so rotated or deleted keys stop resolving
+    /// within one interval instead of lingering until the cache TTL.
+    fn spawn_revocation_poller(&self, interval: Duration) {
+        let resolver = self.clone();
+        tokio::spawn(async move {
+            let mut snapshot = None;
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+                if let Err(error) = resolver.apply_key_changes(&mut snapshot).await {
+                    warn!(error = %error, "Failed to poll ingest key changes");
+                    counter!("ingest_key_poll_errors_total").increment(1);
+                }
+            }
+        });
+    }
+
+    async fn apply_key_changes(
+        &self,
+        snapshot: &mut Option<IngestKeySnapshot>,
+    ) -> Result<(), String> {
+        let conn = self.db.connect().map_err(|error| error.to_string())?;
+        let mut rows = conn
+            .query(
+                "SELECT COALESCE(MAX(updated_at), 0), COUNT(*) FROM org_ingest_keys",
+                (),
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        let row = rows
+            .next()
+            .await
+            .map_err(|error| error.to_string())?
+            .ok_or_else(|| "Empty ingest key version query".to_string())?;
+        let version = IngestKeyTableVersion {
+            max_updated_at: row.get(0).map_err(|error| error.to_string())?,
+            row_count: row.get(1).map_err(|error| error.to_string())?,
+        };
+        if snapshot
+            .as_ref()
+            .is_some_and(|snapshot| snapshot.version == version)
+        {
+            return Ok(());
+        }
+
+        let mut rows = conn
+            .query(
+                "SELECT org_id, public_key_hash, private_key_hash, public_key_allowed_origins_json
+                 FROM org_ingest_keys",
+                (),
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+        let mut keys = HashMap::new();
+        while let Some(row) = rows.next().await.map_err(|error| error.to_string())? {
+            let org_id: String = row.get(0).map_err(|error| error.to_string())?;
+            let public_key_hash: String = row.get(1).map_err(|error| error.to_string())?;
+            let private_key_hash: String = row.get(2).map_err(|error| error.to_string())?;
+            let allowed_origins_json: Option<String> =
+                row.get(3).map_err(|error| error.to_string())?;
+            keys.insert(
+                org_id,
+                OrgIngestKeys {
+                    key_hashes: [public_key_hash, private_key_hash],
+                    allowed_origins_json,
+                },
+            );
+        }
+
+        let previous = snapshot.replace(IngestKeySnapshot { version, keys });
+        let (Some(previous), Some(current)) = (previous, snapshot.as_ref()) else {
+            return Ok(());
+        };
+
+        // Diffing whole snapshots catches a deletion even when an insert in
+        // the same interval keeps the row count level. A changed origin
+        // allow-list counts as a rotation, since cached keys carry it.
+        let mut rotated = HashSet::new();
+        for (org_id, keys) in &current.keys {
+            let previous_keys = previous.keys.get(org_id);
+            if previous_keys == Some(keys) {
+                continue;
+            }
+            if previous_keys.map(|previous| &previous.key_hashes) != Some(&keys.key_hashes) {
+                if let Some(negative_cache) = &self.negative_cache {
+                    for key_hash in &keys.key_hashes {
+                        negative_cache.invalidate(key_hash).await;
+                    }
+                }
+            }
+            if previous_keys.is_some() {
+                rotated.insert(org_id.clone());
+            }
+        }
+        let deleted: HashSet<String> = previous
+            .keys
+            .into_keys()
+            .filter(|org_id| !current.keys.contains_key(org_id))
+            .collect();
+
+        if rotated.is_empty() && deleted.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            rotated = rotated.len(),
+            deleted = deleted.len(),
+            "Ingest keys changed, invalidating cached keys"
+        );
+        counter!("ingest_key_cache_invalidations_total", "reason" => "rotated")
+            .increment(rotated.len() as u64);
+        counter!("ingest_key_cache_invalidations_total", "reason" => "deleted")
+            .increment(deleted.len() as u64);
+        let org_ids: HashSet<String> = rotated.into_iter().chain(deleted).collect();
+        self.cache
+            .invalidate_entries_if(move |_, resolved| org_ids.contains(&resolved.org_id))
+            .map_err(|error| error.to_string())?;
+
+        Ok(())
+    }
 }
 
 fn infer_ingest_key_type(raw_key: &str) -> Option<IngestKeyType> {
@@ -1058,6 +2703,34 @@ fn parse_bool(name: &str, raw: Option<String>, default: bool) -> Result<bool, St
     }
 }
 
+fn parse_forward_protocol(name: &str, raw: Option<String>) -> Result<ForwardProtocol, String> {
+    let Some(raw) = raw else {
+        return Ok(ForwardProtocol::Http);
+    };
+
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "" | "http" | "http/protobuf" => Ok(ForwardProtocol::Http),
+        "grpc" => Ok(ForwardProtocol::Grpc),
+        _ => Err(format!("{name} must be http or grpc")),
+    }
+}
+
+fn parse_forward_compression(
+    name: &str,
+    raw: Option<String>,
+) -> Result<ForwardCompression, String> {
+    let Some(raw) = raw else {
+        return Ok(ForwardCompression::Passthrough);
+    };
+
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "" => Ok(ForwardCompression::Passthrough),
+        value => ForwardCompression::parse(value).ok_or_else(|| {
+            format!("{name} must be passthrough, none, gzip, deflate, zstd or snappy")
+        }),
+    }
+}
+
 fn parse_u16(name: &str, raw: Option<String>, default: u16) -> Result<u16, String> {
     let Some(raw) = raw else {
         return Ok(default);
@@ -1103,10 +2776,77 @@ fn parse_usize(name: &str, raw: Option<String>, default: usize) -> Result<usize,
         .map_err(|_| format!("{name} must be a positive integer"))
 }
 
+/// A comma-separated list of signal names; unset or empty means all signals.
+fn parse_signals(name: &str, raw: Option<String>) -> Result<HashSet<&'static str>, String> {
+    let all = HashSet::from(["traces", "logs", "metrics"]);
+    let Some(raw) = raw.filter(|raw| !raw.trim().is_empty()) else {
+        return Ok(all);
+    };
+
+    raw.split(',')
+        .map(|signal| signal.trim().to_ascii_lowercase())
+        .filter(|signal| !signal.is_empty())
+        .map(|signal| {
+            all.get(signal.as_str())
+                .copied()
+                .ok_or_else(|| format!("{name} has unknown signal '{signal}'"))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn ingest_keys_are_read_from_bearer_splunk_or_maple_headers() {
+        let key = |name, value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(name, HeaderValue::from_static(value));
+            extract_ingest_key(&headers)
+        };
+
+        assert_eq!(
+            key(AUTHORIZATION, "Bearer maple_sk_1").as_deref(),
+            Some("maple_sk_1")
+        );
+        assert_eq!(
+            key(AUTHORIZATION, "splunk maple_sk_2").as_deref(),
+            Some("maple_sk_2")
+        );
+        assert_eq!(key(AUTHORIZATION, "Basic dXNlcg=="), None);
+        assert_eq!(key(AUTHORIZATION, "Splunk  "), None);
+        assert_eq!(
+            key(HeaderName::from_static("x-maple-ingest-key"), "maple_pk_3").as_deref(),
+            Some("maple_pk_3")
+        );
+    }
+
+    #[test]
+    fn public_key_origins_match_exactly_or_by_subdomain_wildcard() {
+        let allowed: Vec<String> = ["https://app.example.com/", "https://*.example.org"]
+            .iter()
+            .map(|origin| normalize_origin(origin))
+            .collect();
+
+        assert!(origin_allowed(&allowed, "https://app.example.com"));
+        assert!(origin_allowed(&allowed, "HTTPS://App.Example.com"));
+        assert!(origin_allowed(&allowed, "https://eu.shop.example.org"));
+        assert!(!origin_allowed(&allowed, "https://example.org"));
+        assert!(!origin_allowed(&allowed, "https://evilexample.org"));
+        assert!(!origin_allowed(&allowed, "http://app.example.com"));
+        assert!(!origin_allowed(&allowed, "https://app.example.com.evil.io"));
+    }
+
+    #[test]
+    fn public_key_signals_default_to_all_and_reject_unknown_names() {
+        assert_eq!(parse_signals("S", None).unwrap().len(), 3);
+        let signals = parse_signals("S", Some(" Traces, logs ".to_string())).unwrap();
+        assert!(signals.contains("traces") && signals.contains("logs"));
+        assert!(!signals.contains("metrics"));
+        assert!(parse_signals("S", Some("traces,profiles".to_string())).is_err());
+    }
+
     #[test]
     fn hash_is_deterministic() {
         let hash_a = hash_ingest_key("maple_pk_123", "secret").unwrap();
@@ -1135,6 +2875,7 @@ mod tests {
             org_id: "org_real".to_string(),
             key_type: IngestKeyType::Private,
             key_id: "abc".to_string(),
+            allowed_origins: None,
         };
 
         enrich_resource_attributes(&mut attributes, &resolved);
@@ -1161,6 +2902,28 @@ mod tests {
         assert!(!values.contains_key("org_id"));
     }
 
+    #[test]
+    fn rejected_and_sampled_out_items_are_not_billed() {
+        let mut result = EnrichResult {
+            payload: Vec::new(),
+            item_count: 4,
+            rejections: Rejections::default(),
+            sampled_out: 0,
+        };
+        assert_eq!(result.billable_bytes(1_000), 1_000);
+
+        result.item_count = 2;
+        result.rejections.count = 1;
+        result.sampled_out = 1;
+        assert_eq!(result.billable_bytes(1_000), 500);
+
+        // Everything rejected: a 200 partial success that forwards nothing.
+        result.item_count = 0;
+        result.rejections.count = 4;
+        result.sampled_out = 0;
+        assert_eq!(result.billable_bytes(1_000), 0);
+    }
+
     #[test]
     fn relative_file_url_resolves_for_local_db_default() {
         let path = resolve_local_db_path("file:../api/.data/maple.db")
@@ -1174,4 +2937,129 @@ mod tests {
         assert!(is_remote_db_url("https://example.com"));
         assert!(!is_remote_db_url("file:../api/.data/maple.db"));
     }
+
+    #[tokio::test]
+    async fn rotated_keys_stop_resolving_and_new_keys_clear_negative_cache() {
+        let path =
+            std::env::temp_dir().join(format!("maple-ingest-keys-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let db = Builder::new_local(&path).build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE org_ingest_keys (
+                org_id TEXT PRIMARY KEY,
+                public_key_hash TEXT NOT NULL,
+                private_key_hash TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                public_key_allowed_origins_json TEXT
+            );",
+        )
+        .await
+        .unwrap();
+
+        let hash = |raw: &str| hash_ingest_key(raw, "secret").unwrap();
+        conn.execute(
+            "INSERT INTO org_ingest_keys (org_id, public_key_hash, private_key_hash, updated_at) VALUES ('org_a', ?, ?, 1)",
+            params![hash("maple_pk_old"), hash("maple_sk_a")],
+        )
+        .await
+        .unwrap();
+
+        let resolver = IngestKeyResolver {
+            db: Arc::new(db),
+            lookup_hmac_key: "secret".to_string(),
+            cache: Cache::builder().support_invalidation_closures().build(),
+            negative_cache: Some(Cache::builder().build()),
+        };
+        let mut snapshot = None;
+        resolver.apply_key_changes(&mut snapshot).await.unwrap();
+
+        assert!(resolver
+            .resolve_ingest_key("maple_pk_old")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(resolver
+            .resolve_ingest_key("maple_pk_new")
+            .await
+            .unwrap()
+            .is_none());
+
+        conn.execute(
+            "UPDATE org_ingest_keys SET public_key_hash = ?, updated_at = 2",
+            params![hash("maple_pk_new")],
+        )
+        .await
+        .unwrap();
+        resolver.apply_key_changes(&mut snapshot).await.unwrap();
+
+        assert!(resolver
+            .resolve_ingest_key("maple_pk_old")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(resolver
+            .resolve_ingest_key("maple_pk_new")
+            .await
+            .unwrap()
+            .is_some());
+
+        // A deletion in the same interval as an insert still revokes, even
+        // though the row count is unchanged.
+        assert!(resolver
+            .resolve_ingest_key("maple_sk_a")
+            .await
+            .unwrap()
+            .is_some());
+        conn.execute("DELETE FROM org_ingest_keys WHERE org_id = 'org_a'", ())
+            .await
+            .unwrap();
+        conn.execute(
+            "INSERT INTO org_ingest_keys (org_id, public_key_hash, private_key_hash, updated_at) VALUES ('org_b', ?, ?, 3)",
+            params![hash("maple_pk_b"), hash("maple_sk_b")],
+        )
+        .await
+        .unwrap();
+        resolver.apply_key_changes(&mut snapshot).await.unwrap();
+
+        assert!(resolver
+            .resolve_ingest_key("maple_sk_a")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(resolver
+            .resolve_ingest_key("maple_sk_b")
+            .await
+            .unwrap()
+            .is_some());
+
+        // Narrowing a public key's origins evicts it like a rotation.
+        let origins = |raw_key: &str| {
+            let resolver = &resolver;
+            let raw_key = raw_key.to_string();
+            async move {
+                resolver
+                    .resolve_ingest_key(&raw_key)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .allowed_origins
+            }
+        };
+        assert_eq!(origins("maple_pk_b").await, None);
+        conn.execute(
+            "UPDATE org_ingest_keys SET public_key_allowed_origins_json = ?, updated_at = 4 WHERE org_id = 'org_b'",
+            params![r#"["https://app.example.com"]"#],
+        )
+        .await
+        .unwrap();
+        resolver.apply_key_changes(&mut snapshot).await.unwrap();
+        assert_eq!(
+            origins("maple_pk_b").await.as_deref(),
+            Some(&["https://app.example.com".to_string()][..])
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
 }
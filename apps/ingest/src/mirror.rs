@@ -0,0 +1,324 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use axum::body::Bytes;
+use axum::http::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use metrics::{counter, gauge};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, warn};
+
+use crate::routing::Matcher;
+use crate::{encode_payload, PayloadFormat, ResolvedIngestKey, Signal};
+
+const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_QUEUE_CAPACITY: usize = 1_000;
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const RETRY_MIN_BACKOFF: Duration = Duration::from_millis(200);
+const RETRY_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// One entry of `INGEST_MIRRORS_FILE`. Empty match lists match everything.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MirrorSpec {
+    name: String,
+    /// OTLP/HTTP base URL; `/v1/<signal>` is appended.
+    endpoint: String,
+    #[serde(default)]
+    org_ids: Vec<String>,
+    #[serde(default)]
+    signals: Vec<String>,
+    #[serde(default)]
+    key_types: Vec<String>,
+    timeout_ms: Option<u64>,
+    queue_capacity: Option<usize>,
+    max_attempts: Option<u32>,
+    #[serde(default)]
+    require_tls: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MirrorsFile {
+    mirrors: Vec<MirrorSpec>,
+}
+
+/// A copy of a forwarded payload, in the format and encoding the primary
+/// collector received it.
+struct MirrorJob {
+    signal: Signal,
+    payload_format: PayloadFormat,
+    content_encoding: Option<String>,
+    payload: Bytes,
+}
+
+/// A secondary OTLP/HTTP endpoint fed from its own bounded queue. A full
+/// queue drops the copy rather than slowing the primary path down.
+struct Mirror {
+    name: String,
+    matcher: Matcher,
+    tx: mpsc::Sender<MirrorJob>,
+}
+
+/// Copies matching traffic to secondary exporters. Delivery is best-effort:
+/// copies still queued at shutdown are lost.
+pub struct Mirrors {
+    mirrors: Vec<Mirror>,
+}
+
+impl Mirrors {
+    pub fn load(path: &Path, http_client: &Client, require_tls: bool) -> Result<Self, String> {
+        let raw = std::fs::read(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+        let file: MirrorsFile = serde_json::from_slice(&raw)
+            .map_err(|error| format!("invalid mirrors in {}: {error}", path.display()))?;
+
+        let mut names = HashSet::new();
+        let mut mirrors = Vec::with_capacity(file.mirrors.len());
+        for spec in file.mirrors {
+            if !names.insert(spec.name.clone()) {
+                return Err(format!("duplicate mirror name '{}'", spec.name));
+            }
+            let (mirror, rx) = mirror_from_spec(&spec, require_tls)?;
+            tokio::spawn(deliver_loop(
+                rx,
+                http_client.clone(),
+                spec.name,
+                spec.endpoint.trim().trim_end_matches('/').to_string(),
+                Duration::from_millis(spec.timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS)),
+                spec.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            ));
+            mirrors.push(mirror);
+        }
+
+        Ok(Self { mirrors })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.mirrors.iter().map(|mirror| mirror.name.as_str())
+    }
+
+    /// Queues a copy for every matching mirror without waiting on any of them.
+    pub fn mirror(
+        &self,
+        signal: Signal,
+        payload_format: PayloadFormat,
+        content_encoding: Option<&str>,
+        payload: &Bytes,
+        resolved_key: &ResolvedIngestKey,
+    ) {
+        for mirror in &self.mirrors {
+            if !mirror.matcher.matches(signal, resolved_key) {
+                continue;
+            }
+            let job = MirrorJob {
+                signal,
+                payload_format,
+                content_encoding: content_encoding.map(str::to_string),
+                payload: payload.clone(),
+            };
+            match mirror.tx.try_send(job) {
+                Ok(()) => {
+                    gauge!("ingest_mirror_queue_depth", "mirror" => mirror.name.clone())
+                        .increment(1.0);
+                }
+                Err(TrySendError::Full(_) | TrySendError::Closed(_)) => {
+                    record_outcome(&mirror.name, signal, "dropped");
+                }
+            }
+        }
+    }
+}
+
+fn mirror_from_spec(
+    spec: &MirrorSpec,
+    require_tls: bool,
+) -> Result<(Mirror, mpsc::Receiver<MirrorJob>), String> {
+    let endpoint = spec.endpoint.trim();
+    if endpoint.is_empty() {
+        return Err(format!("mirror '{}' has no endpoint", spec.name));
+    }
+    if (spec.require_tls || require_tls) && !endpoint.starts_with("https://") {
+        return Err(format!(
+            "mirror '{}' requires TLS but {endpoint} is not https",
+            spec.name
+        ));
+    }
+
+    let matcher = Matcher::new(
+        &spec.name,
+        spec.org_ids.clone(),
+        &spec.signals,
+        &spec.key_types,
+    )?;
+    let (tx, rx) = mpsc::channel(spec.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY).max(1));
+    Ok((
+        Mirror {
+            name: spec.name.clone(),
+            matcher,
+            tx,
+        },
+        rx,
+    ))
+}
+
+async fn deliver_loop(
+    mut rx: mpsc::Receiver<MirrorJob>,
+    http_client: Client,
+    name: String,
+    endpoint: String,
+    timeout: Duration,
+    max_attempts: u32,
+) {
+    while let Some(job) = rx.recv().await {
+        gauge!("ingest_mirror_queue_depth", "mirror" => name.clone()).decrement(1.0);
+
+        let body = match encode_payload(&job.payload, job.content_encoding.as_deref()) {
+            Ok(body) => Bytes::from(body),
+            Err(error) => {
+                warn!(mirror = %name, error = %error.message, "Failed to encode mirrored payload");
+                record_outcome(&name, job.signal, "failed");
+                continue;
+            }
+        };
+        let url = format!("{endpoint}/v1/{}", job.signal.path());
+
+        let mut backoff = RETRY_MIN_BACKOFF;
+        let mut attempt = 1;
+        let outcome = loop {
+            let mut request = http_client
+                .post(&url)
+                .timeout(timeout)
+                .header(CONTENT_TYPE, job.payload_format.content_type())
+                .body(body.clone());
+            if let Some(content_encoding) = &job.content_encoding {
+                request = request.header(CONTENT_ENCODING, content_encoding);
+            }
+
+            let retryable = match request.send().await {
+                Ok(response) if response.status().is_success() => break "ok",
+                Ok(response) => {
+                    let status = response.status();
+                    debug!(mirror = %name, status = status.as_u16(), "Mirror rejected payload");
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(error) => {
+                    debug!(mirror = %name, error = %error, "Mirror request failed");
+                    true
+                }
+            };
+            if !retryable || attempt >= max_attempts {
+                break "failed";
+            }
+
+            counter!("ingest_mirror_retries_total", "mirror" => name.clone()).increment(1);
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(RETRY_MAX_BACKOFF);
+            attempt += 1;
+        };
+
+        if outcome == "failed" {
+            warn!(
+                mirror = %name,
+                signal = job.signal.path(),
+                attempts = attempt,
+                "Giving up on mirrored payload"
+            );
+        }
+        record_outcome(&name, job.signal, outcome);
+    }
+}
+
+fn record_outcome(name: &str, signal: Signal, outcome: &'static str) {
+    counter!(
+        "ingest_mirror_requests_total",
+        "mirror" => name.to_string(),
+        "signal" => signal.path(),
+        "outcome" => outcome
+    )
+    .increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IngestKeyType;
+
+    fn spec(value: serde_json::Value) -> MirrorSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn key(org_id: &str) -> ResolvedIngestKey {
+        ResolvedIngestKey {
+            org_id: org_id.to_string(),
+            key_type: IngestKeyType::Private,
+            key_id: "key".to_string(),
+            allowed_origins: None,
+        }
+    }
+
+    #[test]
+    fn only_matching_traffic_is_queued_and_a_full_queue_drops() {
+        let (mirror, mut rx) = mirror_from_spec(
+            &spec(serde_json::json!({
+                "name": "migration",
+                "endpoint": "http://new-collector:4318",
+                "org_ids": ["org_a"],
+                "signals": ["logs"],
+                "queue_capacity": 1,
+            })),
+            false,
+        )
+        .unwrap();
+        let mirrors = Mirrors {
+            mirrors: vec![mirror],
+        };
+        let payload = Bytes::from_static(b"{}");
+
+        mirrors.mirror(
+            Signal::Traces,
+            PayloadFormat::Json,
+            None,
+            &payload,
+            &key("org_a"),
+        );
+        mirrors.mirror(
+            Signal::Logs,
+            PayloadFormat::Json,
+            None,
+            &payload,
+            &key("org_b"),
+        );
+        assert!(rx.try_recv().is_err());
+
+        mirrors.mirror(
+            Signal::Logs,
+            PayloadFormat::Json,
+            Some("gzip"),
+            &payload,
+            &key("org_a"),
+        );
+        mirrors.mirror(
+            Signal::Logs,
+            PayloadFormat::Json,
+            None,
+            &payload,
+            &key("org_a"),
+        );
+        let job = rx.try_recv().unwrap();
+        assert_eq!(job.content_encoding.as_deref(), Some("gzip"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn tls_is_enforced_for_mirrors() {
+        let plaintext = spec(serde_json::json!({
+            "name": "customer",
+            "endpoint": "http://customer-collector:4318",
+        }));
+        assert!(mirror_from_spec(&plaintext, true).is_err());
+        assert!(mirror_from_spec(&plaintext, false).is_ok());
+    }
+}
@@ -0,0 +1,545 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use libsql::{params, params_from_iter, Database};
+use metrics::{counter, gauge};
+use moka::future::Cache;
+use tracing::{error, warn};
+
+use crate::spool::unix_millis;
+
+const DAY_MS: i64 = 86_400_000;
+/// Quota rows scoped to every signal use this value in the `signal` column.
+const ALL_SIGNALS: &str = "all";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum QuotaPeriod {
+    Day,
+    Month,
+}
+
+impl QuotaPeriod {
+    fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "day" => Some(Self::Day),
+            "month" => Some(Self::Month),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Month => "month",
+        }
+    }
+
+    /// Returns the `[start, end)` bounds, in epoch milliseconds, of the UTC
+    /// calendar window containing `now_ms`.
+    fn window(self, now_ms: i64) -> (i64, i64) {
+        let day = now_ms.div_euclid(DAY_MS);
+        match self {
+            Self::Day => (day * DAY_MS, (day + 1) * DAY_MS),
+            Self::Month => {
+                let (year, month, _) = civil_from_days(day);
+                let (next_year, next_month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                (
+                    days_from_civil(year, month, 1) * DAY_MS,
+                    days_from_civil(next_year, next_month, 1) * DAY_MS,
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct QuotaLimit {
+    scope: String,
+    period: QuotaPeriod,
+    limit_bytes: u64,
+    soft_limit_bytes: Option<u64>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    org_id: String,
+    scope: String,
+    period: QuotaPeriod,
+    window_start: i64,
+}
+
+#[derive(Default)]
+struct UsageCounter {
+    /// Bytes already stored in `org_ingest_usage`, including other replicas.
+    synced_bytes: u64,
+    /// Bytes accepted here that have not been written back yet.
+    pending_bytes: u64,
+    soft_limit_warned: bool,
+}
+
+impl UsageCounter {
+    fn total(&self) -> u64 {
+        self.synced_bytes + self.pending_bytes
+    }
+}
+
+/// A hard limit that has been reached for the current window.
+pub struct QuotaExceeded {
+    pub scope: String,
+    pub period: QuotaPeriod,
+    pub retry_after_secs: u64,
+}
+
+/// Outcome of a quota check that lets the request through.
+#[derive(Default)]
+pub struct QuotaStatus {
+    /// Set when a soft limit has been crossed for any applicable quota.
+    pub soft_limited: bool,
+}
+
+/// Enforces per-org daily and monthly byte budgets stored in
+/// `org_ingest_quotas`. Usage is counted locally and periodically merged into
+/// `org_ingest_usage` so budgets survive restarts and are shared by replicas.
+pub struct QuotaEnforcer {
+    db: Arc<Database>,
+    limits: Cache<String, Arc<[QuotaLimit]>>,
+    usage: Mutex<HashMap<UsageKey, UsageCounter>>,
+}
+
+impl QuotaEnforcer {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            limits: Cache::builder()
+                .time_to_live(Duration::from_secs(60))
+                .max_capacity(1_000)
+                .build(),
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Fails open: if the quota table cannot be read the request is allowed.
+    pub async fn check(&self, org_id: &str, signal: &str) -> Result<QuotaStatus, QuotaExceeded> {
+        let limits = self.limits_for(org_id).await;
+        if limits.is_empty() {
+            return Ok(QuotaStatus::default());
+        }
+        self.evaluate(org_id, signal, &limits, unix_millis() as i64)
+    }
+
+    /// Adds `bytes` to every window the request counts against. Only windows
+    /// a limit applies to are tracked, as [`QuotaEnforcer::check`] opens them,
+    /// so orgs without quotas cost nothing here or in [`QuotaEnforcer::sync`].
+    pub fn record(&self, org_id: &str, signal: &str, bytes: u64) {
+        self.record_at(org_id, signal, bytes, unix_millis() as i64);
+    }
+
+    fn record_at(&self, org_id: &str, signal: &str, bytes: u64, now_ms: i64) {
+        let mut usage = self.usage.lock().unwrap();
+        for scope in [ALL_SIGNALS, signal] {
+            for period in [QuotaPeriod::Day, QuotaPeriod::Month] {
+                let key = UsageKey {
+                    org_id: org_id.to_string(),
+                    scope: scope.to_string(),
+                    period,
+                    window_start: period.window(now_ms).0,
+                };
+                if let Some(counter) = usage.get_mut(&key) {
+                    counter.pending_bytes += bytes;
+                }
+            }
+        }
+    }
+
+    async fn limits_for(&self, org_id: &str) -> Arc<[QuotaLimit]> {
+        if let Some(cached) = self.limits.get(org_id).await {
+            return cached;
+        }
+
+        let limits: Arc<[QuotaLimit]> = match self.load_limits(org_id).await {
+            Ok(limits) => limits.into(),
+            Err(error) => {
+                warn!(org_id, error = %error, "Failed to load ingest quotas");
+                counter!("ingest_quota_errors_total", "op" => "load").increment(1);
+                Arc::from(Vec::new())
+            }
+        };
+        // Failures are cached too so a missing table is not queried per request.
+        self.limits.insert(org_id.to_string(), limits.clone()).await;
+        limits
+    }
+
+    async fn load_limits(&self, org_id: &str) -> Result<Vec<QuotaLimit>, String> {
+        let conn = self.db.connect().map_err(|error| error.to_string())?;
+        let mut rows = conn
+            .query(
+                "SELECT signal, period, limit_bytes, soft_limit_bytes FROM org_ingest_quotas WHERE org_id = ?",
+                params![org_id],
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+
+        let mut limits = Vec::new();
+        while let Some(row) = rows.next().await.map_err(|error| error.to_string())? {
+            let scope: String = row.get(0).map_err(|error| error.to_string())?;
+            let period: String = row.get(1).map_err(|error| error.to_string())?;
+            let limit_bytes: i64 = row.get(2).map_err(|error| error.to_string())?;
+            let soft_limit_bytes: Option<i64> = row.get(3).map_err(|error| error.to_string())?;
+
+            let Some(period) = QuotaPeriod::parse(&period) else {
+                warn!(org_id, period = %period, "Ignoring quota with unknown period");
+                continue;
+            };
+
+            limits.push(QuotaLimit {
+                scope,
+                period,
+                limit_bytes: limit_bytes.max(0) as u64,
+                soft_limit_bytes: soft_limit_bytes.map(|value| value.max(0) as u64),
+            });
+        }
+
+        Ok(limits)
+    }
+
+    fn evaluate(
+        &self,
+        org_id: &str,
+        signal: &str,
+        limits: &[QuotaLimit],
+        now_ms: i64,
+    ) -> Result<QuotaStatus, QuotaExceeded> {
+        let mut status = QuotaStatus::default();
+        let mut exceeded: Option<QuotaExceeded> = None;
+        let mut usage = self.usage.lock().unwrap();
+
+        for limit in limits {
+            if limit.scope != ALL_SIGNALS && limit.scope != signal {
+                continue;
+            }
+
+            let (window_start, window_end) = limit.period.window(now_ms);
+            let counter = usage
+                .entry(UsageKey {
+                    org_id: org_id.to_string(),
+                    scope: limit.scope.clone(),
+                    period: limit.period,
+                    window_start,
+                })
+                .or_default();
+            let used = counter.total();
+
+            if used >= limit.limit_bytes {
+                let retry_after_secs = ((window_end - now_ms).max(0) as u64).div_ceil(1_000);
+                // When several budgets are exhausted, report the one that resets last.
+                if exceeded
+                    .as_ref()
+                    .is_none_or(|current| retry_after_secs > current.retry_after_secs)
+                {
+                    exceeded = Some(QuotaExceeded {
+                        scope: limit.scope.clone(),
+                        period: limit.period,
+                        retry_after_secs,
+                    });
+                }
+                continue;
+            }
+
+            if let Some(soft_limit_bytes) = limit.soft_limit_bytes {
+                if used >= soft_limit_bytes {
+                    status.soft_limited = true;
+                    if !counter.soft_limit_warned {
+                        counter.soft_limit_warned = true;
+                        warn!(
+                            org_id,
+                            scope = %limit.scope,
+                            period = limit.period.as_str(),
+                            used_bytes = used,
+                            soft_limit_bytes,
+                            limit_bytes = limit.limit_bytes,
+                            "Ingest quota soft limit reached"
+                        );
+                        counter!(
+                            "ingest_quota_soft_limit_total",
+                            "scope" => limit.scope.clone(),
+                            "period" => limit.period.as_str()
+                        )
+                        .increment(1);
+                    }
+                }
+            }
+        }
+
+        match exceeded {
+            Some(exceeded) => Err(exceeded),
+            None => Ok(status),
+        }
+    }
+
+    /// Writes pending usage back to the database, then reloads the shared
+    /// totals for the current windows of the orgs tracked here.
+    pub async fn sync(&self) -> Result<(), String> {
+        let now_ms = unix_millis() as i64;
+        let day_start = QuotaPeriod::Day.window(now_ms).0;
+        let month_start = QuotaPeriod::Month.window(now_ms).0;
+
+        let (deltas, org_ids) = {
+            let mut usage = self.usage.lock().unwrap();
+            usage.retain(|key, counter| {
+                let current = match key.period {
+                    QuotaPeriod::Day => day_start,
+                    QuotaPeriod::Month => month_start,
+                };
+                key.window_start >= current || counter.pending_bytes > 0
+            });
+            let deltas: Vec<(UsageKey, u64)> = usage
+                .iter_mut()
+                .filter(|(_, counter)| counter.pending_bytes > 0)
+                .map(|(key, counter)| {
+                    let delta = std::mem::take(&mut counter.pending_bytes);
+                    counter.synced_bytes += delta;
+                    (key.clone(), delta)
+                })
+                .collect();
+            let mut org_ids: Vec<String> = usage.keys().map(|key| key.org_id.clone()).collect();
+            org_ids.sort_unstable();
+            org_ids.dedup();
+            (deltas, org_ids)
+        };
+        if org_ids.is_empty() {
+            gauge!("ingest_quota_tracked_windows").set(0.0);
+            return Ok(());
+        }
+
+        let conn = self.db.connect().map_err(|error| error.to_string())?;
+        let mut first_error = None;
+        for (key, delta) in deltas {
+            let result = conn
+                .execute(
+                    "INSERT INTO org_ingest_usage (org_id, signal, period, window_start, bytes, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?)
+                     ON CONFLICT (org_id, signal, period, window_start)
+                     DO UPDATE SET bytes = bytes + excluded.bytes, updated_at = excluded.updated_at",
+                    params![
+                        key.org_id.clone(),
+                        key.scope.clone(),
+                        key.period.as_str(),
+                        key.window_start,
+                        delta as i64,
+                        now_ms
+                    ],
+                )
+                .await;
+
+            if let Err(error) = result {
+                // Hand the delta back so the next round retries it.
+                let mut usage = self.usage.lock().unwrap();
+                let counter = usage.entry(key).or_default();
+                counter.synced_bytes = counter.synced_bytes.saturating_sub(delta);
+                counter.pending_bytes += delta;
+                first_error.get_or_insert(error.to_string());
+            }
+        }
+        if let Some(error) = first_error {
+            return Err(error);
+        }
+
+        let placeholders = vec!["?"; org_ids.len()].join(", ");
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT org_id, signal, period, window_start, bytes FROM org_ingest_usage
+                     WHERE ((period = 'day' AND window_start = ?) OR (period = 'month' AND window_start = ?))
+                     AND org_id IN ({placeholders})"
+                ),
+                params_from_iter(
+                    [libsql::Value::from(day_start), libsql::Value::from(month_start)]
+                        .into_iter()
+                        .chain(org_ids.into_iter().map(libsql::Value::from)),
+                ),
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+
+        let mut totals = Vec::new();
+        while let Some(row) = rows.next().await.map_err(|error| error.to_string())? {
+            let org_id: String = row.get(0).map_err(|error| error.to_string())?;
+            let scope: String = row.get(1).map_err(|error| error.to_string())?;
+            let period: String = row.get(2).map_err(|error| error.to_string())?;
+            let window_start: i64 = row.get(3).map_err(|error| error.to_string())?;
+            let bytes: i64 = row.get(4).map_err(|error| error.to_string())?;
+            let Some(period) = QuotaPeriod::parse(&period) else {
+                continue;
+            };
+            totals.push((
+                UsageKey {
+                    org_id,
+                    scope,
+                    period,
+                    window_start,
+                },
+                bytes.max(0) as u64,
+            ));
+        }
+
+        let mut usage = self.usage.lock().unwrap();
+        for (key, bytes) in totals {
+            usage.entry(key).or_default().synced_bytes = bytes;
+        }
+        gauge!("ingest_quota_tracked_windows").set(usage.len() as f64);
+
+        Ok(())
+    }
+}
+
+pub fn spawn_sync(enforcer: Arc<QuotaEnforcer>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            if let Err(error) = enforcer.sync().await {
+                error!(error = %error, "Failed to sync ingest quota usage");
+                counter!("ingest_quota_errors_total", "op" => "sync").increment(1);
+            }
+        }
+    });
+}
+
+/// Converts days since the Unix epoch to a proleptic Gregorian `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Inverse of [`civil_from_days`].
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2026-02-14T12:00:00Z
+    const NOW_MS: i64 = 1_771_070_400_000;
+
+    #[test]
+    fn windows_follow_utc_calendar_boundaries() {
+        assert_eq!(
+            QuotaPeriod::Day.window(NOW_MS),
+            (1_771_027_200_000, 1_771_113_600_000)
+        );
+        // 2026-02-01 .. 2026-03-01
+        assert_eq!(
+            QuotaPeriod::Month.window(NOW_MS),
+            (1_769_904_000_000, 1_772_323_200_000)
+        );
+        // 2025-12-31 rolls over into the next year.
+        let (_, end) = QuotaPeriod::Month.window(1_767_139_200_000);
+        assert_eq!(end, 1_767_225_600_000);
+    }
+
+    #[tokio::test]
+    async fn hard_limits_reject_and_soft_limits_warn() {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let enforcer = QuotaEnforcer::new(Arc::new(db));
+        let limits = [
+            QuotaLimit {
+                scope: ALL_SIGNALS.to_string(),
+                period: QuotaPeriod::Month,
+                limit_bytes: 1_000,
+                soft_limit_bytes: Some(500),
+            },
+            QuotaLimit {
+                scope: "logs".to_string(),
+                period: QuotaPeriod::Day,
+                limit_bytes: 100,
+                soft_limit_bytes: None,
+            },
+        ];
+
+        let add = |scope: &str, period: QuotaPeriod, bytes: u64| {
+            let key = UsageKey {
+                org_id: "org_a".to_string(),
+                scope: scope.to_string(),
+                period,
+                window_start: period.window(NOW_MS).0,
+            };
+            enforcer
+                .usage
+                .lock()
+                .unwrap()
+                .entry(key)
+                .or_default()
+                .pending_bytes += bytes;
+        };
+
+        add(ALL_SIGNALS, QuotaPeriod::Month, 600);
+        let status = enforcer
+            .evaluate("org_a", "traces", &limits, NOW_MS)
+            .ok()
+            .unwrap();
+        assert!(status.soft_limited);
+
+        add("logs", QuotaPeriod::Day, 100);
+        let exceeded = enforcer
+            .evaluate("org_a", "logs", &limits, NOW_MS)
+            .err()
+            .unwrap();
+        assert_eq!(exceeded.period, QuotaPeriod::Day);
+        assert_eq!(exceeded.retry_after_secs, 12 * 3_600);
+        assert!(enforcer
+            .evaluate("org_a", "traces", &limits, NOW_MS)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_windows_with_a_limit_are_recorded() {
+        let db = libsql::Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap();
+        let enforcer = QuotaEnforcer::new(Arc::new(db));
+        let limits = [QuotaLimit {
+            scope: "logs".to_string(),
+            period: QuotaPeriod::Day,
+            limit_bytes: 1_000,
+            soft_limit_bytes: None,
+        }];
+
+        enforcer.record_at("org_free", "logs", 100, NOW_MS);
+        assert!(enforcer.usage.lock().unwrap().is_empty());
+
+        assert!(enforcer.evaluate("org_a", "logs", &limits, NOW_MS).is_ok());
+        enforcer.record_at("org_a", "logs", 100, NOW_MS);
+        enforcer.record_at("org_a", "traces", 100, NOW_MS);
+        let usage = enforcer.usage.lock().unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage.values().next().unwrap().pending_bytes, 100);
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use moka::future::Cache;
+
+/// Sustained rates for one bucket pair; `0` disables that dimension.
+#[derive(Clone, Copy, Default)]
+pub struct RateLimit {
+    pub requests_per_sec: u64,
+    pub bytes_per_sec: u64,
+}
+
+impl RateLimit {
+    fn is_unlimited(self) -> bool {
+        self.requests_per_sec == 0 && self.bytes_per_sec == 0
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RateLimits {
+    pub per_key: RateLimit,
+    pub per_org: RateLimit,
+    /// How many seconds of traffic a bucket can absorb in one burst.
+    pub burst: Duration,
+}
+
+impl RateLimits {
+    pub fn is_unlimited(&self) -> bool {
+        self.per_key.is_unlimited() && self.per_org.is_unlimited()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThrottleScope {
+    Key,
+    Org,
+}
+
+impl ThrottleScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Key => "key",
+            Self::Org => "org",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ThrottleLimit {
+    Requests,
+    Bytes,
+}
+
+impl ThrottleLimit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Requests => "requests",
+            Self::Bytes => "bytes",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Throttled {
+    pub scope: ThrottleScope,
+    pub limit: ThrottleLimit,
+    pub retry_after: Duration,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn full(capacity: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, capacity: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated_at = now;
+    }
+
+    /// Time until `amount` can be taken. Requests larger than the bucket only
+    /// need a full bucket and then leave it in debt, so oversized payloads
+    /// are slowed down rather than rejected forever.
+    fn wait_for(&self, rate: f64, capacity: f64, amount: f64) -> Duration {
+        let needed = amount.min(capacity) - self.tokens;
+        if needed <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(needed / rate)
+        }
+    }
+}
+
+struct Buckets {
+    requests: TokenBucket,
+    bytes: TokenBucket,
+}
+
+/// Requests/sec and bytes/sec token buckets keyed by ingest key and by org.
+pub struct RateLimiter {
+    limits: RateLimits,
+    keys: Cache<String, Arc<Mutex<Buckets>>>,
+    orgs: Cache<String, Arc<Mutex<Buckets>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        let cache = || {
+            Cache::builder()
+                .time_to_idle(Duration::from_secs(600))
+                .max_capacity(100_000)
+                .build()
+        };
+
+        Self {
+            limits,
+            keys: cache(),
+            orgs: cache(),
+        }
+    }
+
+    /// Takes `amount` from the `kind` bucket of both the key and the org, or
+    /// nothing at all when either would go short. Requests are charged
+    /// before the body is read and bytes once its decoded size is known.
+    pub async fn acquire(
+        &self,
+        key_id: &str,
+        org_id: &str,
+        kind: ThrottleLimit,
+        amount: u64,
+    ) -> Result<(), Throttled> {
+        let now = Instant::now();
+        let key_buckets = self
+            .buckets(&self.keys, key_id, self.limits.per_key, now)
+            .await;
+        let org_buckets = self
+            .buckets(&self.orgs, org_id, self.limits.per_org, now)
+            .await;
+
+        // Always lock key before org so concurrent callers cannot deadlock.
+        let mut key_buckets = key_buckets.lock().unwrap();
+        let mut org_buckets = org_buckets.lock().unwrap();
+
+        let mut checks = [
+            (ThrottleScope::Key, self.limits.per_key, &mut *key_buckets),
+            (ThrottleScope::Org, self.limits.per_org, &mut *org_buckets),
+        ]
+        .map(|(scope, limit, buckets)| match kind {
+            ThrottleLimit::Requests => (scope, limit.requests_per_sec, &mut buckets.requests),
+            ThrottleLimit::Bytes => (scope, limit.bytes_per_sec, &mut buckets.bytes),
+        });
+        let amount = amount as f64;
+
+        let mut throttled: Option<Throttled> = None;
+        for (scope, rate, bucket) in checks.iter_mut() {
+            if *rate == 0 {
+                continue;
+            }
+            let rate = *rate as f64;
+            let capacity = self.capacity(rate);
+            bucket.refill(rate, capacity, now);
+
+            let wait = bucket.wait_for(rate, capacity, amount);
+            if !wait.is_zero()
+                && throttled
+                    .as_ref()
+                    .is_none_or(|current| wait > current.retry_after)
+            {
+                throttled = Some(Throttled {
+                    scope: *scope,
+                    limit: kind,
+                    retry_after: wait,
+                });
+            }
+        }
+
+        if let Some(throttled) = throttled {
+            return Err(throttled);
+        }
+
+        for (_, rate, bucket) in checks.iter_mut() {
+            if *rate > 0 {
+                bucket.tokens -= amount;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn capacity(&self, rate: f64) -> f64 {
+        (rate * self.limits.burst.as_secs_f64()).max(1.0)
+    }
+
+    async fn buckets(
+        &self,
+        cache: &Cache<String, Arc<Mutex<Buckets>>>,
+        id: &str,
+        limit: RateLimit,
+        now: Instant,
+    ) -> Arc<Mutex<Buckets>> {
+        cache
+            .get_with(id.to_string(), async {
+                Arc::new(Mutex::new(Buckets {
+                    requests: TokenBucket::full(self.capacity(limit.requests_per_sec as f64), now),
+                    bytes: TokenBucket::full(self.capacity(limit.bytes_per_sec as f64), now),
+                }))
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(per_key: RateLimit, per_org: RateLimit) -> RateLimiter {
+        RateLimiter::new(RateLimits {
+            per_key,
+            per_org,
+            burst: Duration::from_secs(1),
+        })
+    }
+
+    #[tokio::test]
+    async fn key_requests_are_throttled_once_the_burst_is_spent() {
+        let limiter = limiter(
+            RateLimit {
+                requests_per_sec: 2,
+                bytes_per_sec: 0,
+            },
+            RateLimit::default(),
+        );
+
+        assert!(limiter
+            .acquire("key_a", "org_a", ThrottleLimit::Requests, 1)
+            .await
+            .is_ok());
+        assert!(limiter
+            .acquire("key_a", "org_a", ThrottleLimit::Requests, 1)
+            .await
+            .is_ok());
+        let throttled = limiter
+            .acquire("key_a", "org_a", ThrottleLimit::Requests, 1)
+            .await
+            .unwrap_err();
+        assert_eq!(throttled.scope, ThrottleScope::Key);
+        assert_eq!(throttled.limit, ThrottleLimit::Requests);
+        assert!(throttled.retry_after <= Duration::from_millis(500));
+
+        // Other keys have their own bucket.
+        assert!(limiter
+            .acquire("key_b", "org_a", ThrottleLimit::Requests, 1)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn org_bytes_are_shared_and_rejections_take_no_tokens() {
+        let limiter = limiter(
+            RateLimit {
+                requests_per_sec: 100,
+                bytes_per_sec: 0,
+            },
+            RateLimit {
+                requests_per_sec: 0,
+                bytes_per_sec: 1_000,
+            },
+        );
+
+        assert!(limiter
+            .acquire("key_a", "org_a", ThrottleLimit::Bytes, 600)
+            .await
+            .is_ok());
+        let throttled = limiter
+            .acquire("key_b", "org_a", ThrottleLimit::Bytes, 600)
+            .await
+            .unwrap_err();
+        assert_eq!(throttled.scope, ThrottleScope::Org);
+        assert_eq!(throttled.limit, ThrottleLimit::Bytes);
+
+        // The rejected call must not have drained the org bucket.
+        assert!(limiter
+            .acquire("key_b", "org_a", ThrottleLimit::Bytes, 400)
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use metrics::gauge;
+use moka::future::Cache;
+use serde_json::json;
+use tokio::net::TcpStream;
+use tracing::warn;
+
+use crate::routing::Route;
+use crate::AppState;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Caches collector reachability so that frequent load balancer checks do
+/// not open a connection per route every time. The database is probed on
+/// every call, since its query is cheap and key lookups depend on it.
+pub struct ReadinessProbe {
+    collectors: Cache<String, Result<(), String>>,
+}
+
+impl ReadinessProbe {
+    pub fn new(collector_cache_ttl: Duration) -> Self {
+        Self {
+            collectors: Cache::builder()
+                .time_to_live(collector_cache_ttl)
+                .max_capacity(1_000)
+                .build(),
+        }
+    }
+
+    async fn collector(&self, route: &Route) -> Result<(), String> {
+        self.collectors
+            .get_with(route.name.clone(), probe_collector(&route.endpoint))
+            .await
+    }
+}
+
+/// Connects to the collector's host and port without sending anything; a
+/// refused or timed-out connection means forwards would fail too.
+async fn probe_collector(endpoint: &str) -> Result<(), String> {
+    let url = url::Url::parse(endpoint).map_err(|error| format!("invalid endpoint: {error}"))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| "endpoint has no host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| "endpoint has no port".to_string())?;
+
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((host.as_str(), port))).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(error)) => Err(error.to_string()),
+        Err(_) => Err(format!(
+            "connect timed out after {}s",
+            PROBE_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+async fn probe_database(state: &AppState) -> Result<(), String> {
+    let query = async {
+        let conn = state
+            .resolver
+            .db
+            .connect()
+            .map_err(|error| error.to_string())?;
+        let mut rows = conn
+            .query("SELECT 1 FROM org_ingest_keys LIMIT 1", ())
+            .await
+            .map_err(|error| error.to_string())?;
+        rows.next().await.map_err(|error| error.to_string())?;
+        Ok(())
+    };
+    tokio::time::timeout(PROBE_TIMEOUT, query)
+        .await
+        .unwrap_or_else(|_| {
+            Err(format!(
+                "query timed out after {}s",
+                PROBE_TIMEOUT.as_secs()
+            ))
+        })
+}
+
+fn component(result: &Result<(), String>, started: Instant) -> serde_json::Value {
+    match result {
+        Ok(()) => json!({
+            "status": "ok",
+            "latency_ms": started.elapsed().as_millis() as u64,
+        }),
+        Err(error) => json!({ "status": "error", "error": error }),
+    }
+}
+
+/// Process is up and serving HTTP; says nothing about dependencies.
+pub async fn livez() -> &'static str {
+    "OK"
+}
+
+/// 200 only when the key database answers and every route's collector
+/// accepts connections; otherwise 503 with the failing components. With a
+/// spool configured an unreachable collector only degrades readiness, since
+/// payloads are buffered until it returns. 503 once shutdown has begun, so
+/// load balancers stop routing to an instance that is draining.
+pub async fn readyz(State(state): State<Arc<AppState>>) -> Response {
+    let started = Instant::now();
+    let database = probe_database(&state).await;
+    let database_ok = database.is_ok();
+    let database = component(&database, started);
+
+    let mut collectors_ok = true;
+    let mut collectors = serde_json::Map::new();
+    for route in state.routes.iter() {
+        let started = Instant::now();
+        let result = state.readiness.collector(route).await;
+        collectors_ok &= result.is_ok();
+        if let Err(error) = &result {
+            warn!(route = %route.name, endpoint = %route.endpoint, error = %error, "Collector not reachable");
+        }
+        let mut status = component(&result, started);
+        status["endpoint"] = route.endpoint.clone().into();
+        status["circuit_breaker"] = route.breaker.state().into();
+        collectors.insert(route.name.clone(), status);
+    }
+
+    let readiness = Readiness::from_checks(
+        database_ok,
+        collectors_ok,
+        state.spool.is_some(),
+        *state.shutdown.borrow(),
+    );
+    let status = readiness.status_code();
+    gauge!("ingest_ready").set(if status.is_success() { 1.0 } else { 0.0 });
+    let body = json!({
+        "status": readiness.as_str(),
+        "checks": {
+            "database": database,
+            "collectors": collectors,
+        },
+    });
+    (status, Json(body)).into_response()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Readiness {
+    Ready,
+    /// Serving, but buffering to the spool because a collector is down.
+    Degraded,
+    NotReady,
+    Draining,
+}
+
+impl Readiness {
+    fn from_checks(database_ok: bool, collectors_ok: bool, spooled: bool, draining: bool) -> Self {
+        if draining {
+            Self::Draining
+        } else if !database_ok {
+            Self::NotReady
+        } else if collectors_ok {
+            Self::Ready
+        } else if spooled {
+            Self::Degraded
+        } else {
+            Self::NotReady
+        }
+    }
+
+    fn status_code(self) -> StatusCode {
+        match self {
+            Self::Ready | Self::Degraded => StatusCode::OK,
+            Self::NotReady | Self::Draining => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Ready => "ready",
+            Self::Degraded => "degraded",
+            Self::NotReady => "not_ready",
+            Self::Draining => "draining",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn collector_probe_reports_refused_and_accepted_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(probe_collector(&format!("http://127.0.0.1:{port}"))
+            .await
+            .is_ok());
+
+        drop(listener);
+        assert!(probe_collector(&format!("http://127.0.0.1:{port}"))
+            .await
+            .is_err());
+        assert!(probe_collector("not a url").await.is_err());
+    }
+
+    #[test]
+    fn spool_degrades_collector_outages_and_draining_is_not_ready() {
+        assert_eq!(
+            Readiness::from_checks(true, false, false, false),
+            Readiness::NotReady
+        );
+        assert_eq!(
+            Readiness::from_checks(true, false, true, false),
+            Readiness::Degraded
+        );
+        assert_eq!(Readiness::Degraded.status_code(), StatusCode::OK);
+        assert_eq!(
+            Readiness::from_checks(false, true, true, false),
+            Readiness::NotReady
+        );
+        assert_eq!(
+            Readiness::from_checks(true, true, true, true).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
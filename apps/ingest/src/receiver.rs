@@ -0,0 +1,111 @@
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
+use serde_json::json;
+
+use crate::{jaeger, loki, remote_write, splunk, zipkin, ApiError};
+
+/// `service.name` for telemetry that does not say which service sent it.
+pub const UNKNOWN_SERVICE: &str = "unknown_service";
+
+/// The wire format a request arrived in. Everything that is not OTLP is
+/// translated into an OTLP protobuf request right after decompression, so
+/// validation, sampling, redaction, enrichment and forwarding are shared.
+#[derive(Clone)]
+pub enum Receiver {
+    Otlp,
+    Zipkin,
+    Jaeger,
+    RemoteWrite,
+    Loki,
+    SplunkEvent,
+    /// Carries the event metadata given in the query string.
+    SplunkRaw(splunk::Metadata),
+}
+
+impl Receiver {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Otlp => "otlp",
+            Self::Zipkin => "zipkin",
+            Self::Jaeger => "jaeger",
+            Self::RemoteWrite => "remote_write",
+            Self::Loki => "loki",
+            Self::SplunkEvent => "splunk_event",
+            Self::SplunkRaw(_) => "splunk_raw",
+        }
+    }
+
+    /// The encoding a body uses when the request carries no
+    /// `Content-Encoding`. Loki's protobuf pushes are always snappy.
+    pub fn implicit_content_encoding(&self, content_type: &str) -> Option<&'static str> {
+        match self {
+            Self::Loki if content_type.contains("protobuf") => Some("snappy"),
+            _ => None,
+        }
+    }
+
+    /// Returns the payload as OTLP protobuf, plus headers for the accepted
+    /// response. OTLP payloads pass through untouched, in whichever encoding
+    /// the client used.
+    pub fn translate(
+        &self,
+        content_type: &str,
+        payload: Vec<u8>,
+    ) -> Result<(Vec<u8>, HeaderMap), ApiError> {
+        match self {
+            Self::Otlp => Ok((payload, HeaderMap::new())),
+            Self::Zipkin => Ok((zipkin::translate(content_type, &payload)?, HeaderMap::new())),
+            Self::Jaeger => Ok((jaeger::translate(content_type, &payload)?, HeaderMap::new())),
+            Self::RemoteWrite => remote_write::translate(content_type, &payload),
+            Self::Loki => Ok((loki::translate(content_type, &payload)?, HeaderMap::new())),
+            Self::SplunkEvent => Ok((splunk::translate_events(&payload)?, HeaderMap::new())),
+            Self::SplunkRaw(metadata) => {
+                Ok((splunk::translate_raw(metadata, &payload)?, HeaderMap::new()))
+            }
+        }
+    }
+
+    /// What a client of this receiver expects once its data is accepted, in
+    /// place of the collector's OTLP response. `None` keeps the latter.
+    pub fn accepted_response(&self, headers: HeaderMap) -> Option<Response> {
+        let status = match self {
+            Self::Otlp => return None,
+            Self::SplunkEvent | Self::SplunkRaw(_) => {
+                let body = Json(json!({ "text": "Success", "code": 0 }));
+                return Some((headers, body).into_response());
+            }
+            Self::Zipkin | Self::Jaeger => StatusCode::ACCEPTED,
+            Self::RemoteWrite | Self::Loki => StatusCode::NO_CONTENT,
+        };
+        Some((status, headers).into_response())
+    }
+}
+
+pub fn string_attribute(key: &str, value: impl Into<String>) -> KeyValue {
+    attribute(key, any_value::Value::StringValue(value.into()))
+}
+
+pub fn int_attribute(key: &str, value: i64) -> KeyValue {
+    attribute(key, any_value::Value::IntValue(value))
+}
+
+pub fn bool_attribute(key: &str, value: bool) -> KeyValue {
+    attribute(key, any_value::Value::BoolValue(value))
+}
+
+pub fn double_attribute(key: &str, value: f64) -> KeyValue {
+    attribute(key, any_value::Value::DoubleValue(value))
+}
+
+pub fn bytes_attribute(key: &str, value: Vec<u8>) -> KeyValue {
+    attribute(key, any_value::Value::BytesValue(value))
+}
+
+fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue { value: Some(value) }),
+    }
+}
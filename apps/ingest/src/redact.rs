@@ -0,0 +1,377 @@
+use std::path::Path;
+
+use hmac::{Hmac, Mac};
+use metrics::counter;
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
+use prost::Message;
+use regex::{Captures, Regex, RegexSet};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::Signal;
+
+const DEFAULT_MASK_REPLACEMENT: &str = "[REDACTED]";
+
+/// Redaction rules as read from `INGEST_REDACTION_RULES_FILE`.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct RedactionRules {
+    /// Attribute keys (`*` and `?` globs) removed outright.
+    #[serde(default)]
+    drop_keys: Vec<String>,
+    /// Attribute keys whose values are replaced with an org-scoped HMAC, so
+    /// they can still be grouped on without being readable.
+    #[serde(default)]
+    hash_keys: Vec<String>,
+    /// Built-in pattern names or `{ "name", "pattern" }` regexes masked in
+    /// every remaining string value.
+    #[serde(default)]
+    mask: Vec<MaskSpec>,
+    mask_replacement: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaskSpec {
+    Builtin(String),
+    Custom { name: String, pattern: String },
+}
+
+struct MaskRule {
+    regex: Regex,
+    /// Only replace matches that pass a Luhn check, so long numeric IDs and
+    /// timestamps are not mistaken for card numbers.
+    luhn: bool,
+}
+
+impl MaskRule {
+    fn builtin(name: &str) -> Result<Self, String> {
+        let (pattern, luhn) = match name {
+            "email" => (r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}", false),
+            "credit_card" => (r"\b(?:\d[ -]?){12,18}\d\b", true),
+            "bearer_token" => (r"(?i)\bbearer\s+[a-z0-9\-._~+/]+=*", false),
+            other => return Err(format!("unknown built-in mask pattern '{other}'")),
+        };
+        Ok(Self {
+            regex: Regex::new(pattern).expect("built-in mask patterns are valid"),
+            luhn,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Counts {
+    dropped: u64,
+    hashed: u64,
+    masked: u64,
+}
+
+/// Per-request redaction state: the org's hashing key and what was changed.
+struct Context {
+    org_key: Option<[u8; 32]>,
+    counts: Counts,
+}
+
+pub struct Redactor {
+    drop: RegexSet,
+    hash: RegexSet,
+    masks: Vec<MaskRule>,
+    replacement: String,
+    hash_key: Option<String>,
+}
+
+impl Redactor {
+    pub fn load(path: &Path, hash_key: Option<&str>) -> Result<Self, String> {
+        let raw = std::fs::read(path)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+        let rules: RedactionRules = serde_json::from_slice(&raw)
+            .map_err(|error| format!("invalid redaction rules in {}: {error}", path.display()))?;
+        Self::new(rules, hash_key)
+    }
+
+    pub fn new(rules: RedactionRules, hash_key: Option<&str>) -> Result<Self, String> {
+        if !rules.hash_keys.is_empty() && hash_key.is_none() {
+            return Err("INGEST_REDACTION_HASH_KEY is required when hash_keys is set".to_string());
+        }
+
+        let masks = rules
+            .mask
+            .into_iter()
+            .map(|spec| match spec {
+                MaskSpec::Builtin(name) => MaskRule::builtin(&name),
+                MaskSpec::Custom { name, pattern } => Regex::new(&pattern)
+                    .map(|regex| MaskRule { regex, luhn: false })
+                    .map_err(|error| format!("invalid mask pattern '{name}': {error}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            drop: glob_set(&rules.drop_keys)?,
+            hash: glob_set(&rules.hash_keys)?,
+            masks,
+            replacement: rules
+                .mask_replacement
+                .unwrap_or_else(|| DEFAULT_MASK_REPLACEMENT.to_string()),
+            hash_key: hash_key.map(str::to_string),
+        })
+    }
+
+    /// Redacts span and span event attributes.
+    pub fn redact_traces(&self, request: &mut ExportTraceServiceRequest, org_id: &str) {
+        let mut context = self.context(org_id);
+        for span in request
+            .resource_spans
+            .iter_mut()
+            .flat_map(|resource_spans| &mut resource_spans.scope_spans)
+            .flat_map(|scope_spans| &mut scope_spans.spans)
+        {
+            self.redact_attributes(&mut span.attributes, &mut context);
+            for event in &mut span.events {
+                self.redact_attributes(&mut event.attributes, &mut context);
+            }
+        }
+        record(Signal::Traces, &context.counts);
+    }
+
+    /// Redacts log record attributes and bodies.
+    pub fn redact_logs(&self, request: &mut ExportLogsServiceRequest, org_id: &str) {
+        let mut context = self.context(org_id);
+        for record in request
+            .resource_logs
+            .iter_mut()
+            .flat_map(|resource_logs| &mut resource_logs.scope_logs)
+            .flat_map(|scope_logs| &mut scope_logs.log_records)
+        {
+            self.redact_attributes(&mut record.attributes, &mut context);
+            if let Some(body) = &mut record.body {
+                self.redact_value(body, &mut context);
+            }
+        }
+        record(Signal::Logs, &context.counts);
+    }
+
+    fn context(&self, org_id: &str) -> Context {
+        // Each org hashes with its own key, so equal values cannot be
+        // correlated across tenants.
+        let org_key = self.hash_key.as_ref().map(|hash_key| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(hash_key.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(org_id.as_bytes());
+            mac.finalize().into_bytes().into()
+        });
+        Context {
+            org_key,
+            counts: Counts::default(),
+        }
+    }
+
+    fn redact_attributes(&self, attributes: &mut Vec<KeyValue>, context: &mut Context) {
+        attributes.retain_mut(|attribute| {
+            if self.drop.is_match(&attribute.key) {
+                context.counts.dropped += 1;
+                return false;
+            }
+            let Some(value) = &mut attribute.value else {
+                return true;
+            };
+            if self.hash.is_match(&attribute.key) {
+                if let Some(org_key) = &context.org_key {
+                    hash_value(value, org_key);
+                    context.counts.hashed += 1;
+                }
+                return true;
+            }
+            self.redact_value(value, context);
+            true
+        });
+    }
+
+    fn redact_value(&self, value: &mut AnyValue, context: &mut Context) {
+        match &mut value.value {
+            Some(any_value::Value::StringValue(text)) => {
+                context.counts.masked += self.mask(text);
+            }
+            Some(any_value::Value::ArrayValue(array)) => {
+                for value in &mut array.values {
+                    self.redact_value(value, context);
+                }
+            }
+            Some(any_value::Value::KvlistValue(list)) => {
+                self.redact_attributes(&mut list.values, context);
+            }
+            _ => {}
+        }
+    }
+
+    fn mask(&self, text: &mut String) -> u64 {
+        let mut masked = 0;
+        for rule in &self.masks {
+            if !rule.regex.is_match(text) {
+                continue;
+            }
+            let replaced = rule.regex.replace_all(text, |captures: &Captures| {
+                let matched = &captures[0];
+                if rule.luhn && !passes_luhn(matched) {
+                    return matched.to_string();
+                }
+                masked += 1;
+                self.replacement.clone()
+            });
+            *text = replaced.into_owned();
+        }
+        masked
+    }
+}
+
+fn record(signal: Signal, counts: &Counts) {
+    for (action, count) in [
+        ("drop", counts.dropped),
+        ("hash", counts.hashed),
+        ("mask", counts.masked),
+    ] {
+        if count > 0 {
+            counter!(
+                "ingest_redactions_total",
+                "signal" => signal.path(),
+                "action" => action
+            )
+            .increment(count);
+        }
+    }
+}
+
+/// Compiles attribute-key globs, where `*` matches any run of characters and
+/// `?` exactly one.
+fn glob_set(globs: &[String]) -> Result<RegexSet, String> {
+    let patterns = globs.iter().map(|glob| {
+        let escaped = regex::escape(glob).replace(r"\*", ".*").replace(r"\?", ".");
+        format!("^{escaped}$")
+    });
+    RegexSet::new(patterns).map_err(|error| format!("invalid key glob: {error}"))
+}
+
+fn hash_value(value: &mut AnyValue, org_key: &[u8; 32]) {
+    let mut mac = Hmac::<Sha256>::new_from_slice(org_key).expect("HMAC accepts keys of any length");
+    match &value.value {
+        Some(any_value::Value::StringValue(text)) => mac.update(text.as_bytes()),
+        _ => mac.update(&value.encode_to_vec()),
+    }
+    let digest = mac.finalize().into_bytes();
+    let hex: String = digest[..16]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    value.value = Some(any_value::Value::StringValue(hex));
+}
+
+fn passes_luhn(candidate: &str) -> bool {
+    let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| {
+            if index % 2 == 1 {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+
+    fn string_value(attribute: &KeyValue) -> &str {
+        match attribute
+            .value
+            .as_ref()
+            .and_then(|value| value.value.as_ref())
+        {
+            Some(any_value::Value::StringValue(text)) => text,
+            _ => panic!("not a string attribute"),
+        }
+    }
+
+    fn redactor() -> Redactor {
+        let rules = serde_json::from_value(serde_json::json!({
+            "drop_keys": ["password", "http.request.header.*"],
+            "hash_keys": ["user.id"],
+            "mask": ["email", "credit_card", "bearer_token"],
+        }))
+        .unwrap();
+        Redactor::new(rules, Some("redaction-secret")).unwrap()
+    }
+
+    #[test]
+    fn attributes_are_dropped_hashed_and_masked() {
+        let redactor = redactor();
+        let mut attributes = vec![
+            string_attribute("password", "hunter2"),
+            string_attribute("http.request.header.cookie", "session=abc"),
+            string_attribute("user.id", "user-42"),
+            string_attribute(
+                "message",
+                "mail bob@example.com, card 4111 1111 1111 1111, order 1697500000000",
+            ),
+            string_attribute("auth", "Bearer abc.def-123"),
+        ];
+
+        let mut context = redactor.context("org_a");
+        redactor.redact_attributes(&mut attributes, &mut context);
+
+        let keys: Vec<&str> = attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(keys, ["user.id", "message", "auth"]);
+        assert_eq!(string_value(&attributes[0]).len(), 32);
+        assert_eq!(
+            string_value(&attributes[1]),
+            "mail [REDACTED], card [REDACTED], order 1697500000000"
+        );
+        assert_eq!(string_value(&attributes[2]), "[REDACTED]");
+        assert_eq!(context.counts.dropped, 2);
+        assert_eq!(context.counts.hashed, 1);
+        assert_eq!(context.counts.masked, 3);
+    }
+
+    #[test]
+    fn hashes_are_stable_per_org_and_differ_across_orgs() {
+        let redactor = redactor();
+        let hash_for = |org_id: &str| {
+            let mut attributes = vec![string_attribute("user.id", "user-42")];
+            redactor.redact_attributes(&mut attributes, &mut redactor.context(org_id));
+            string_value(&attributes[0]).to_string()
+        };
+
+        assert_eq!(hash_for("org_a"), hash_for("org_a"));
+        assert_ne!(hash_for("org_a"), hash_for("org_b"));
+    }
+
+    #[test]
+    fn hash_rules_require_a_key() {
+        let rules =
+            serde_json::from_value(serde_json::json!({ "hash_keys": ["user.id"] })).unwrap();
+        assert!(Redactor::new(rules, None).is_err());
+    }
+}
@@ -0,0 +1,940 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use opentelemetry_proto::tonic::collector::metrics::v1::ExportMetricsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{InstrumentationScope, KeyValue};
+use opentelemetry_proto::tonic::metrics::v1::exponential_histogram_data_point::Buckets;
+use opentelemetry_proto::tonic::metrics::v1::{
+    metric, number_data_point, AggregationTemporality, ExponentialHistogram,
+    ExponentialHistogramDataPoint, Gauge, Histogram, HistogramDataPoint, Metric, NumberDataPoint,
+    ResourceMetrics, ScopeMetrics, Sum,
+};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+
+use crate::receiver::{string_attribute, UNKNOWN_SERVICE};
+use crate::ApiError;
+
+/// Prometheus marks a series stale with this NaN; it carries no value.
+const STALE_NAN: u64 = 0x7ff0_0000_0000_0002;
+/// Native histograms with custom bucket boundaries.
+const CUSTOM_BUCKETS_SCHEMA: i32 = -53;
+/// Upper bound on buckets materialised from a native histogram's spans.
+const MAX_BUCKETS: usize = 10_000;
+
+const SAMPLES_WRITTEN: &str = "x-prometheus-remote-write-samples-written";
+const HISTOGRAMS_WRITTEN: &str = "x-prometheus-remote-write-histograms-written";
+const EXEMPLARS_WRITTEN: &str = "x-prometheus-remote-write-exemplars-written";
+
+// MetricType values, shared by remote write v1 and v2.
+const COUNTER: i32 = 1;
+const GAUGE: i32 = 2;
+const HISTOGRAM: i32 = 3;
+
+/// A series from either protocol version, with labels resolved.
+struct Series {
+    name: String,
+    /// Sorted, without `__name__`.
+    labels: Vec<(String, String)>,
+    metric_type: i32,
+    help: String,
+    unit: String,
+    start_ms: i64,
+    samples: Vec<proto::Sample>,
+    histograms: Vec<proto::Histogram>,
+}
+
+/// Translates a snappy-decoded remote write request into OTLP metrics. v2
+/// (`proto=io.prometheus.write.v2.Request`) is told apart by content type;
+/// anything else is v1's `prometheus.WriteRequest`. The returned headers
+/// report what was written, which v2 senders expect.
+pub fn translate(content_type: &str, payload: &[u8]) -> Result<(Vec<u8>, HeaderMap), ApiError> {
+    let v2 = content_type.contains("io.prometheus.write.v2.request");
+    let series = if v2 {
+        proto::v2::Request::decode(payload)
+            .map_err(|error| {
+                ApiError::bad_request(format!("Invalid remote write v2 request: {error}"))
+            })
+            .and_then(from_v2)?
+    } else {
+        proto::WriteRequest::decode(payload)
+            .map(from_v1)
+            .map_err(|error| {
+                ApiError::bad_request(format!("Invalid remote write request: {error}"))
+            })?
+    };
+
+    let mut output = Output::default();
+    let (samples, histograms) = output.add(series)?;
+
+    let mut headers = HeaderMap::new();
+    if v2 {
+        for (name, count) in [
+            (SAMPLES_WRITTEN, samples),
+            (HISTOGRAMS_WRITTEN, histograms),
+            (EXEMPLARS_WRITTEN, 0),
+        ] {
+            headers.insert(HeaderName::from_static(name), HeaderValue::from(count));
+        }
+    }
+    Ok((output.finish().encode_to_vec(), headers))
+}
+
+fn from_v1(request: proto::WriteRequest) -> Vec<Series> {
+    let metadata: HashMap<String, proto::MetricMetadata> = request
+        .metadata
+        .into_iter()
+        .map(|metadata| (metadata.metric_family_name.clone(), metadata))
+        .collect();
+
+    request
+        .timeseries
+        .into_iter()
+        .map(|series| {
+            let (name, labels) = split_name(
+                series
+                    .labels
+                    .into_iter()
+                    .map(|label| (label.name, label.value))
+                    .collect(),
+            );
+            let metadata = metadata
+                .get(&name)
+                .or_else(|| metadata.get(family_name(&name)));
+            Series {
+                metric_type: metadata.map(|metadata| metadata.r#type).unwrap_or_default(),
+                help: metadata
+                    .map(|metadata| metadata.help.clone())
+                    .unwrap_or_default(),
+                unit: metadata
+                    .map(|metadata| metadata.unit.clone())
+                    .unwrap_or_default(),
+                name,
+                labels,
+                start_ms: 0,
+                samples: series.samples,
+                histograms: series.histograms,
+            }
+        })
+        .collect()
+}
+
+fn from_v2(request: proto::v2::Request) -> Result<Vec<Series>, ApiError> {
+    let symbols = request.symbols;
+    let symbol = |reference: u32| {
+        symbols
+            .get(reference as usize)
+            .cloned()
+            .ok_or_else(|| ApiError::bad_request(format!("Unknown symbol reference {reference}")))
+    };
+    // Reference 0 is the empty string, even when the table is empty.
+    let optional_symbol = |reference: u32| match reference {
+        0 => Ok(String::new()),
+        reference => symbol(reference),
+    };
+
+    request
+        .timeseries
+        .into_iter()
+        .map(|series| {
+            if !series.labels_refs.len().is_multiple_of(2) {
+                return Err(ApiError::bad_request("Odd number of label references"));
+            }
+            let labels = series
+                .labels_refs
+                .chunks(2)
+                .map(|pair| Ok((symbol(pair[0])?, symbol(pair[1])?)))
+                .collect::<Result<Vec<_>, ApiError>>()?;
+            let (name, labels) = split_name(labels);
+            let metadata = series.metadata.unwrap_or_default();
+            Ok(Series {
+                name,
+                labels,
+                metric_type: metadata.r#type,
+                help: optional_symbol(metadata.help_ref)?,
+                unit: optional_symbol(metadata.unit_ref)?,
+                start_ms: series.created_timestamp,
+                samples: series.samples,
+                histograms: series.histograms,
+            })
+        })
+        .collect()
+}
+
+fn split_name(mut labels: Vec<(String, String)>) -> (String, Vec<(String, String)>) {
+    let name = labels
+        .iter()
+        .position(|(name, _)| name == "__name__")
+        .map(|position| labels.remove(position).1)
+        .unwrap_or_default();
+    labels.sort();
+    (name, labels)
+}
+
+fn family_name(name: &str) -> &str {
+    ["_bucket", "_sum", "_count", "_total"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name)
+}
+
+fn label<'a>(labels: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    labels
+        .iter()
+        .find(|(label, _)| label == name)
+        .map(|(_, value)| value.as_str())
+}
+
+/// Resource, family, labels without `le`, and timestamp.
+type ClassicKey = (usize, String, Vec<(String, String)>, i64);
+
+/// One classic histogram data point, assembled from its `_bucket`, `_sum`
+/// and `_count` series.
+struct ClassicPoint {
+    resource: usize,
+    family: String,
+    help: String,
+    time_ms: i64,
+    attributes: Vec<KeyValue>,
+    start_ms: i64,
+    /// `(le, cumulative count)`.
+    buckets: Vec<(f64, f64)>,
+    sum: Option<f64>,
+    count: Option<f64>,
+}
+
+/// OTLP metrics grouped into one resource per `job`/`instance` pair and one
+/// metric per name within it.
+#[derive(Default)]
+struct Output {
+    resources: Vec<ResourceMetrics>,
+    resource_index: HashMap<(String, String), usize>,
+    metric_index: HashMap<(usize, String), usize>,
+    classic: Vec<ClassicPoint>,
+    classic_index: HashMap<ClassicKey, usize>,
+}
+
+impl Output {
+    /// Returns how many samples and native histograms were translated.
+    fn add(&mut self, series: Vec<Series>) -> Result<(u64, u64), ApiError> {
+        let histogram_families: HashSet<String> = series
+            .iter()
+            .filter(|series| {
+                (series.metric_type == HISTOGRAM && !series.samples.is_empty())
+                    || (series.name.ends_with("_bucket") && label(&series.labels, "le").is_some())
+            })
+            .map(|series| family_name(&series.name).to_string())
+            .collect();
+
+        let (mut samples, mut histograms) = (0, 0);
+        for series in series {
+            let resource = self.resource(&series.labels);
+            let family = family_name(&series.name);
+            let classic = histogram_families.contains(family) && family != series.name;
+
+            for sample in &series.samples {
+                if sample.value.to_bits() == STALE_NAN {
+                    continue;
+                }
+                samples += 1;
+                if classic {
+                    self.add_classic(resource, &series, family, sample);
+                } else {
+                    self.add_number(resource, &series, sample);
+                }
+            }
+            for histogram in &series.histograms {
+                histograms += 1;
+                self.add_native(resource, &series, histogram)?;
+            }
+        }
+        Ok((samples, histograms))
+    }
+
+    fn resource(&mut self, labels: &[(String, String)]) -> usize {
+        let job = label(labels, "job").unwrap_or_default().to_string();
+        let instance = label(labels, "instance").unwrap_or_default().to_string();
+        let resources = &mut self.resources;
+        *self
+            .resource_index
+            .entry((job.clone(), instance.clone()))
+            .or_insert_with(|| {
+                let service_name = if job.is_empty() {
+                    UNKNOWN_SERVICE.to_string()
+                } else {
+                    job
+                };
+                let mut attributes = vec![string_attribute("service.name", service_name)];
+                if !instance.is_empty() {
+                    attributes.push(string_attribute("service.instance.id", instance));
+                }
+                resources.push(ResourceMetrics {
+                    resource: Some(Resource {
+                        attributes,
+                        ..Default::default()
+                    }),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: Some(InstrumentationScope {
+                            name: "prometheus".to_string(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                });
+                resources.len() - 1
+            })
+    }
+
+    /// The metric `name` in `resource`, created with `data` on first use.
+    /// A name reused with another type keeps the type it was created with.
+    fn metric(
+        &mut self,
+        resource: usize,
+        name: &str,
+        help: &str,
+        unit: &str,
+        data: impl FnOnce() -> metric::Data,
+    ) -> &mut metric::Data {
+        let metrics = &mut self.resources[resource].scope_metrics[0].metrics;
+        let position = *self
+            .metric_index
+            .entry((resource, name.to_string()))
+            .or_insert_with(|| {
+                metrics.push(Metric {
+                    name: name.to_string(),
+                    description: help.to_string(),
+                    unit: unit.to_string(),
+                    data: Some(data()),
+                    ..Default::default()
+                });
+                metrics.len() - 1
+            });
+        metrics[position]
+            .data
+            .as_mut()
+            .expect("metrics are created with data")
+    }
+
+    fn add_number(&mut self, resource: usize, series: &Series, sample: &proto::Sample) {
+        let point = NumberDataPoint {
+            attributes: attributes(&series.labels, false),
+            start_time_unix_nano: millis_to_nanos(series.start_ms),
+            time_unix_nano: millis_to_nanos(sample.timestamp),
+            value: Some(number_data_point::Value::AsDouble(sample.value)),
+            ..Default::default()
+        };
+        let counter = series.metric_type == COUNTER
+            || (series.metric_type != GAUGE && series.name.ends_with("_total"));
+        let data = self.metric(resource, &series.name, &series.help, &series.unit, || {
+            if counter {
+                metric::Data::Sum(Sum {
+                    aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                    is_monotonic: true,
+                    ..Default::default()
+                })
+            } else {
+                metric::Data::Gauge(Gauge::default())
+            }
+        });
+        match data {
+            metric::Data::Sum(sum) => sum.data_points.push(point),
+            metric::Data::Gauge(gauge) => gauge.data_points.push(point),
+            _ => {}
+        }
+    }
+
+    fn add_classic(
+        &mut self,
+        resource: usize,
+        series: &Series,
+        family: &str,
+        sample: &proto::Sample,
+    ) {
+        let labels: Vec<(String, String)> = series
+            .labels
+            .iter()
+            .filter(|(name, _)| name != "le")
+            .cloned()
+            .collect();
+        let key = (resource, family.to_string(), labels, sample.timestamp);
+        let classic = &mut self.classic;
+        let position = *self.classic_index.entry(key).or_insert_with(|| {
+            classic.push(ClassicPoint {
+                resource,
+                family: family.to_string(),
+                help: series.help.clone(),
+                time_ms: sample.timestamp,
+                attributes: attributes(&series.labels, true),
+                start_ms: series.start_ms,
+                buckets: Vec::new(),
+                sum: None,
+                count: None,
+            });
+            classic.len() - 1
+        });
+        let point = &mut self.classic[position];
+
+        if series.name.ends_with("_sum") {
+            point.sum = Some(sample.value);
+        } else if series.name.ends_with("_count") {
+            point.count = Some(sample.value);
+        } else if let Some(le) = label(&series.labels, "le").and_then(|le| le.parse().ok()) {
+            point.buckets.push((le, sample.value));
+        }
+    }
+
+    fn add_native(
+        &mut self,
+        resource: usize,
+        series: &Series,
+        histogram: &proto::Histogram,
+    ) -> Result<(), ApiError> {
+        let count = if histogram.count_float > 0.0 {
+            histogram.count_float.round() as u64
+        } else {
+            histogram.count_int
+        };
+        let attributes = attributes(&series.labels, false);
+        let start_time_unix_nano = millis_to_nanos(series.start_ms);
+        let time_unix_nano = millis_to_nanos(histogram.timestamp);
+        let (first, positive) = dense_buckets(
+            &histogram.positive_spans,
+            &histogram.positive_deltas,
+            &histogram.positive_counts,
+        )?;
+
+        if histogram.schema == CUSTOM_BUCKETS_SCHEMA {
+            let buckets = histogram.custom_values.len() + 1;
+            let mut bucket_counts = vec![0; buckets];
+            for (index, value) in positive.into_iter().enumerate() {
+                let index = usize::try_from(first)
+                    .ok()
+                    .map(|first| first + index)
+                    .filter(|index| *index < buckets)
+                    .ok_or_else(|| ApiError::bad_request("Bucket outside custom boundaries"))?;
+                bucket_counts[index] = value;
+            }
+            let point = HistogramDataPoint {
+                attributes,
+                start_time_unix_nano,
+                time_unix_nano,
+                count,
+                sum: Some(histogram.sum),
+                bucket_counts,
+                explicit_bounds: histogram.custom_values.clone(),
+                ..Default::default()
+            };
+            if let metric::Data::Histogram(data) = self.metric(
+                resource,
+                &series.name,
+                &series.help,
+                &series.unit,
+                cumulative_histogram,
+            ) {
+                data.data_points.push(point);
+            }
+            return Ok(());
+        }
+
+        let (negative_first, negative) = dense_buckets(
+            &histogram.negative_spans,
+            &histogram.negative_deltas,
+            &histogram.negative_counts,
+        )?;
+        let zero_count = if histogram.zero_count_float > 0.0 {
+            histogram.zero_count_float.round() as u64
+        } else {
+            histogram.zero_count_int
+        };
+        // Prometheus bucket `i` is (base^(i-1), base^i]; OTLP's is
+        // (base^i, base^(i+1)], hence the offset shift.
+        let point = ExponentialHistogramDataPoint {
+            attributes,
+            start_time_unix_nano,
+            time_unix_nano,
+            count,
+            sum: Some(histogram.sum),
+            scale: histogram.schema,
+            zero_count,
+            zero_threshold: histogram.zero_threshold,
+            positive: Some(Buckets {
+                offset: first.saturating_sub(1),
+                bucket_counts: positive,
+            }),
+            negative: Some(Buckets {
+                offset: negative_first.saturating_sub(1),
+                bucket_counts: negative,
+            }),
+            ..Default::default()
+        };
+        let data = self.metric(resource, &series.name, &series.help, &series.unit, || {
+            metric::Data::ExponentialHistogram(ExponentialHistogram {
+                aggregation_temporality: AggregationTemporality::Cumulative as i32,
+                ..Default::default()
+            })
+        });
+        if let metric::Data::ExponentialHistogram(data) = data {
+            data.data_points.push(point);
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> ExportMetricsServiceRequest {
+        let classic = std::mem::take(&mut self.classic);
+        for mut point in classic {
+            point
+                .buckets
+                .sort_by(|left, right| left.0.total_cmp(&right.0));
+            let mut previous = 0.0;
+            let bucket_counts: Vec<u64> = point
+                .buckets
+                .iter()
+                .map(|(_, cumulative)| {
+                    let count = (cumulative - previous).max(0.0).round() as u64;
+                    previous = *cumulative;
+                    count
+                })
+                .collect();
+            let mut explicit_bounds: Vec<f64> = point
+                .buckets
+                .iter()
+                .map(|(le, _)| *le)
+                .filter(|le| le.is_finite())
+                .collect();
+            // Without a +Inf bucket the last bound is the top of the range.
+            if point.buckets.last().is_some_and(|(le, _)| le.is_finite()) {
+                explicit_bounds.pop();
+            }
+            let count = point
+                .count
+                .or(point.buckets.last().map(|(_, cumulative)| *cumulative))
+                .unwrap_or_default();
+
+            let data_point = HistogramDataPoint {
+                attributes: point.attributes,
+                start_time_unix_nano: millis_to_nanos(point.start_ms),
+                time_unix_nano: millis_to_nanos(point.time_ms),
+                count: count.max(0.0).round() as u64,
+                sum: point.sum,
+                bucket_counts,
+                explicit_bounds,
+                ..Default::default()
+            };
+            if let metric::Data::Histogram(data) = self.metric(
+                point.resource,
+                &point.family,
+                &point.help,
+                "",
+                cumulative_histogram,
+            ) {
+                data.data_points.push(data_point);
+            }
+        }
+
+        // Series without any usable samples leave empty resources behind.
+        self.resources
+            .retain(|resource| !resource.scope_metrics[0].metrics.is_empty());
+        ExportMetricsServiceRequest {
+            resource_metrics: self.resources,
+        }
+    }
+}
+
+fn cumulative_histogram() -> metric::Data {
+    metric::Data::Histogram(Histogram {
+        aggregation_temporality: AggregationTemporality::Cumulative as i32,
+        ..Default::default()
+    })
+}
+
+/// Data point attributes: every label except the ones that became the
+/// resource, and `le` for classic histogram buckets.
+fn attributes(labels: &[(String, String)], drop_le: bool) -> Vec<KeyValue> {
+    labels
+        .iter()
+        .filter(|(name, _)| name != "job" && name != "instance" && !(drop_le && name == "le"))
+        .map(|(name, value)| string_attribute(name, value.clone()))
+        .collect()
+}
+
+fn millis_to_nanos(millis: i64) -> u64 {
+    (millis.max(0) as u64).saturating_mul(1_000_000)
+}
+
+/// Expands a native histogram's sparse spans into the index of the first
+/// bucket and contiguous counts, gaps filled with zeros. Integer histograms
+/// send delta-encoded counts, float histograms absolute ones.
+fn dense_buckets(
+    spans: &[proto::BucketSpan],
+    deltas: &[i64],
+    counts: &[f64],
+) -> Result<(i32, Vec<u64>), ApiError> {
+    let mut values = Vec::with_capacity(deltas.len().max(counts.len()));
+    if deltas.is_empty() {
+        values.extend(counts.iter().map(|count| count.max(0.0).round() as u64));
+    } else {
+        let mut running = 0i64;
+        for delta in deltas {
+            running = running.saturating_add(*delta);
+            values.push(running.max(0) as u64);
+        }
+    }
+
+    let Some(first) = spans.first().map(|span| span.offset) else {
+        return Ok((0, Vec::new()));
+    };
+    let mut dense = Vec::new();
+    let mut values = values.into_iter();
+    let mut index = i64::from(first);
+    for (position, span) in spans.iter().enumerate() {
+        if position > 0 {
+            index += i64::from(span.offset);
+        }
+        for _ in 0..span.length {
+            let slot = usize::try_from(index - i64::from(first))
+                .ok()
+                .filter(|slot| *slot < MAX_BUCKETS)
+                .ok_or_else(|| ApiError::bad_request("Native histogram spans out of range"))?;
+            if dense.len() <= slot {
+                dense.resize(slot + 1, 0);
+            }
+            dense[slot] = values
+                .next()
+                .ok_or_else(|| ApiError::bad_request("Native histogram spans exceed counts"))?;
+            index += 1;
+        }
+    }
+    Ok((first, dense))
+}
+
+/// `prometheus.WriteRequest` (remote write v1) and
+/// `io.prometheus.write.v2.Request`, declared by hand. Exemplars are not
+/// decoded.
+mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct WriteRequest {
+        #[prost(message, repeated, tag = "1")]
+        pub timeseries: Vec<TimeSeries>,
+        #[prost(message, repeated, tag = "3")]
+        pub metadata: Vec<MetricMetadata>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TimeSeries {
+        #[prost(message, repeated, tag = "1")]
+        pub labels: Vec<Label>,
+        #[prost(message, repeated, tag = "2")]
+        pub samples: Vec<Sample>,
+        #[prost(message, repeated, tag = "4")]
+        pub histograms: Vec<Histogram>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Label {
+        #[prost(string, tag = "1")]
+        pub name: String,
+        #[prost(string, tag = "2")]
+        pub value: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sample {
+        #[prost(double, tag = "1")]
+        pub value: f64,
+        /// Milliseconds since the epoch.
+        #[prost(int64, tag = "2")]
+        pub timestamp: i64,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MetricMetadata {
+        #[prost(int32, tag = "1")]
+        pub r#type: i32,
+        #[prost(string, tag = "2")]
+        pub metric_family_name: String,
+        #[prost(string, tag = "4")]
+        pub help: String,
+        #[prost(string, tag = "5")]
+        pub unit: String,
+    }
+
+    /// A native histogram. The `oneof` count fields are plain fields here,
+    /// which is wire-compatible; integer histograms leave the float ones 0.
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Histogram {
+        #[prost(uint64, tag = "1")]
+        pub count_int: u64,
+        #[prost(double, tag = "2")]
+        pub count_float: f64,
+        #[prost(double, tag = "3")]
+        pub sum: f64,
+        #[prost(sint32, tag = "4")]
+        pub schema: i32,
+        #[prost(double, tag = "5")]
+        pub zero_threshold: f64,
+        #[prost(uint64, tag = "6")]
+        pub zero_count_int: u64,
+        #[prost(double, tag = "7")]
+        pub zero_count_float: f64,
+        #[prost(message, repeated, tag = "8")]
+        pub negative_spans: Vec<BucketSpan>,
+        #[prost(sint64, repeated, tag = "9")]
+        pub negative_deltas: Vec<i64>,
+        #[prost(double, repeated, tag = "10")]
+        pub negative_counts: Vec<f64>,
+        #[prost(message, repeated, tag = "11")]
+        pub positive_spans: Vec<BucketSpan>,
+        #[prost(sint64, repeated, tag = "12")]
+        pub positive_deltas: Vec<i64>,
+        #[prost(double, repeated, tag = "13")]
+        pub positive_counts: Vec<f64>,
+        #[prost(int64, tag = "15")]
+        pub timestamp: i64,
+        #[prost(double, repeated, tag = "16")]
+        pub custom_values: Vec<f64>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct BucketSpan {
+        #[prost(sint32, tag = "1")]
+        pub offset: i32,
+        #[prost(uint32, tag = "2")]
+        pub length: u32,
+    }
+
+    pub mod v2 {
+        use super::{Histogram, Sample};
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct Request {
+            #[prost(string, repeated, tag = "4")]
+            pub symbols: Vec<String>,
+            #[prost(message, repeated, tag = "5")]
+            pub timeseries: Vec<TimeSeries>,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct TimeSeries {
+            /// Pairs of name and value references into `symbols`.
+            #[prost(uint32, repeated, tag = "1")]
+            pub labels_refs: Vec<u32>,
+            #[prost(message, repeated, tag = "2")]
+            pub samples: Vec<Sample>,
+            #[prost(message, repeated, tag = "3")]
+            pub histograms: Vec<Histogram>,
+            #[prost(message, optional, tag = "5")]
+            pub metadata: Option<Metadata>,
+            #[prost(int64, tag = "6")]
+            pub created_timestamp: i64,
+        }
+
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct Metadata {
+            #[prost(int32, tag = "1")]
+            pub r#type: i32,
+            #[prost(uint32, tag = "3")]
+            pub help_ref: u32,
+            #[prost(uint32, tag = "4")]
+            pub unit_ref: u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(labels: &[(&str, &str)], value: f64) -> proto::TimeSeries {
+        proto::TimeSeries {
+            labels: labels
+                .iter()
+                .map(|(name, value)| proto::Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            samples: vec![proto::Sample {
+                value,
+                timestamp: 1_700_000_000_000,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn metric<'a>(request: &'a ExportMetricsServiceRequest, name: &str) -> &'a Metric {
+        request.resource_metrics[0].scope_metrics[0]
+            .metrics
+            .iter()
+            .find(|metric| metric.name == name)
+            .unwrap()
+    }
+
+    #[test]
+    fn v1_samples_become_gauges_sums_and_classic_histograms() {
+        let request = proto::WriteRequest {
+            timeseries: vec![
+                series(
+                    &[("__name__", "up"), ("job", "api"), ("instance", "a:9090")],
+                    1.0,
+                ),
+                series(
+                    &[
+                        ("__name__", "http_requests_total"),
+                        ("job", "api"),
+                        ("instance", "a:9090"),
+                        ("code", "200"),
+                    ],
+                    42.0,
+                ),
+                series(
+                    &[
+                        ("__name__", "latency_bucket"),
+                        ("job", "api"),
+                        ("instance", "a:9090"),
+                        ("le", "0.1"),
+                    ],
+                    3.0,
+                ),
+                series(
+                    &[
+                        ("__name__", "latency_bucket"),
+                        ("job", "api"),
+                        ("instance", "a:9090"),
+                        ("le", "+Inf"),
+                    ],
+                    5.0,
+                ),
+                series(
+                    &[
+                        ("__name__", "latency_sum"),
+                        ("job", "api"),
+                        ("instance", "a:9090"),
+                    ],
+                    0.9,
+                ),
+                series(
+                    &[
+                        ("__name__", "latency_count"),
+                        ("job", "api"),
+                        ("instance", "a:9090"),
+                    ],
+                    5.0,
+                ),
+                series(
+                    &[("__name__", "up"), ("job", "api"), ("instance", "a:9090")],
+                    f64::from_bits(STALE_NAN),
+                ),
+            ],
+            metadata: Vec::new(),
+        };
+        let (payload, headers) = translate("application/x-protobuf", &request.encode_to_vec())
+            .ok()
+            .unwrap();
+        assert!(headers.is_empty());
+        let request = ExportMetricsServiceRequest::decode(payload.as_slice()).unwrap();
+
+        assert_eq!(request.resource_metrics.len(), 1);
+        let resource = request.resource_metrics[0].resource.as_ref().unwrap();
+        assert_eq!(
+            resource.attributes[0],
+            string_attribute("service.name", "api")
+        );
+
+        let Some(metric::Data::Gauge(gauge)) = &metric(&request, "up").data else {
+            panic!("up should be a gauge");
+        };
+        assert_eq!(gauge.data_points.len(), 1);
+        let Some(metric::Data::Sum(sum)) = &metric(&request, "http_requests_total").data else {
+            panic!("_total should be a counter");
+        };
+        assert!(sum.is_monotonic);
+        assert_eq!(
+            sum.data_points[0].attributes,
+            vec![string_attribute("code", "200")]
+        );
+
+        let Some(metric::Data::Histogram(histogram)) = &metric(&request, "latency").data else {
+            panic!("latency should be a histogram");
+        };
+        let point = &histogram.data_points[0];
+        assert_eq!(point.explicit_bounds, vec![0.1]);
+        assert_eq!(point.bucket_counts, vec![3, 2]);
+        assert_eq!(point.count, 5);
+        assert_eq!(point.sum, Some(0.9));
+        assert!(point.attributes.is_empty());
+    }
+
+    #[test]
+    fn v2_native_histograms_become_exponential_histograms() {
+        let request = proto::v2::Request {
+            symbols: vec![
+                String::new(),
+                "__name__".to_string(),
+                "rpc_duration_seconds".to_string(),
+                "job".to_string(),
+                "worker".to_string(),
+                "Latency of RPCs.".to_string(),
+            ],
+            timeseries: vec![proto::v2::TimeSeries {
+                labels_refs: vec![1, 2, 3, 4],
+                histograms: vec![proto::Histogram {
+                    count_int: 6,
+                    sum: 1.5,
+                    schema: 0,
+                    zero_count_int: 1,
+                    positive_spans: vec![
+                        proto::BucketSpan {
+                            offset: 1,
+                            length: 2,
+                        },
+                        proto::BucketSpan {
+                            offset: 1,
+                            length: 1,
+                        },
+                    ],
+                    positive_deltas: vec![2, -1, 1],
+                    timestamp: 1_700_000_000_000,
+                    ..Default::default()
+                }],
+                metadata: Some(proto::v2::Metadata {
+                    r#type: HISTOGRAM,
+                    help_ref: 5,
+                    unit_ref: 0,
+                }),
+                ..Default::default()
+            }],
+        };
+        let (payload, headers) = translate(
+            "application/x-protobuf;proto=io.prometheus.write.v2.request",
+            &request.encode_to_vec(),
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(headers[HISTOGRAMS_WRITTEN], "1");
+        assert_eq!(headers[SAMPLES_WRITTEN], "0");
+
+        let request = ExportMetricsServiceRequest::decode(payload.as_slice()).unwrap();
+        let metric = metric(&request, "rpc_duration_seconds");
+        assert_eq!(metric.description, "Latency of RPCs.");
+        let Some(metric::Data::ExponentialHistogram(histogram)) = &metric.data else {
+            panic!("native histograms should be exponential");
+        };
+        let positive = histogram.data_points[0].positive.as_ref().unwrap();
+        assert_eq!(positive.offset, 0);
+        assert_eq!(positive.bucket_counts, vec![2, 1, 0, 2]);
+
+        let bad = proto::v2::Request {
+            symbols: vec![String::new()],
+            timeseries: vec![proto::v2::TimeSeries {
+                labels_refs: vec![0, 7],
+                ..Default::default()
+            }],
+        };
+        assert!(translate(
+            "application/x-protobuf;proto=io.prometheus.write.v2.request",
+            &bad.encode_to_vec()
+        )
+        .is_err());
+    }
+}
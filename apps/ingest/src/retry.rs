@@ -0,0 +1,248 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::http::HeaderValue;
+use metrics::{counter, gauge};
+use tracing::{info, warn};
+
+/// How failed forwards are retried within the route's deadline.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    /// Total attempts per payload; `1` disables retries.
+    pub max_attempts: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with equal jitter: somewhere between half and all
+    /// of `base * 2^(attempt - 1)`, capped at `max_backoff`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        let jitter = RandomState::new().build_hasher().finish() % (half.as_millis() as u64 + 1);
+        half + Duration::from_millis(jitter)
+    }
+}
+
+/// Parses a delay-seconds `Retry-After`; HTTP dates are ignored.
+pub fn parse_retry_after(value: Option<&HeaderValue>) -> Option<Duration> {
+    value?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// What one forward attempt says about the collector.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The collector answered: 2xx, or a 4xx the client has to fix.
+    Delivered,
+    /// 429: the collector is up but shedding load.
+    Throttled(Option<Duration>),
+    /// Connect error, timeout, 502, 503 or 504.
+    Unavailable,
+    /// Any other 5xx. Counts against the breaker but is not retried.
+    Failed,
+}
+
+impl Outcome {
+    pub fn from_status(status: u16, retry_after: Option<Duration>) -> Self {
+        match status {
+            429 => Self::Throttled(retry_after),
+            502..=504 => Self::Unavailable,
+            500..=599 => Self::Failed,
+            _ => Self::Delivered,
+        }
+    }
+
+    fn is_failure(self) -> bool {
+        matches!(self, Self::Unavailable | Self::Failed)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BreakerConfig {
+    /// Consecutive failures that open the breaker; `0` disables it.
+    pub failure_threshold: u32,
+    /// How long an open breaker fails fast before letting a probe through.
+    pub cooldown: Duration,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probe_started: Instant },
+}
+
+/// Per-route circuit breaker. Once `failure_threshold` forwards fail in a row
+/// it rejects forwards for `cooldown`, then lets a single probe decide
+/// whether to close again.
+pub struct CircuitBreaker {
+    route: String,
+    config: BreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(route: &str, config: BreakerConfig) -> Self {
+        let breaker = Self {
+            route: route.to_string(),
+            config,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        };
+        breaker.publish(BreakerState::Closed { failures: 0 });
+        breaker
+    }
+
+    /// Whether a forward may be attempted now.
+    pub fn allow(&self) -> bool {
+        if self.config.failure_threshold == 0 {
+            return true;
+        }
+        let mut state = self.state.lock().expect("breaker lock poisoned");
+        let now = Instant::now();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now < until => false,
+            // A probe whose request was abandoned must not wedge the breaker.
+            BreakerState::HalfOpen { probe_started }
+                if now.duration_since(probe_started) < self.config.cooldown =>
+            {
+                false
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                self.transition(&mut state, BreakerState::HalfOpen { probe_started: now });
+                true
+            }
+        }
+    }
+
+    pub fn record(&self, outcome: Outcome) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+        let mut state = self.state.lock().expect("breaker lock poisoned");
+        let next = match (*state, outcome.is_failure()) {
+            (_, false) => BreakerState::Closed { failures: 0 },
+            (BreakerState::Closed { failures }, true)
+                if failures + 1 < self.config.failure_threshold =>
+            {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, true) => BreakerState::Open {
+                until: Instant::now() + self.config.cooldown,
+            },
+        };
+        self.transition(&mut state, next);
+    }
+
+    pub fn state(&self) -> &'static str {
+        state_name(*self.state.lock().expect("breaker lock poisoned"))
+    }
+
+    fn transition(&self, state: &mut BreakerState, next: BreakerState) {
+        let previous = state_name(*state);
+        *state = next;
+        if previous == state_name(next) {
+            return;
+        }
+        match next {
+            BreakerState::Open { .. } => warn!(
+                route = %self.route,
+                cooldown_secs = self.config.cooldown.as_secs(),
+                "Circuit breaker opened, failing fast"
+            ),
+            _ => info!(
+                route = %self.route,
+                from = previous,
+                to = state_name(next),
+                "Circuit breaker state changed"
+            ),
+        }
+        counter!(
+            "ingest_circuit_breaker_transitions_total",
+            "route" => self.route.clone(),
+            "state" => state_name(next)
+        )
+        .increment(1);
+        self.publish(next);
+    }
+
+    fn publish(&self, state: BreakerState) {
+        let value = match state {
+            BreakerState::Closed { .. } => 0.0,
+            BreakerState::HalfOpen { .. } => 1.0,
+            BreakerState::Open { .. } => 2.0,
+        };
+        gauge!("ingest_circuit_breaker_state", "route" => self.route.clone()).set(value);
+    }
+}
+
+fn state_name(state: BreakerState) -> &'static str {
+    match state {
+        BreakerState::Closed { .. } => "closed",
+        BreakerState::Open { .. } => "open",
+        BreakerState::HalfOpen { .. } => "half_open",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_with_jitter_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+        for _ in 0..50 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let second = policy.backoff(2);
+            assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+            let capped = policy.backoff(10);
+            assert!(capped >= Duration::from_millis(150) && capped <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures_and_probes_after_cooldown() {
+        let breaker = CircuitBreaker::new(
+            "default",
+            BreakerConfig {
+                failure_threshold: 2,
+                cooldown: Duration::from_millis(20),
+            },
+        );
+
+        breaker.record(Outcome::Unavailable);
+        breaker.record(Outcome::Delivered);
+        breaker.record(Outcome::Failed);
+        assert_eq!(breaker.state(), "closed");
+        breaker.record(Outcome::Unavailable);
+        assert_eq!(breaker.state(), "open");
+        assert!(!breaker.allow());
+
+        std::thread::sleep(Duration::from_millis(25));
+        assert!(breaker.allow());
+        assert_eq!(breaker.state(), "half_open");
+        assert!(!breaker.allow());
+        breaker.record(Outcome::Throttled(None));
+        assert_eq!(breaker.state(), "closed");
+        assert!(breaker.allow());
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::compression::{ContentEncoding, ForwardCompression};
+use crate::grpc::GrpcForwarder;
+use crate::retry::{BreakerConfig, CircuitBreaker};
+use crate::{AppConfig, ForwardProtocol, PayloadFormat, ResolvedIngestKey, Signal};
+
+/// Name of the route built from the `INGEST_FORWARD_*` settings.
+pub const DEFAULT_ROUTE: &str = "default";
+
+/// One entry of `INGEST_ROUTES_FILE`. Empty match lists match everything.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteSpec {
+    name: String,
+    endpoint: String,
+    /// `http` or `grpc`; defaults to `INGEST_FORWARD_PROTOCOL`.
+    protocol: Option<String>,
+    #[serde(default)]
+    org_ids: Vec<String>,
+    #[serde(default)]
+    signals: Vec<String>,
+    #[serde(default)]
+    key_types: Vec<String>,
+    /// Defaults to `INGEST_FORWARD_TIMEOUT_MS`.
+    timeout_ms: Option<u64>,
+    #[serde(default)]
+    require_tls: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RoutesFile {
+    routes: Vec<RouteSpec>,
+}
+
+/// Global forwarding settings that routes inherit unless they override them.
+#[derive(Clone, Copy)]
+struct RouteDefaults {
+    protocol: ForwardProtocol,
+    timeout: Duration,
+    require_tls: bool,
+    compression: ForwardCompression,
+    breaker: BreakerConfig,
+}
+
+/// Which traffic a route or mirror applies to. Empty lists match everything.
+#[derive(Default)]
+pub struct Matcher {
+    org_ids: HashSet<String>,
+    signals: HashSet<&'static str>,
+    key_types: HashSet<&'static str>,
+}
+
+impl Matcher {
+    pub fn new(
+        name: &str,
+        org_ids: Vec<String>,
+        signals: &[String],
+        key_types: &[String],
+    ) -> Result<Self, String> {
+        let signals = signals
+            .iter()
+            .map(|signal| match signal.as_str() {
+                "traces" => Ok("traces"),
+                "logs" => Ok("logs"),
+                "metrics" => Ok("metrics"),
+                other => Err(format!("'{name}' has unknown signal '{other}'")),
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        let key_types = key_types
+            .iter()
+            .map(|key_type| match key_type.as_str() {
+                "public" => Ok("public"),
+                "private" => Ok("private"),
+                other => Err(format!("'{name}' has unknown key type '{other}'")),
+            })
+            .collect::<Result<HashSet<_>, _>>()?;
+
+        Ok(Self {
+            org_ids: org_ids.into_iter().collect(),
+            signals,
+            key_types,
+        })
+    }
+
+    pub fn matches(&self, signal: Signal, resolved_key: &ResolvedIngestKey) -> bool {
+        (self.org_ids.is_empty() || self.org_ids.contains(&resolved_key.org_id))
+            && (self.signals.is_empty() || self.signals.contains(signal.path()))
+            && (self.key_types.is_empty()
+                || self.key_types.contains(resolved_key.key_type.as_str()))
+    }
+}
+
+/// A downstream collector and the traffic it receives.
+pub struct Route {
+    pub name: String,
+    pub endpoint: String,
+    pub protocol: ForwardProtocol,
+    pub timeout: Duration,
+    pub require_tls: bool,
+    pub breaker: CircuitBreaker,
+    grpc_forwarder: Option<GrpcForwarder>,
+    matcher: Matcher,
+}
+
+impl Route {
+    fn new(
+        name: String,
+        endpoint: &str,
+        protocol: ForwardProtocol,
+        timeout: Duration,
+        require_tls: bool,
+        forward_compression: ForwardCompression,
+        breaker: BreakerConfig,
+    ) -> Result<Self, String> {
+        let endpoint = endpoint.trim().trim_end_matches('/').to_string();
+        if endpoint.is_empty() {
+            return Err(format!("route '{name}' has no endpoint"));
+        }
+        if require_tls && !endpoint.starts_with("https://") {
+            return Err(format!(
+                "route '{name}' requires TLS but {endpoint} is not https"
+            ));
+        }
+        if protocol == ForwardProtocol::Grpc
+            && matches!(
+                forward_compression,
+                ForwardCompression::Fixed(ContentEncoding::Snappy | ContentEncoding::SnappyFramed)
+            )
+        {
+            return Err(format!(
+                "route '{name}' uses grpc, which does not support INGEST_FORWARD_COMPRESSION=snappy"
+            ));
+        }
+
+        let grpc_forwarder = match protocol {
+            ForwardProtocol::Http => None,
+            ForwardProtocol::Grpc => Some(GrpcForwarder::connect_lazy(&name, &endpoint, timeout)?),
+        };
+
+        Ok(Self {
+            breaker: CircuitBreaker::new(&name, breaker),
+            name,
+            endpoint,
+            protocol,
+            timeout,
+            require_tls,
+            grpc_forwarder,
+            matcher: Matcher::default(),
+        })
+    }
+
+    pub fn grpc_forwarder(&self) -> Option<&GrpcForwarder> {
+        self.grpc_forwarder.as_ref()
+    }
+
+    /// The format payloads leave in: the gRPC forwarder always speaks
+    /// protobuf, whatever the client sent.
+    pub fn output_format(&self, payload_format: PayloadFormat) -> PayloadFormat {
+        match self.protocol {
+            ForwardProtocol::Http => payload_format,
+            ForwardProtocol::Grpc => PayloadFormat::Protobuf,
+        }
+    }
+}
+
+/// Ordered routing rules; the first match wins and unmatched traffic goes to
+/// the default route.
+pub struct RouteTable {
+    routes: Vec<Route>,
+    default: Route,
+}
+
+impl RouteTable {
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let defaults = RouteDefaults {
+            protocol: config.forward_protocol,
+            timeout: config.forward_timeout,
+            require_tls: config.require_tls,
+            compression: config.forward_compression,
+            breaker: config.breaker,
+        };
+        let default = Route::new(
+            DEFAULT_ROUTE.to_string(),
+            &config.forward_endpoint,
+            defaults.protocol,
+            defaults.timeout,
+            defaults.require_tls,
+            defaults.compression,
+            defaults.breaker,
+        )?;
+
+        let specs = match &config.routes_path {
+            Some(path) => load_specs(path)?,
+            None => Vec::new(),
+        };
+
+        let mut names = HashSet::from([DEFAULT_ROUTE.to_string()]);
+        let mut routes = Vec::with_capacity(specs.len());
+        for spec in specs {
+            if !names.insert(spec.name.clone()) {
+                return Err(format!("duplicate route name '{}'", spec.name));
+            }
+            routes.push(route_from_spec(spec, defaults)?);
+        }
+
+        Ok(Self { routes, default })
+    }
+
+    pub fn route_for(&self, signal: Signal, resolved_key: &ResolvedIngestKey) -> &Route {
+        self.routes
+            .iter()
+            .find(|route| route.matcher.matches(signal, resolved_key))
+            .unwrap_or(&self.default)
+    }
+
+    /// Every route, configured ones first and the default last.
+    pub fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter().chain(std::iter::once(&self.default))
+    }
+}
+
+fn load_specs(path: &Path) -> Result<Vec<RouteSpec>, String> {
+    let raw = std::fs::read(path)
+        .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+    let file: RoutesFile = serde_json::from_slice(&raw)
+        .map_err(|error| format!("invalid routes in {}: {error}", path.display()))?;
+    Ok(file.routes)
+}
+
+fn route_from_spec(spec: RouteSpec, defaults: RouteDefaults) -> Result<Route, String> {
+    let protocol = match spec.protocol.as_deref() {
+        None => defaults.protocol,
+        Some("http") => ForwardProtocol::Http,
+        Some("grpc") => ForwardProtocol::Grpc,
+        Some(other) => {
+            return Err(format!(
+                "route '{}' has unknown protocol '{other}'",
+                spec.name
+            ));
+        }
+    };
+
+    let matcher = Matcher::new(&spec.name, spec.org_ids, &spec.signals, &spec.key_types)?;
+
+    let mut route = Route::new(
+        spec.name,
+        &spec.endpoint,
+        protocol,
+        spec.timeout_ms
+            .map_or(defaults.timeout, Duration::from_millis),
+        spec.require_tls || defaults.require_tls,
+        defaults.compression,
+        defaults.breaker,
+    )?;
+    route.matcher = matcher;
+    Ok(route)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IngestKeyType;
+
+    fn key(org_id: &str, key_type: IngestKeyType) -> ResolvedIngestKey {
+        ResolvedIngestKey {
+            org_id: org_id.to_string(),
+            key_type,
+            key_id: "key".to_string(),
+            allowed_origins: None,
+        }
+    }
+
+    fn spec(value: serde_json::Value) -> RouteSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn defaults() -> RouteDefaults {
+        RouteDefaults {
+            protocol: ForwardProtocol::Http,
+            timeout: Duration::from_secs(10),
+            require_tls: false,
+            compression: ForwardCompression::Passthrough,
+            breaker: BreakerConfig {
+                failure_threshold: 5,
+                cooldown: Duration::from_secs(30),
+            },
+        }
+    }
+
+    #[test]
+    fn first_matching_route_wins_and_the_rest_falls_back_to_default() {
+        let defaults = defaults();
+        let routes = RouteTable {
+            routes: vec![
+                route_from_spec(
+                    spec(serde_json::json!({
+                        "name": "enterprise",
+                        "endpoint": "http://enterprise:4318/",
+                        "org_ids": ["org_big"],
+                        "timeout_ms": 2500,
+                    })),
+                    defaults,
+                )
+                .unwrap(),
+                route_from_spec(
+                    spec(serde_json::json!({
+                        "name": "browser-logs",
+                        "endpoint": "http://browser:4318",
+                        "signals": ["logs"],
+                        "key_types": ["public"],
+                    })),
+                    defaults,
+                )
+                .unwrap(),
+            ],
+            default: Route::new(
+                DEFAULT_ROUTE.to_string(),
+                "http://collector:4318",
+                defaults.protocol,
+                defaults.timeout,
+                defaults.require_tls,
+                defaults.compression,
+                defaults.breaker,
+            )
+            .unwrap(),
+        };
+
+        let enterprise = routes.route_for(Signal::Logs, &key("org_big", IngestKeyType::Public));
+        assert_eq!(enterprise.name, "enterprise");
+        assert_eq!(enterprise.endpoint, "http://enterprise:4318");
+        assert_eq!(enterprise.timeout, Duration::from_millis(2500));
+
+        let browser = routes.route_for(Signal::Logs, &key("org_a", IngestKeyType::Public));
+        assert_eq!(browser.name, "browser-logs");
+
+        let private = routes.route_for(Signal::Logs, &key("org_a", IngestKeyType::Private));
+        assert_eq!(private.name, DEFAULT_ROUTE);
+        let traces = routes.route_for(Signal::Traces, &key("org_a", IngestKeyType::Public));
+        assert_eq!(traces.name, DEFAULT_ROUTE);
+    }
+
+    #[test]
+    fn tls_routes_reject_plaintext_endpoints() {
+        let defaults = defaults();
+        let result = route_from_spec(
+            spec(serde_json::json!({
+                "name": "eu",
+                "endpoint": "http://eu-collector:4318",
+                "require_tls": true,
+            })),
+            defaults,
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use libsql::{params, Database};
+use metrics::counter;
+use moka::future::Cache;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{any_value, AnyValue, KeyValue};
+use opentelemetry_proto::tonic::trace::v1::status::StatusCode;
+use opentelemetry_proto::tonic::trace::v1::Span;
+use tracing::warn;
+
+/// Written onto every span kept under a policy: how many original spans it
+/// stands for, so downstream counts and rates can be scaled back up.
+const ADJUSTED_COUNT_ATTRIBUTE: &str = "sampling.adjusted_count";
+/// The ratio decision uses the low 56 bits of the trace ID, which W3C trace
+/// context requires to be random.
+const RANDOM_BITS: u32 = 56;
+
+/// A row from `org_trace_sampling_policies`.
+#[derive(Debug)]
+pub struct SamplingPolicy {
+    ratio: f64,
+    keep_errors: bool,
+    keep_services: HashSet<String>,
+    keep_min_duration_ns: Option<u64>,
+}
+
+impl SamplingPolicy {
+    /// Drops spans of traces that are neither selected by the trace-ID ratio
+    /// nor matched by an always-keep rule, and returns how many were dropped.
+    ///
+    /// Rules keep the whole trace, but only the spans of it that arrive in
+    /// the same request; other parts fall back to the ratio decision.
+    pub fn apply(&self, request: &mut ExportTraceServiceRequest) -> usize {
+        let mut kept_by_rule: HashSet<Vec<u8>> = HashSet::new();
+        for resource_spans in &request.resource_spans {
+            let service_kept = resource_spans
+                .resource
+                .as_ref()
+                .and_then(|resource| service_name(&resource.attributes))
+                .is_some_and(|service| self.keep_services.contains(service));
+            for span in resource_spans
+                .scope_spans
+                .iter()
+                .flat_map(|scope_spans| &scope_spans.spans)
+            {
+                if service_kept || self.matches_rule(span) {
+                    kept_by_rule.insert(span.trace_id.clone());
+                }
+            }
+        }
+
+        let threshold = ratio_threshold(self.ratio);
+        let sampled_count = 1.0 / self.ratio;
+        let mut dropped = 0;
+        for scope_spans in request
+            .resource_spans
+            .iter_mut()
+            .flat_map(|resource_spans| &mut resource_spans.scope_spans)
+        {
+            scope_spans.spans.retain_mut(|span| {
+                let adjusted_count = if kept_by_rule.contains(&span.trace_id) {
+                    1.0
+                } else if trace_randomness(&span.trace_id) < threshold {
+                    sampled_count
+                } else {
+                    dropped += 1;
+                    return false;
+                };
+                upsert_double_attribute(
+                    &mut span.attributes,
+                    ADJUSTED_COUNT_ATTRIBUTE,
+                    adjusted_count,
+                );
+                true
+            });
+        }
+        dropped
+    }
+
+    fn matches_rule(&self, span: &Span) -> bool {
+        let is_error = span
+            .status
+            .as_ref()
+            .is_some_and(|status| status.code == StatusCode::Error as i32);
+        let duration_ns = span
+            .end_time_unix_nano
+            .saturating_sub(span.start_time_unix_nano);
+
+        (self.keep_errors && is_error)
+            || self
+                .keep_min_duration_ns
+                .is_some_and(|min_duration_ns| duration_ns >= min_duration_ns)
+    }
+}
+
+fn service_name(attributes: &[KeyValue]) -> Option<&str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.key == "service.name")
+        .and_then(|attribute| attribute.value.as_ref())
+        .and_then(|value| match &value.value {
+            Some(any_value::Value::StringValue(service)) => Some(service.as_str()),
+            _ => None,
+        })
+}
+
+/// Every replica derives the same decision from the trace ID alone, so all
+/// spans of a trace are kept or dropped together wherever they land.
+fn trace_randomness(trace_id: &[u8]) -> u64 {
+    let mut low = [0u8; 8];
+    if let Some(tail) = trace_id.get(trace_id.len().saturating_sub(8)..) {
+        low[8 - tail.len()..].copy_from_slice(tail);
+    }
+    u64::from_be_bytes(low) & ((1 << RANDOM_BITS) - 1)
+}
+
+fn ratio_threshold(ratio: f64) -> u64 {
+    (ratio.clamp(0.0, 1.0) * (1u64 << RANDOM_BITS) as f64) as u64
+}
+
+fn upsert_double_attribute(attributes: &mut Vec<KeyValue>, key: &str, value: f64) {
+    let value = Some(AnyValue {
+        value: Some(any_value::Value::DoubleValue(value)),
+    });
+    match attributes.iter_mut().find(|attribute| attribute.key == key) {
+        Some(attribute) => attribute.value = value,
+        None => attributes.push(KeyValue {
+            key: key.to_string(),
+            value,
+        }),
+    }
+}
+
+/// Per-org trace sampling policies from `org_trace_sampling_policies`,
+/// cached like quota limits. Orgs without a row are not sampled.
+pub struct SamplingPolicies {
+    db: Arc<Database>,
+    cache: Cache<String, Option<Arc<SamplingPolicy>>>,
+}
+
+impl SamplingPolicies {
+    pub fn new(db: Arc<Database>) -> Self {
+        Self {
+            db,
+            cache: Cache::builder()
+                .time_to_live(Duration::from_secs(60))
+                .max_capacity(1_000)
+                .build(),
+        }
+    }
+
+    /// Fails open: if the policy table cannot be read nothing is sampled.
+    pub async fn policy_for(&self, org_id: &str) -> Option<Arc<SamplingPolicy>> {
+        if let Some(cached) = self.cache.get(org_id).await {
+            return cached;
+        }
+
+        let policy = match self.load(org_id).await {
+            Ok(policy) => policy.map(Arc::new),
+            Err(error) => {
+                warn!(org_id, error = %error, "Failed to load trace sampling policy");
+                counter!("ingest_sampling_errors_total").increment(1);
+                None
+            }
+        };
+        self.cache.insert(org_id.to_string(), policy.clone()).await;
+        policy
+    }
+
+    async fn load(&self, org_id: &str) -> Result<Option<SamplingPolicy>, String> {
+        let conn = self.db.connect().map_err(|error| error.to_string())?;
+        let mut rows = conn
+            .query(
+                "SELECT sample_ratio, keep_errors, keep_services_json, keep_min_duration_ms FROM org_trace_sampling_policies WHERE org_id = ? LIMIT 1",
+                params![org_id],
+            )
+            .await
+            .map_err(|error| error.to_string())?;
+
+        let Some(row) = rows.next().await.map_err(|error| error.to_string())? else {
+            return Ok(None);
+        };
+        let ratio: f64 = row.get(0).map_err(|error| error.to_string())?;
+        let keep_errors: i64 = row.get(1).map_err(|error| error.to_string())?;
+        let keep_services_json: Option<String> = row.get(2).map_err(|error| error.to_string())?;
+        let keep_min_duration_ms: Option<i64> = row.get(3).map_err(|error| error.to_string())?;
+
+        // A ratio of 1 keeps everything, so there is nothing to apply.
+        if ratio >= 1.0 {
+            return Ok(None);
+        }
+        if ratio.is_nan() || ratio <= 0.0 {
+            return Err(format!("sample_ratio must be in (0, 1], got {ratio}"));
+        }
+
+        let keep_services = match keep_services_json {
+            Some(raw) => serde_json::from_str(&raw)
+                .map_err(|error| format!("invalid keep_services_json: {error}"))?,
+            None => HashSet::new(),
+        };
+
+        Ok(Some(SamplingPolicy {
+            ratio,
+            keep_errors: keep_errors != 0,
+            keep_services,
+            keep_min_duration_ns: keep_min_duration_ms
+                .map(|duration_ms| duration_ms.max(0) as u64 * 1_000_000),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_proto::tonic::resource::v1::Resource;
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Status};
+
+    fn trace_id(randomness: u64) -> Vec<u8> {
+        let mut id = vec![0xab; 8];
+        id.extend_from_slice(&randomness.to_be_bytes());
+        id
+    }
+
+    fn request(service: &str, spans: Vec<Span>) -> ExportTraceServiceRequest {
+        ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![KeyValue {
+                        key: "service.name".to_string(),
+                        value: Some(AnyValue {
+                            value: Some(any_value::Value::StringValue(service.to_string())),
+                        }),
+                    }],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn span(trace_id: Vec<u8>, error: bool) -> Span {
+        Span {
+            trace_id,
+            span_id: vec![1; 8],
+            status: error.then(|| Status {
+                code: StatusCode::Error as i32,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn policy(keep_services: &[&str]) -> SamplingPolicy {
+        SamplingPolicy {
+            ratio: 0.25,
+            keep_errors: true,
+            keep_services: keep_services.iter().map(|s| s.to_string()).collect(),
+            keep_min_duration_ns: None,
+        }
+    }
+
+    fn adjusted_count(span: &Span) -> f64 {
+        match span
+            .attributes
+            .iter()
+            .find(|kv| kv.key == ADJUSTED_COUNT_ATTRIBUTE)
+        {
+            Some(KeyValue {
+                value:
+                    Some(AnyValue {
+                        value: Some(any_value::Value::DoubleValue(count)),
+                    }),
+                ..
+            }) => *count,
+            _ => panic!("missing adjusted count"),
+        }
+    }
+
+    #[test]
+    fn ratio_decision_depends_only_on_the_trace_id() {
+        let low = trace_id(1);
+        let high = trace_id((1 << RANDOM_BITS) - 1);
+        let mut request = request(
+            "checkout",
+            vec![
+                span(low.clone(), false),
+                span(high, false),
+                span(low, false),
+            ],
+        );
+
+        assert_eq!(policy(&[]).apply(&mut request), 1);
+        let spans = &request.resource_spans[0].scope_spans[0].spans;
+        assert_eq!(spans.len(), 2);
+        assert!(spans.iter().all(|span| adjusted_count(span) == 4.0));
+    }
+
+    #[test]
+    fn errors_keep_their_whole_trace_and_services_are_always_kept() {
+        let unsampled = trace_id((1 << RANDOM_BITS) - 1);
+        let mut errors = request(
+            "checkout",
+            vec![
+                span(unsampled.clone(), false),
+                span(unsampled.clone(), true),
+            ],
+        );
+        assert_eq!(policy(&[]).apply(&mut errors), 0);
+        let spans = &errors.resource_spans[0].scope_spans[0].spans;
+        assert!(spans.iter().all(|span| adjusted_count(span) == 1.0));
+
+        let mut payments = request("payments", vec![span(unsampled, false)]);
+        assert_eq!(policy(&["payments"]).apply(&mut payments), 0);
+    }
+}
@@ -0,0 +1,245 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use opentelemetry_proto::tonic::collector::logs::v1::ExportLogsServiceRequest;
+use opentelemetry_proto::tonic::common::v1::{
+    any_value, AnyValue, ArrayValue, InstrumentationScope, KeyValue, KeyValueList,
+};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::receiver::{string_attribute, UNKNOWN_SERVICE};
+use crate::ApiError;
+
+/// Event metadata that applies to the whole request: the query parameters
+/// of `/services/collector/raw`, or one HEC event's envelope.
+#[derive(Clone, Default, Deserialize, PartialEq)]
+pub struct Metadata {
+    host: Option<String>,
+    source: Option<String>,
+    sourcetype: Option<String>,
+    index: Option<String>,
+}
+
+/// One event of `/services/collector/event`.
+#[derive(Deserialize)]
+struct Event {
+    /// Epoch seconds, possibly fractional, as a number or a string.
+    time: Option<Value>,
+    #[serde(flatten)]
+    metadata: Metadata,
+    event: Option<Value>,
+    #[serde(default)]
+    fields: serde_json::Map<String, Value>,
+}
+
+/// Translates HEC events, concatenated with or without whitespace between
+/// them, into OTLP logs with one resource per distinct envelope.
+pub fn translate_events(payload: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let now = now_nanos();
+    let mut groups: Vec<(Metadata, Vec<LogRecord>)> = Vec::new();
+    for event in serde_json::Deserializer::from_slice(payload).into_iter::<Event>() {
+        let event =
+            event.map_err(|error| ApiError::bad_request(format!("Invalid HEC event: {error}")))?;
+        let body = match event.event {
+            None | Some(Value::Null) => {
+                return Err(ApiError::bad_request("Event field is required"))
+            }
+            Some(Value::String(text)) if text.is_empty() => {
+                return Err(ApiError::bad_request("Event field cannot be blank"))
+            }
+            Some(body) => body,
+        };
+        let record = LogRecord {
+            time_unix_nano: event.time.as_ref().and_then(time_nanos).unwrap_or(now),
+            observed_time_unix_nano: now,
+            body: Some(any_value(body)),
+            attributes: event
+                .fields
+                .into_iter()
+                .map(|(key, value)| KeyValue {
+                    key,
+                    value: Some(any_value(value)),
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        match groups
+            .iter_mut()
+            .find(|(metadata, _)| *metadata == event.metadata)
+        {
+            Some((_, records)) => records.push(record),
+            None => groups.push((event.metadata, vec![record])),
+        }
+    }
+
+    Ok(to_request(groups).encode_to_vec())
+}
+
+/// Translates a raw HEC body, one event per non-empty line, into OTLP logs
+/// carrying `metadata` from the query string.
+pub fn translate_raw(metadata: &Metadata, payload: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let text = std::str::from_utf8(payload)
+        .map_err(|_| ApiError::bad_request("Raw events must be UTF-8"))?;
+    let now = now_nanos();
+    let records = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| LogRecord {
+            time_unix_nano: now,
+            observed_time_unix_nano: now,
+            body: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(line.to_string())),
+            }),
+            ..Default::default()
+        })
+        .collect();
+
+    Ok(to_request(vec![(metadata.clone(), records)]).encode_to_vec())
+}
+
+fn to_request(groups: Vec<(Metadata, Vec<LogRecord>)>) -> ExportLogsServiceRequest {
+    ExportLogsServiceRequest {
+        resource_logs: groups
+            .into_iter()
+            .filter(|(_, records)| !records.is_empty())
+            .map(|(metadata, log_records)| ResourceLogs {
+                resource: Some(Resource {
+                    attributes: resource_attributes(metadata),
+                    ..Default::default()
+                }),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(InstrumentationScope {
+                        name: "splunk_hec".to_string(),
+                        ..Default::default()
+                    }),
+                    log_records,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+/// Envelope fields map to the attribute names the OpenTelemetry Collector's
+/// Splunk components use.
+fn resource_attributes(metadata: Metadata) -> Vec<KeyValue> {
+    let mut attributes = vec![string_attribute("service.name", UNKNOWN_SERVICE)];
+    for (key, value) in [
+        ("host.name", metadata.host),
+        ("com.splunk.source", metadata.source),
+        ("com.splunk.sourcetype", metadata.sourcetype),
+        ("com.splunk.index", metadata.index),
+    ] {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            attributes.push(string_attribute(key, value));
+        }
+    }
+    attributes
+}
+
+fn time_nanos(time: &Value) -> Option<u64> {
+    let seconds = match time {
+        Value::Number(number) => number.as_f64()?,
+        Value::String(text) => text.trim().parse().ok()?,
+        _ => return None,
+    };
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return None;
+    }
+    // Split before scaling so millisecond fractions survive f64 precision.
+    let nanos = (seconds.fract() * 1e9).round() as u64;
+    Some((seconds.trunc() as u64).saturating_mul(1_000_000_000) + nanos)
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn any_value(value: Value) -> AnyValue {
+    let value = match value {
+        Value::Null => None,
+        Value::Bool(flag) => Some(any_value::Value::BoolValue(flag)),
+        Value::Number(number) => Some(match number.as_i64() {
+            Some(int) => any_value::Value::IntValue(int),
+            None => any_value::Value::DoubleValue(number.as_f64().unwrap_or_default()),
+        }),
+        Value::String(text) => Some(any_value::Value::StringValue(text)),
+        Value::Array(items) => Some(any_value::Value::ArrayValue(ArrayValue {
+            values: items.into_iter().map(any_value).collect(),
+        })),
+        Value::Object(entries) => Some(any_value::Value::KvlistValue(KeyValueList {
+            values: entries
+                .into_iter()
+                .map(|(key, value)| KeyValue {
+                    key,
+                    value: Some(any_value(value)),
+                })
+                .collect(),
+        })),
+    };
+    AnyValue { value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concatenated_events_group_by_envelope() {
+        let payload = br#"{"time": 1700000000.5, "host": "web-1", "sourcetype": "access", "event": "GET / 200"}
+{"time": "1700000001", "host": "web-1", "sourcetype": "access", "event": {"status": 500}, "fields": {"region": "eu"}}{"host": "web-2", "event": "boot"}"#;
+        let request =
+            ExportLogsServiceRequest::decode(translate_events(payload).ok().unwrap().as_slice())
+                .unwrap();
+
+        assert_eq!(request.resource_logs.len(), 2);
+        let attributes = &request.resource_logs[0]
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes;
+        assert!(attributes.contains(&string_attribute("host.name", "web-1")));
+        assert!(attributes.contains(&string_attribute("com.splunk.sourcetype", "access")));
+
+        let records = &request.resource_logs[0].scope_logs[0].log_records;
+        assert_eq!(records[0].time_unix_nano, 1_700_000_000_500_000_000);
+        assert_eq!(records[1].time_unix_nano, 1_700_000_001_000_000_000);
+        assert!(matches!(
+            records[1].body.as_ref().unwrap().value,
+            Some(any_value::Value::KvlistValue(_))
+        ));
+        assert_eq!(records[1].attributes[0], string_attribute("region", "eu"));
+
+        assert!(translate_events(br#"{"host": "web-1"}"#).is_err());
+        assert!(translate_events(br#"{"event": "a"} not json"#).is_err());
+    }
+
+    #[test]
+    fn raw_lines_become_records_with_query_metadata() {
+        let metadata = Metadata {
+            source: Some("/var/log/app.log".to_string()),
+            ..Default::default()
+        };
+        let payload = translate_raw(&metadata, b"first line\n\nsecond line\n")
+            .ok()
+            .unwrap();
+        let request = ExportLogsServiceRequest::decode(payload.as_slice()).unwrap();
+
+        let resource = &request.resource_logs[0];
+        assert!(resource
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes
+            .contains(&string_attribute("com.splunk.source", "/var/log/app.log")));
+        assert_eq!(resource.scope_logs[0].log_records.len(), 2);
+    }
+}
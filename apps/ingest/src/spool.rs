@@ -0,0 +1,775 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::http::StatusCode;
+use bytes::Bytes;
+use metrics::{counter, gauge};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+
+use crate::{forward_enriched, AppState, IngestKeyType, PayloadFormat, ResolvedIngestKey, Signal};
+
+const SEGMENT_EXTENSION: &str = "seg";
+const CURSOR_FILE: &str = "cursor";
+const CURSOR_STAGING_FILE: &str = "cursor.tmp";
+/// Every frame starts with a little-endian `u32` body length and `u32` CRC32.
+const FRAME_PREFIX_BYTES: u64 = 8;
+const REPLAY_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const REPLAY_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub struct SpoolLimits {
+    pub max_bytes: u64,
+    pub max_age: Duration,
+    pub segment_bytes: u64,
+}
+
+/// An enriched, uncompressed payload waiting to be forwarded.
+pub struct SpooledPayload {
+    pub signal: Signal,
+    pub payload_format: PayloadFormat,
+    pub content_encoding: Option<String>,
+    pub resolved_key: ResolvedIngestKey,
+    pub enqueued_at_ms: u64,
+    pub payload: Bytes,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordHeader {
+    signal: String,
+    payload_format: String,
+    content_encoding: Option<String>,
+    org_id: String,
+    key_type: String,
+    key_id: String,
+    enqueued_at_ms: u64,
+}
+
+struct Segment {
+    id: u64,
+    bytes: u64,
+    records: u64,
+}
+
+struct SpoolState {
+    /// Oldest first; the last segment is the one being appended to.
+    segments: VecDeque<Segment>,
+    writer: Option<File>,
+    next_segment_id: u64,
+    /// Replay position inside the front segment.
+    read_offset: u64,
+    pending_bytes: u64,
+    pending_records: u64,
+}
+
+/// Disk-backed FIFO of payloads the collector could not take. Records are
+/// appended to size-capped segment files and replayed in order by
+/// [`spawn_replay`]; delivery is at-least-once across crashes.
+pub struct Spool {
+    dir: PathBuf,
+    limits: SpoolLimits,
+    state: Mutex<SpoolState>,
+    notify: Notify,
+}
+
+impl Spool {
+    pub fn open(dir: &Path, limits: SpoolLimits) -> Result<Self, String> {
+        fs::create_dir_all(dir)
+            .map_err(|error| format!("Failed to create buffer directory: {error}"))?;
+
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir).map_err(|error| error.to_string())? {
+            let path = entry.map_err(|error| error.to_string())?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            if let Some(id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let (cursor_id, cursor_offset) = read_cursor(dir);
+
+        let mut segments = VecDeque::new();
+        let mut read_offset = 0;
+        let mut pending_bytes = 0;
+        let mut pending_records = 0;
+
+        for id in ids {
+            let path = segment_path(dir, id);
+            if id < cursor_id {
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+
+            let skip_to = if id == cursor_id { cursor_offset } else { 0 };
+            let (bytes, records, resume_at) = scan_segment(&path, skip_to)
+                .map_err(|error| format!("Failed to scan buffer segment {id}: {error}"))?;
+
+            if segments.is_empty() {
+                read_offset = resume_at;
+            }
+            let skipped = if segments.is_empty() { read_offset } else { 0 };
+            pending_bytes += bytes - skipped;
+            pending_records += records;
+            segments.push_back(Segment { id, bytes, records });
+        }
+
+        let next_segment_id = segments
+            .back()
+            .map_or(cursor_id, |segment| segment.id + 1)
+            .max(1);
+
+        let writer = match segments.back() {
+            Some(segment) => Some(
+                OpenOptions::new()
+                    .append(true)
+                    .open(segment_path(dir, segment.id))
+                    .map_err(|error| format!("Failed to open buffer segment: {error}"))?,
+            ),
+            None => None,
+        };
+
+        if pending_records > 0 {
+            info!(
+                pending_records,
+                pending_bytes,
+                segments = segments.len(),
+                "Recovered forwarding buffer from disk"
+            );
+        }
+
+        let spool = Self {
+            dir: dir.to_path_buf(),
+            limits,
+            state: Mutex::new(SpoolState {
+                segments,
+                writer,
+                next_segment_id,
+                read_offset,
+                pending_bytes,
+                pending_records,
+            }),
+            notify: Notify::new(),
+        };
+        spool.record_gauges(&spool.state.lock().unwrap());
+
+        Ok(spool)
+    }
+
+    /// Persists a payload, failing when the buffer is at its size cap.
+    pub async fn push(self: &Arc<Self>, payload: SpooledPayload) -> Result<(), String> {
+        let signal = payload.signal;
+        let spool = Arc::clone(self);
+        tokio::task::spawn_blocking(move || spool.append(&payload))
+            .await
+            .map_err(|error| error.to_string())??;
+
+        counter!("ingest_buffer_enqueued_total", "signal" => signal.path()).increment(1);
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    fn append(&self, payload: &SpooledPayload) -> Result<(), String> {
+        let frame = encode_frame(payload)?;
+        let frame_len = frame.len() as u64;
+
+        let mut state = self.state.lock().unwrap();
+        if state.pending_bytes + frame_len > self.limits.max_bytes {
+            counter!("ingest_buffer_rejected_total", "reason" => "full").increment(1);
+            return Err("Forwarding buffer is full".to_string());
+        }
+
+        let rotate = match state.segments.back() {
+            Some(segment) => {
+                segment.bytes > 0 && segment.bytes + frame_len > self.limits.segment_bytes
+            }
+            None => true,
+        };
+        if rotate {
+            let id = state.next_segment_id;
+            let file = OpenOptions::new()
+                .create_new(true)
+                .append(true)
+                .open(segment_path(&self.dir, id))
+                .map_err(|error| format!("Failed to create buffer segment: {error}"))?;
+            state.next_segment_id = id + 1;
+            state.segments.push_back(Segment {
+                id,
+                bytes: 0,
+                records: 0,
+            });
+            state.writer = Some(file);
+        }
+
+        let Some(writer) = state.writer.as_mut() else {
+            return Err("Forwarding buffer has no open segment".to_string());
+        };
+        let written = writer.write_all(&frame).and_then(|()| writer.sync_data());
+        if let Err(error) = written {
+            // Drop the torn frame so replay never sees it.
+            let valid_len = state.segments.back().map_or(0, |segment| segment.bytes);
+            if let Some(writer) = state.writer.as_mut() {
+                let _ = writer.set_len(valid_len);
+            }
+            return Err(format!("Failed to write buffer segment: {error}"));
+        }
+
+        if let Some(segment) = state.segments.back_mut() {
+            segment.bytes += frame_len;
+            segment.records += 1;
+        }
+        state.pending_bytes += frame_len;
+        state.pending_records += 1;
+        self.record_gauges(&state);
+
+        Ok(())
+    }
+
+    /// Reads the oldest pending record without removing it. Returns the record
+    /// and its frame length, which must be passed back to [`Spool::ack`].
+    fn peek(&self) -> Result<Option<(SpooledPayload, u64)>, String> {
+        let mut cursor = None;
+        let next = self.peek_locked(&mut cursor);
+        if let Some((id, offset)) = cursor {
+            write_cursor(&self.dir, id, offset);
+        }
+        next
+    }
+
+    fn peek_locked(
+        &self,
+        cursor: &mut Option<(u64, u64)>,
+    ) -> Result<Option<(SpooledPayload, u64)>, String> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let Some(front) = state.segments.front() else {
+                return Ok(None);
+            };
+            let (front_id, front_bytes, front_records) = (front.id, front.bytes, front.records);
+
+            if state.read_offset >= front_bytes {
+                if state.segments.len() == 1 {
+                    return Ok(None);
+                }
+                *cursor = Some(self.pop_front_segment(&mut state));
+                continue;
+            }
+
+            let path = segment_path(&self.dir, front_id);
+            match read_frame(&path, state.read_offset) {
+                Ok((payload, frame_len)) => return Ok(Some((payload, frame_len))),
+                Err(error) => {
+                    // Unreadable data cannot become readable later; skip the
+                    // rest of the segment instead of wedging replay on it.
+                    error!(
+                        segment = front_id,
+                        offset = state.read_offset,
+                        error = %error,
+                        "Skipping unreadable forwarding buffer segment"
+                    );
+                    counter!("ingest_buffer_dropped_total", "reason" => "corrupt")
+                        .increment(front_records);
+                    state.pending_bytes -= front_bytes - state.read_offset;
+                    state.pending_records -= front_records;
+                    state.read_offset = front_bytes;
+                    if let Some(front) = state.segments.front_mut() {
+                        front.records = 0;
+                    }
+                    self.record_gauges(&state);
+                }
+            }
+        }
+    }
+
+    /// Marks the record returned by the last [`Spool::peek`] as done. Blocks
+    /// on the cursor write, but not while holding the state lock.
+    fn ack(&self, frame_len: u64) {
+        let cursor = {
+            let mut state = self.state.lock().unwrap();
+            state.read_offset += frame_len;
+            state.pending_bytes = state.pending_bytes.saturating_sub(frame_len);
+            state.pending_records = state.pending_records.saturating_sub(1);
+            if let Some(front) = state.segments.front_mut() {
+                front.records = front.records.saturating_sub(1);
+            }
+
+            let drained = state
+                .segments
+                .front()
+                .is_some_and(|front| state.read_offset >= front.bytes);
+            let cursor = if drained {
+                Some(self.pop_front_segment(&mut state))
+            } else {
+                state
+                    .segments
+                    .front()
+                    .map(|front| (front.id, state.read_offset))
+            };
+
+            self.record_gauges(&state);
+            cursor
+        };
+
+        if let Some((id, offset)) = cursor {
+            write_cursor(&self.dir, id, offset);
+        }
+    }
+
+    /// Drops the drained front segment and returns the cursor to persist.
+    fn pop_front_segment(&self, state: &mut SpoolState) -> (u64, u64) {
+        let Some(front) = state.segments.pop_front() else {
+            return (state.next_segment_id, 0);
+        };
+        if state.segments.is_empty() {
+            state.writer = None;
+        }
+        if let Err(error) = fs::remove_file(segment_path(&self.dir, front.id)) {
+            warn!(segment = front.id, error = %error, "Failed to remove drained buffer segment");
+        }
+        state.read_offset = 0;
+        let next_id = state
+            .segments
+            .front()
+            .map_or(front.id + 1, |segment| segment.id);
+        (next_id, 0)
+    }
+
+    fn record_gauges(&self, state: &SpoolState) {
+        gauge!("ingest_buffer_bytes").set(state.pending_bytes as f64);
+        gauge!("ingest_buffer_records").set(state.pending_records as f64);
+        gauge!("ingest_buffer_segments").set(state.segments.len() as f64);
+    }
+}
+
+/// Replays buffered payloads in order, backing off while the collector keeps
+/// failing. Records older than the age cap are dropped unsent.
+pub fn spawn_replay(spool: Arc<Spool>, state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut backoff = REPLAY_MIN_BACKOFF;
+
+        loop {
+            let reader = Arc::clone(&spool);
+            let next = tokio::task::spawn_blocking(move || reader.peek())
+                .await
+                .map_err(|error| error.to_string())
+                .and_then(|result| result);
+
+            let (record, frame_len) = match next {
+                Ok(Some(next)) => next,
+                Ok(None) => {
+                    gauge!("ingest_buffer_replay_lag_seconds").set(0.0);
+                    spool.notify.notified().await;
+                    continue;
+                }
+                Err(error) => {
+                    error!(error = %error, "Failed to read forwarding buffer");
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(REPLAY_MAX_BACKOFF);
+                    continue;
+                }
+            };
+
+            let age = Duration::from_millis(unix_millis().saturating_sub(record.enqueued_at_ms));
+            gauge!("ingest_buffer_replay_lag_seconds").set(age.as_secs_f64());
+
+            if age > spool.limits.max_age {
+                counter!("ingest_buffer_dropped_total", "reason" => "expired").increment(1);
+                warn!(
+                    signal = record.signal.path(),
+                    org_id = %record.resolved_key.org_id,
+                    age_secs = age.as_secs(),
+                    "Dropping expired buffered payload"
+                );
+                acknowledge(&spool, frame_len).await;
+                continue;
+            }
+
+            let result = forward_enriched(
+                &state,
+                record.signal,
+                record.payload_format,
+                record.content_encoding.as_deref(),
+                record.payload,
+                &record.resolved_key,
+            )
+            .await;
+
+            let outcome = match &result {
+                Ok(response) if response.status().is_success() => "ok",
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => "retry",
+                Ok(_) => "rejected",
+                Err(_) => "retry",
+            };
+            counter!(
+                "ingest_buffer_replayed_total",
+                "signal" => record.signal.path(),
+                "outcome" => outcome
+            )
+            .increment(1);
+
+            match outcome {
+                "retry" => {
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(REPLAY_MAX_BACKOFF);
+                }
+                "rejected" => {
+                    warn!(
+                        signal = record.signal.path(),
+                        org_id = %record.resolved_key.org_id,
+                        status = result.map(|response| response.status().as_u16()).unwrap_or_default(),
+                        "Collector rejected buffered payload, dropping it"
+                    );
+                    acknowledge(&spool, frame_len).await;
+                }
+                _ => {
+                    backoff = REPLAY_MIN_BACKOFF;
+                    acknowledge(&spool, frame_len).await;
+                }
+            }
+        }
+    });
+}
+
+/// Runs [`Spool::ack`] on the blocking pool, since it writes the cursor file.
+async fn acknowledge(spool: &Arc<Spool>, frame_len: u64) {
+    let spool = Arc::clone(spool);
+    if let Err(error) = tokio::task::spawn_blocking(move || spool.ack(frame_len)).await {
+        error!(error = %error, "Failed to acknowledge buffered payload");
+    }
+}
+
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn read_cursor(dir: &Path) -> (u64, u64) {
+    let Ok(raw) = fs::read_to_string(dir.join(CURSOR_FILE)) else {
+        return (0, 0);
+    };
+    let mut parts = raw.split_whitespace().map(|part| part.parse::<u64>().ok());
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(id), Some(offset)) => (id, offset),
+        _ => (0, 0),
+    }
+}
+
+/// Writes the cursor beside the live one and renames it into place, so a
+/// crash leaves either the old cursor or the new one. The staged file is
+/// synced before the rename and the directory after it, so the rename cannot
+/// reach disk ahead of the cursor's contents.
+fn write_cursor(dir: &Path, id: u64, offset: u64) {
+    let staged = dir.join(CURSOR_STAGING_FILE);
+    let written = File::create(&staged)
+        .and_then(|mut file| {
+            file.write_all(format!("{id} {offset}\n").as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&staged, dir.join(CURSOR_FILE)))
+        .and_then(|()| File::open(dir)?.sync_all());
+    if let Err(error) = written {
+        warn!(error = %error, "Failed to persist forwarding buffer cursor");
+    }
+}
+
+/// Returns the valid length of a segment, how many records it holds from the
+/// resume offset on, and that offset: the last frame boundary at or before
+/// `skip_to`. Truncates any torn frame left by a crash mid-write.
+fn scan_segment(path: &Path, skip_to: u64) -> io::Result<(u64, u64, u64)> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut offset = 0;
+    let mut records = 0;
+    let mut resume_at = 0;
+    let mut skipped = 0;
+
+    while offset + FRAME_PREFIX_BYTES <= file_len {
+        let mut prefix = [0u8; FRAME_PREFIX_BYTES as usize];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut prefix)?;
+        let body_len = u32::from_le_bytes(prefix[..4].try_into().unwrap()) as u64;
+        let crc = u32::from_le_bytes(prefix[4..].try_into().unwrap());
+
+        let frame_end = offset + FRAME_PREFIX_BYTES + body_len;
+        if frame_end > file_len {
+            break;
+        }
+        let mut body = vec![0u8; body_len as usize];
+        file.read_exact(&mut body)?;
+        if crc32fast::hash(&body) != crc {
+            break;
+        }
+
+        records += 1;
+        offset = frame_end;
+        if offset <= skip_to {
+            resume_at = offset;
+            skipped = records;
+        }
+    }
+
+    if offset < file_len {
+        warn!(
+            path = %path.display(),
+            valid_bytes = offset,
+            file_bytes = file_len,
+            "Truncating torn frame in forwarding buffer segment"
+        );
+        file.set_len(offset)?;
+    }
+
+    Ok((offset, records - skipped, resume_at))
+}
+
+fn encode_frame(payload: &SpooledPayload) -> Result<Vec<u8>, String> {
+    let header = RecordHeader {
+        signal: payload.signal.path().to_string(),
+        payload_format: payload.payload_format.label().to_string(),
+        content_encoding: payload.content_encoding.clone(),
+        org_id: payload.resolved_key.org_id.clone(),
+        key_type: payload.resolved_key.key_type.as_str().to_string(),
+        key_id: payload.resolved_key.key_id.clone(),
+        enqueued_at_ms: payload.enqueued_at_ms,
+    };
+    let header = serde_json::to_vec(&header).map_err(|error| error.to_string())?;
+
+    let mut body = Vec::with_capacity(4 + header.len() + payload.payload.len());
+    body.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    body.extend_from_slice(&header);
+    body.extend_from_slice(&payload.payload);
+
+    let body_len =
+        u32::try_from(body.len()).map_err(|_| "Payload too large to buffer".to_string())?;
+    let mut frame = Vec::with_capacity(FRAME_PREFIX_BYTES as usize + body.len());
+    frame.extend_from_slice(&body_len.to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+    frame.extend_from_slice(&body);
+    Ok(frame)
+}
+
+fn read_frame(path: &Path, offset: u64) -> Result<(SpooledPayload, u64), String> {
+    let mut file = File::open(path).map_err(|error| error.to_string())?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|error| error.to_string())?;
+
+    let mut prefix = [0u8; FRAME_PREFIX_BYTES as usize];
+    file.read_exact(&mut prefix)
+        .map_err(|error| error.to_string())?;
+    let body_len = u32::from_le_bytes(prefix[..4].try_into().unwrap()) as usize;
+    let crc = u32::from_le_bytes(prefix[4..].try_into().unwrap());
+
+    let mut body = vec![0u8; body_len];
+    file.read_exact(&mut body)
+        .map_err(|error| error.to_string())?;
+    if crc32fast::hash(&body) != crc {
+        return Err("checksum mismatch".to_string());
+    }
+
+    let payload = decode_frame_body(body)?;
+    Ok((payload, FRAME_PREFIX_BYTES + body_len as u64))
+}
+
+fn decode_frame_body(body: Vec<u8>) -> Result<SpooledPayload, String> {
+    if body.len() < 4 {
+        return Err("truncated record".to_string());
+    }
+    let header_len = u32::from_le_bytes(body[..4].try_into().unwrap()) as usize;
+    if body.len() < 4 + header_len {
+        return Err("truncated record header".to_string());
+    }
+
+    let header: RecordHeader =
+        serde_json::from_slice(&body[4..4 + header_len]).map_err(|error| error.to_string())?;
+
+    let signal = match header.signal.as_str() {
+        "traces" => Signal::Traces,
+        "logs" => Signal::Logs,
+        "metrics" => Signal::Metrics,
+        other => return Err(format!("unknown signal {other}")),
+    };
+    let payload_format = match header.payload_format.as_str() {
+        "protobuf" => PayloadFormat::Protobuf,
+        "json" => PayloadFormat::Json,
+        other => return Err(format!("unknown payload format {other}")),
+    };
+    let key_type = match header.key_type.as_str() {
+        "public" => IngestKeyType::Public,
+        "private" => IngestKeyType::Private,
+        other => return Err(format!("unknown key type {other}")),
+    };
+
+    let payload = Bytes::from(body).slice(4 + header_len..);
+
+    Ok(SpooledPayload {
+        signal,
+        payload_format,
+        content_encoding: header.content_encoding,
+        resolved_key: ResolvedIngestKey {
+            org_id: header.org_id,
+            key_type,
+            key_id: header.key_id,
+            allowed_origins: None,
+        },
+        enqueued_at_ms: header.enqueued_at_ms,
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("maple-ingest-spool-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn limits() -> SpoolLimits {
+        SpoolLimits {
+            max_bytes: 1024 * 1024,
+            max_age: Duration::from_secs(60),
+            segment_bytes: 256,
+        }
+    }
+
+    fn payload(body: &'static [u8]) -> SpooledPayload {
+        SpooledPayload {
+            signal: Signal::Logs,
+            payload_format: PayloadFormat::Protobuf,
+            content_encoding: None,
+            resolved_key: ResolvedIngestKey {
+                org_id: "org_a".to_string(),
+                key_type: IngestKeyType::Private,
+                key_id: "abc".to_string(),
+                allowed_origins: None,
+            },
+            enqueued_at_ms: 1,
+            payload: Bytes::from_static(body),
+        }
+    }
+
+    #[test]
+    fn replays_in_order_across_segments_and_restarts() {
+        let dir = test_dir("order");
+        let spool = Spool::open(&dir, limits()).unwrap();
+        for body in [&b"first"[..], b"second", b"third", b"fourth"] {
+            let mut record = payload(b"");
+            record.payload = Bytes::copy_from_slice(body);
+            spool.append(&record).unwrap();
+        }
+
+        let (record, frame_len) = spool.peek().unwrap().unwrap();
+        assert_eq!(record.payload.as_ref(), b"first");
+        spool.ack(frame_len);
+        drop(spool);
+
+        let spool = Spool::open(&dir, limits()).unwrap();
+        let mut replayed = Vec::new();
+        while let Some((record, frame_len)) = spool.peek().unwrap() {
+            replayed.push(String::from_utf8(record.payload.to_vec()).unwrap());
+            spool.ack(frame_len);
+        }
+        assert_eq!(replayed, ["second", "third", "fourth"]);
+        assert_eq!(spool.state.lock().unwrap().pending_records, 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn segment_ids_keep_increasing_after_the_buffer_drains() {
+        let dir = test_dir("drain");
+        let spool = Spool::open(&dir, limits()).unwrap();
+        spool.append(&payload(b"one")).unwrap();
+        let (_, frame_len) = spool.peek().unwrap().unwrap();
+        spool.ack(frame_len);
+        spool.append(&payload(b"two")).unwrap();
+        drop(spool);
+
+        let spool = Spool::open(&dir, limits()).unwrap();
+        let (record, _) = spool.peek().unwrap().unwrap();
+        assert_eq!(record.payload.as_ref(), b"two");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn torn_tail_is_truncated_on_open() {
+        let dir = test_dir("torn");
+        let spool = Spool::open(&dir, limits()).unwrap();
+        spool.append(&payload(b"kept")).unwrap();
+        drop(spool);
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(segment_path(&dir, 1))
+            .unwrap();
+        file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let spool = Spool::open(&dir, limits()).unwrap();
+        let (record, frame_len) = spool.peek().unwrap().unwrap();
+        assert_eq!(record.payload.as_ref(), b"kept");
+        spool.ack(frame_len);
+        assert!(spool.peek().unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn cursor_inside_a_frame_resumes_at_that_frame() {
+        let dir = test_dir("cursor");
+        let limits = || SpoolLimits {
+            segment_bytes: 64 * 1024,
+            ..limits()
+        };
+        let spool = Spool::open(&dir, limits()).unwrap();
+        spool.append(&payload(b"first")).unwrap();
+        spool.append(&payload(b"second")).unwrap();
+        let (_, frame_len) = spool.peek().unwrap().unwrap();
+        drop(spool);
+
+        // As if a crash tore the cursor file mid-write.
+        fs::write(dir.join(CURSOR_FILE), format!("1 {}\n", frame_len + 3)).unwrap();
+
+        let spool = Spool::open(&dir, limits()).unwrap();
+        assert_eq!(spool.state.lock().unwrap().pending_records, 1);
+        let (record, _) = spool.peek().unwrap().unwrap();
+        assert_eq!(record.payload.as_ref(), b"second");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_appends_over_the_size_cap() {
+        let dir = test_dir("full");
+        let spool = Spool::open(
+            &dir,
+            SpoolLimits {
+                max_bytes: 64,
+                ..limits()
+            },
+        )
+        .unwrap();
+        assert!(spool.append(&payload(&[0; 128])).is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,304 @@
+const MAX_DEPTH: usize = 32;
+/// Collections larger than this grow as their elements are read.
+const MAX_RESERVED_ELEMENTS: usize = 1024;
+
+/// A Thrift value decoded without a schema; receivers map structs onto their
+/// own types by field id. All integer widths are widened to `i64`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    Binary(Vec<u8>),
+    Struct(Vec<(i16, Value)>),
+    List(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    pub fn field(&self, id: i16) -> Option<&Value> {
+        match self {
+            Self::Struct(fields) => fields
+                .iter()
+                .find(|(field_id, _)| *field_id == id)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Binary(bytes) => std::str::from_utf8(bytes).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> &[Value] {
+        match self {
+            Self::List(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Protocol {
+    Binary,
+    Compact,
+}
+
+/// Decodes one top-level struct, as written by `TSerializer` without a
+/// message envelope.
+pub fn decode_struct(protocol: Protocol, payload: &[u8]) -> Result<Value, String> {
+    let mut reader = Reader {
+        payload,
+        position: 0,
+        depth: 0,
+    };
+    match protocol {
+        Protocol::Binary => reader.binary_struct(),
+        Protocol::Compact => reader.compact_struct(),
+    }
+}
+
+struct Reader<'a> {
+    payload: &'a [u8],
+    position: usize,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], String> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.payload.len())
+            .ok_or_else(|| "unexpected end of payload".to_string())?;
+        let bytes = &self.payload[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    /// Collection sizes come from the wire; never reserve more elements than
+    /// there are bytes left, nor more than `MAX_RESERVED_ELEMENTS` up front.
+    fn capacity(&self, size: usize) -> usize {
+        size.min(self.payload.len() - self.position)
+            .min(MAX_RESERVED_ELEMENTS)
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("nesting too deep".to_string());
+        }
+        Ok(())
+    }
+
+    fn binary_size(&mut self) -> Result<usize, String> {
+        usize::try_from(i32::from_be_bytes(self.array()?)).map_err(|_| "negative size".to_string())
+    }
+
+    fn binary_struct(&mut self) -> Result<Value, String> {
+        self.enter()?;
+        let mut fields = Vec::new();
+        loop {
+            let field_type = self.byte()?;
+            if field_type == 0 {
+                break;
+            }
+            let id = i16::from_be_bytes(self.array()?);
+            fields.push((id, self.binary_value(field_type)?));
+        }
+        self.depth -= 1;
+        Ok(Value::Struct(fields))
+    }
+
+    fn binary_value(&mut self, value_type: u8) -> Result<Value, String> {
+        Ok(match value_type {
+            2 => Value::Bool(self.byte()? != 0),
+            3 => Value::Int(self.byte()? as i8 as i64),
+            4 => Value::Double(f64::from_be_bytes(self.array()?)),
+            6 => Value::Int(i16::from_be_bytes(self.array()?) as i64),
+            8 => Value::Int(i32::from_be_bytes(self.array()?) as i64),
+            10 => Value::Int(i64::from_be_bytes(self.array()?)),
+            11 => {
+                let len = self.binary_size()?;
+                Value::Binary(self.take(len)?.to_vec())
+            }
+            12 => self.binary_struct()?,
+            13 => {
+                self.enter()?;
+                let key_type = self.byte()?;
+                let value_type = self.byte()?;
+                let size = self.binary_size()?;
+                let mut entries = Vec::with_capacity(self.capacity(size));
+                for _ in 0..size {
+                    entries.push((self.binary_value(key_type)?, self.binary_value(value_type)?));
+                }
+                self.depth -= 1;
+                Value::Map(entries)
+            }
+            14 | 15 => {
+                self.enter()?;
+                let element_type = self.byte()?;
+                let size = self.binary_size()?;
+                let mut items = Vec::with_capacity(self.capacity(size));
+                for _ in 0..size {
+                    items.push(self.binary_value(element_type)?);
+                }
+                self.depth -= 1;
+                Value::List(items)
+            }
+            other => return Err(format!("unknown binary type {other}")),
+        })
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("varint too long".to_string())
+    }
+
+    fn zigzag(&mut self) -> Result<i64, String> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn compact_size(&mut self) -> Result<usize, String> {
+        usize::try_from(self.varint()?).map_err(|_| "size too large".to_string())
+    }
+
+    fn compact_struct(&mut self) -> Result<Value, String> {
+        self.enter()?;
+        let mut fields = Vec::new();
+        let mut last_id = 0i16;
+        loop {
+            let header = self.byte()?;
+            if header == 0 {
+                break;
+            }
+            let delta = header >> 4;
+            let id = if delta == 0 {
+                self.zigzag()? as i16
+            } else {
+                last_id.wrapping_add(delta as i16)
+            };
+            last_id = id;
+            let value = match header & 0x0f {
+                1 => Value::Bool(true),
+                2 => Value::Bool(false),
+                field_type => self.compact_value(field_type)?,
+            };
+            fields.push((id, value));
+        }
+        self.depth -= 1;
+        Ok(Value::Struct(fields))
+    }
+
+    fn compact_value(&mut self, value_type: u8) -> Result<Value, String> {
+        Ok(match value_type {
+            // Collection elements carry bools as a byte of their own.
+            1 | 2 => Value::Bool(self.byte()? == 1),
+            3 => Value::Int(self.byte()? as i8 as i64),
+            4..=6 => Value::Int(self.zigzag()?),
+            7 => Value::Double(f64::from_le_bytes(self.array()?)),
+            8 => {
+                let len = self.compact_size()?;
+                Value::Binary(self.take(len)?.to_vec())
+            }
+            9 | 10 => {
+                self.enter()?;
+                let header = self.byte()?;
+                let size = match header >> 4 {
+                    15 => self.compact_size()?,
+                    size => size as usize,
+                };
+                let mut items = Vec::with_capacity(self.capacity(size));
+                for _ in 0..size {
+                    items.push(self.compact_value(header & 0x0f)?);
+                }
+                self.depth -= 1;
+                Value::List(items)
+            }
+            11 => {
+                self.enter()?;
+                let size = self.compact_size()?;
+                let mut entries = Vec::with_capacity(self.capacity(size));
+                if size > 0 {
+                    let types = self.byte()?;
+                    for _ in 0..size {
+                        entries.push((
+                            self.compact_value(types >> 4)?,
+                            self.compact_value(types & 0x0f)?,
+                        ));
+                    }
+                }
+                self.depth -= 1;
+                Value::Map(entries)
+            }
+            12 => self.compact_struct()?,
+            other => return Err(format!("unknown compact type {other}")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_structs_lists_and_scalars_decode() {
+        let mut payload = vec![11, 0, 1, 0, 0, 0, 3];
+        payload.extend_from_slice(b"svc");
+        payload.extend_from_slice(&[10, 0, 2]);
+        payload.extend_from_slice(&(-5i64).to_be_bytes());
+        payload.extend_from_slice(&[15, 0, 3, 12, 0, 0, 0, 1, 2, 0, 1, 1, 0]);
+        payload.push(0);
+
+        let value = decode_struct(Protocol::Binary, &payload).unwrap();
+        assert_eq!(value.field(1).and_then(Value::as_str), Some("svc"));
+        assert_eq!(value.field(2).and_then(Value::as_int), Some(-5));
+        let items = value.field(3).unwrap().as_list();
+        assert_eq!(items[0].field(1), Some(&Value::Bool(true)));
+
+        assert!(decode_struct(Protocol::Binary, &payload[..payload.len() - 3]).is_err());
+    }
+
+    #[test]
+    fn compact_field_deltas_zigzag_and_bools_decode() {
+        // field 1 string "ab", field 2 i64 -3, field 5 bool true, field 6 list<i32> [1, -1]
+        let payload = [0x18, 2, b'a', b'b', 0x16, 5, 0x31, 0x19, 0x25, 2, 1, 0];
+        let value = decode_struct(Protocol::Compact, &payload).unwrap();
+        assert_eq!(value.field(1).and_then(Value::as_str), Some("ab"));
+        assert_eq!(value.field(2).and_then(Value::as_int), Some(-3));
+        assert_eq!(value.field(5), Some(&Value::Bool(true)));
+        assert_eq!(
+            value.field(6).unwrap().as_list(),
+            &[Value::Int(1), Value::Int(-1)]
+        );
+
+        let deep = [0x1c; 64];
+        assert!(decode_struct(Protocol::Compact, &deep).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use metrics::counter;
+use opentelemetry_proto::tonic::collector::logs::v1::{
+    ExportLogsPartialSuccess, ExportLogsServiceRequest, ExportLogsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::metrics::v1::{
+    ExportMetricsPartialSuccess, ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+};
+use opentelemetry_proto::tonic::collector::trace::v1::{
+    ExportTracePartialSuccess, ExportTraceServiceRequest, ExportTraceServiceResponse,
+};
+use opentelemetry_proto::tonic::metrics::v1::{metric, Metric};
+use prost::Message;
+
+use crate::{PayloadFormat, Signal};
+
+/// Items dropped from a request, counted per reason so the partial-success
+/// message can tell the client what to fix.
+#[derive(Default, Debug)]
+pub struct Rejections {
+    pub count: i64,
+    reasons: BTreeMap<&'static str, i64>,
+}
+
+impl Rejections {
+    fn add(&mut self, reason: &'static str, items: usize) {
+        if items == 0 {
+            return;
+        }
+        self.count += items as i64;
+        *self.reasons.entry(reason).or_default() += items as i64;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn record(&self, signal: Signal) {
+        for (reason, items) in &self.reasons {
+            counter!(
+                "ingest_items_rejected_total",
+                "signal" => signal.path(),
+                "reason" => *reason
+            )
+            .increment(*items as u64);
+        }
+    }
+
+    fn message(&self, signal: Signal) -> String {
+        let reasons: Vec<String> = self
+            .reasons
+            .iter()
+            .map(|(reason, items)| format!("{items} {reason}"))
+            .collect();
+        let items = match signal {
+            Signal::Traces => "spans",
+            Signal::Logs => "log records",
+            Signal::Metrics => "data points",
+        };
+        format!(
+            "maple-ingest dropped {} {items}: {}",
+            self.count,
+            reasons.join(", ")
+        )
+    }
+}
+
+fn valid_id(id: &[u8], len: usize) -> bool {
+    id.len() == len && id.iter().any(|byte| *byte != 0)
+}
+
+/// Optional IDs (a log's trace context, a root span's parent) may be empty.
+fn valid_optional_id(id: &[u8], len: usize) -> bool {
+    id.is_empty() || valid_id(id, len)
+}
+
+pub fn validate_traces(request: &mut ExportTraceServiceRequest) -> Rejections {
+    let mut rejections = Rejections::default();
+    for scope_spans in request
+        .resource_spans
+        .iter_mut()
+        .flat_map(|resource_spans| &mut resource_spans.scope_spans)
+    {
+        scope_spans.spans.retain(|span| {
+            let reason = if !valid_id(&span.trace_id, 16) {
+                "invalid trace_id"
+            } else if !valid_id(&span.span_id, 8) {
+                "invalid span_id"
+            } else if !valid_optional_id(&span.parent_span_id, 8) {
+                "invalid parent_span_id"
+            } else {
+                return true;
+            };
+            rejections.add(reason, 1);
+            false
+        });
+    }
+    rejections
+}
+
+/// `max_body_bytes` of `0` leaves log bodies unbounded.
+pub fn validate_logs(request: &mut ExportLogsServiceRequest, max_body_bytes: usize) -> Rejections {
+    let mut rejections = Rejections::default();
+    for scope_logs in request
+        .resource_logs
+        .iter_mut()
+        .flat_map(|resource_logs| &mut resource_logs.scope_logs)
+    {
+        scope_logs.log_records.retain(|record| {
+            let reason = if !valid_optional_id(&record.trace_id, 16) {
+                "invalid trace_id"
+            } else if !valid_optional_id(&record.span_id, 8) {
+                "invalid span_id"
+            } else if max_body_bytes > 0
+                && record
+                    .body
+                    .as_ref()
+                    .is_some_and(|body| body.encoded_len() > max_body_bytes)
+            {
+                "oversized body"
+            } else {
+                return true;
+            };
+            rejections.add(reason, 1);
+            false
+        });
+    }
+    rejections
+}
+
+pub fn validate_metrics(request: &mut ExportMetricsServiceRequest) -> Rejections {
+    let mut rejections = Rejections::default();
+    for scope_metrics in request
+        .resource_metrics
+        .iter_mut()
+        .flat_map(|resource_metrics| &mut resource_metrics.scope_metrics)
+    {
+        scope_metrics.metrics.retain_mut(|metric| {
+            if metric.name.is_empty() {
+                rejections.add("unnamed metric", data_point_count(metric));
+                return false;
+            }
+            if let Some(metric::Data::Histogram(histogram)) = &mut metric.data {
+                let before = histogram.data_points.len();
+                // Bucket counts must line up with the bounds they are split by.
+                histogram.data_points.retain(|point| {
+                    point.bucket_counts.is_empty()
+                        || point.bucket_counts.len() == point.explicit_bounds.len() + 1
+                });
+                rejections.add(
+                    "mismatched histogram buckets",
+                    before - histogram.data_points.len(),
+                );
+            }
+            true
+        });
+    }
+    rejections
+}
+
+fn data_point_count(metric: &Metric) -> usize {
+    match &metric.data {
+        Some(metric::Data::Gauge(gauge)) => gauge.data_points.len(),
+        Some(metric::Data::Sum(sum)) => sum.data_points.len(),
+        Some(metric::Data::Histogram(histogram)) => histogram.data_points.len(),
+        Some(metric::Data::ExponentialHistogram(histogram)) => histogram.data_points.len(),
+        Some(metric::Data::Summary(summary)) => summary.data_points.len(),
+        None => 0,
+    }
+}
+
+/// Adds the gateway's rejections to the collector's `Export*ServiceResponse`
+/// (which may already report its own) and re-encodes it like the request.
+/// An empty or unreadable `upstream` body is treated as a full success.
+pub fn partial_success_body(
+    signal: Signal,
+    payload_format: PayloadFormat,
+    upstream: &[u8],
+    rejections: &Rejections,
+) -> Vec<u8> {
+    let message = rejections.message(signal);
+    let merge = |rejected: &mut i64, error_message: &mut String| {
+        *rejected += rejections.count;
+        *error_message = if error_message.is_empty() {
+            message.clone()
+        } else {
+            format!("{error_message}; {message}")
+        };
+    };
+
+    match signal {
+        Signal::Traces => {
+            let mut response: ExportTraceServiceResponse = decode(payload_format, upstream);
+            let partial = response
+                .partial_success
+                .get_or_insert_with(ExportTracePartialSuccess::default);
+            merge(&mut partial.rejected_spans, &mut partial.error_message);
+            encode(payload_format, &response)
+        }
+        Signal::Logs => {
+            let mut response: ExportLogsServiceResponse = decode(payload_format, upstream);
+            let partial = response
+                .partial_success
+                .get_or_insert_with(ExportLogsPartialSuccess::default);
+            merge(
+                &mut partial.rejected_log_records,
+                &mut partial.error_message,
+            );
+            encode(payload_format, &response)
+        }
+        Signal::Metrics => {
+            let mut response: ExportMetricsServiceResponse = decode(payload_format, upstream);
+            let partial = response
+                .partial_success
+                .get_or_insert_with(ExportMetricsPartialSuccess::default);
+            merge(
+                &mut partial.rejected_data_points,
+                &mut partial.error_message,
+            );
+            encode(payload_format, &response)
+        }
+    }
+}
+
+fn decode<T>(payload_format: PayloadFormat, body: &[u8]) -> T
+where
+    T: Message + Default + serde::de::DeserializeOwned,
+{
+    match payload_format {
+        PayloadFormat::Protobuf => T::decode(body).unwrap_or_default(),
+        PayloadFormat::Json => serde_json::from_slice(body).unwrap_or_default(),
+    }
+}
+
+fn encode<T>(payload_format: PayloadFormat, response: &T) -> Vec<u8>
+where
+    T: Message + serde::Serialize,
+{
+    match payload_format {
+        PayloadFormat::Protobuf => response.encode_to_vec(),
+        PayloadFormat::Json => serde_json::to_vec(response).unwrap_or_else(|_| b"{}".to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span};
+
+    fn span(trace_id: Vec<u8>, span_id: Vec<u8>) -> Span {
+        Span {
+            trace_id,
+            span_id,
+            name: "op".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn malformed_spans_are_dropped_and_counted() {
+        let mut request = ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![
+                        span(vec![1; 16], vec![2; 8]),
+                        span(vec![1; 8], vec![2; 8]),
+                        span(vec![0; 16], vec![2; 8]),
+                        span(vec![1; 16], Vec::new()),
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let rejections = validate_traces(&mut request);
+        assert_eq!(rejections.count, 3);
+        assert_eq!(rejections.reasons["invalid trace_id"], 2);
+        assert_eq!(rejections.reasons["invalid span_id"], 1);
+        assert_eq!(request.resource_spans[0].scope_spans[0].spans.len(), 1);
+    }
+
+    #[test]
+    fn partial_success_merges_with_the_collector_response() {
+        let mut rejections = Rejections::default();
+        rejections.add("invalid trace_id", 2);
+
+        let upstream =
+            br#"{"partialSuccess":{"rejectedSpans":1,"errorMessage":"collector said no"}}"#;
+        let body = partial_success_body(Signal::Traces, PayloadFormat::Json, upstream, &rejections);
+        let response: ExportTraceServiceResponse = serde_json::from_slice(&body).unwrap();
+        let partial = response.partial_success.unwrap();
+        assert_eq!(partial.rejected_spans, 3);
+        assert!(partial.error_message.starts_with("collector said no; "));
+
+        let body = partial_success_body(Signal::Traces, PayloadFormat::Protobuf, &[], &rejections);
+        let response = ExportTraceServiceResponse::decode(body.as_slice()).unwrap();
+        assert_eq!(response.partial_success.unwrap().rejected_spans, 2);
+    }
+}
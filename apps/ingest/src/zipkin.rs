@@ -0,0 +1,525 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::tonic::common::v1::InstrumentationScope;
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use opentelemetry_proto::tonic::trace::v1::span::{Event, SpanKind};
+use opentelemetry_proto::tonic::trace::v1::status::StatusCode;
+use opentelemetry_proto::tonic::trace::v1::{ResourceSpans, ScopeSpans, Span, Status};
+use prost::Message;
+use sha2::{Digest, Sha256};
+
+use crate::receiver::{bool_attribute, int_attribute, string_attribute, UNKNOWN_SERVICE};
+use crate::ApiError;
+
+/// A span as carried by either Zipkin v2 encoding, with IDs already decoded
+/// to bytes.
+#[derive(Default)]
+struct ZipkinSpan {
+    trace_id: Vec<u8>,
+    parent_id: Vec<u8>,
+    id: Vec<u8>,
+    kind: Option<SpanKind>,
+    name: String,
+    timestamp_us: u64,
+    duration_us: u64,
+    local_endpoint: Option<Endpoint>,
+    remote_endpoint: Option<Endpoint>,
+    annotations: Vec<(u64, String)>,
+    tags: Vec<(String, String)>,
+    shared: bool,
+}
+
+#[derive(Default)]
+struct Endpoint {
+    service_name: String,
+    ip: Option<String>,
+    port: Option<u16>,
+}
+
+/// Translates a Zipkin v2 JSON array or `zipkin.proto3.ListOfSpans` body into
+/// an OTLP trace request, one resource per local service name.
+pub fn translate(content_type: &str, payload: &[u8]) -> Result<Vec<u8>, ApiError> {
+    let spans = if content_type.contains("protobuf") {
+        proto::ListOfSpans::decode(payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Zipkin protobuf: {error}")))?
+            .spans
+            .into_iter()
+            .map(ZipkinSpan::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        serde_json::from_slice::<Vec<json::Span>>(payload)
+            .map_err(|error| ApiError::bad_request(format!("Invalid Zipkin JSON: {error}")))?
+            .into_iter()
+            .map(ZipkinSpan::try_from)
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    Ok(to_otlp(spans).encode_to_vec())
+}
+
+fn to_otlp(spans: Vec<ZipkinSpan>) -> ExportTraceServiceRequest {
+    let mut services: Vec<(String, Vec<Span>)> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for span in spans {
+        let service = span
+            .local_endpoint
+            .as_ref()
+            .map(|endpoint| endpoint.service_name.as_str())
+            .filter(|service| !service.is_empty())
+            .unwrap_or(UNKNOWN_SERVICE)
+            .to_string();
+        let otlp_span = to_otlp_span(span, &service);
+        let position = *index.entry(service.clone()).or_insert_with(|| {
+            services.push((service, Vec::new()));
+            services.len() - 1
+        });
+        services[position].1.push(otlp_span);
+    }
+
+    ExportTraceServiceRequest {
+        resource_spans: services
+            .into_iter()
+            .map(|(service, spans)| ResourceSpans {
+                resource: Some(Resource {
+                    attributes: vec![string_attribute("service.name", service)],
+                    ..Default::default()
+                }),
+                scope_spans: vec![ScopeSpans {
+                    scope: Some(InstrumentationScope {
+                        name: "zipkin".to_string(),
+                        ..Default::default()
+                    }),
+                    spans,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            })
+            .collect(),
+    }
+}
+
+fn to_otlp_span(span: ZipkinSpan, service: &str) -> Span {
+    let start_time_unix_nano = span.timestamp_us.saturating_mul(1_000);
+    let end_time_unix_nano =
+        start_time_unix_nano.saturating_add(span.duration_us.saturating_mul(1_000));
+
+    // A shared span is the server half of an RPC that reused the client's
+    // span ID. OTLP needs unique IDs, so the server half gets an ID derived
+    // from the shared one and becomes its child.
+    let (span_id, parent_span_id) = if span.shared {
+        (shared_span_id(&span.id, service), span.id.clone())
+    } else {
+        (span.id.clone(), span.parent_id.clone())
+    };
+
+    let mut attributes = Vec::with_capacity(span.tags.len() + 4);
+    let mut status = None;
+    for (key, value) in span.tags {
+        match key.as_str() {
+            "error" => {
+                let message = if value.is_empty() || value == "true" {
+                    String::new()
+                } else {
+                    value
+                };
+                status = Some(Status {
+                    code: StatusCode::Error as i32,
+                    message,
+                });
+            }
+            "otel.status_code" => {
+                let code = match value.to_ascii_uppercase().as_str() {
+                    "ERROR" => StatusCode::Error,
+                    "OK" => StatusCode::Ok,
+                    _ => StatusCode::Unset,
+                };
+                status.get_or_insert_with(Status::default).code = code as i32;
+            }
+            "otel.status_description" => {
+                status.get_or_insert_with(Status::default).message = value;
+            }
+            _ => attributes.push(string_attribute(&key, value)),
+        }
+    }
+
+    if let Some(remote) = &span.remote_endpoint {
+        if !remote.service_name.is_empty() {
+            attributes.push(string_attribute(
+                "peer.service",
+                remote.service_name.clone(),
+            ));
+        }
+        if let Some(ip) = &remote.ip {
+            attributes.push(string_attribute("network.peer.address", ip.clone()));
+        }
+        if let Some(port) = remote.port {
+            attributes.push(int_attribute("network.peer.port", port.into()));
+        }
+    }
+    if let Some(local) = &span.local_endpoint {
+        if let Some(ip) = &local.ip {
+            attributes.push(string_attribute("network.local.address", ip.clone()));
+        }
+        if let Some(port) = local.port {
+            attributes.push(int_attribute("network.local.port", port.into()));
+        }
+    }
+    if span.shared {
+        attributes.push(bool_attribute("zipkin.shared", true));
+    }
+
+    Span {
+        trace_id: span.trace_id,
+        span_id,
+        parent_span_id,
+        name: span.name,
+        kind: span.kind.unwrap_or(SpanKind::Internal) as i32,
+        start_time_unix_nano,
+        end_time_unix_nano,
+        attributes,
+        events: span
+            .annotations
+            .into_iter()
+            .map(|(timestamp_us, value)| Event {
+                time_unix_nano: timestamp_us.saturating_mul(1_000),
+                name: value,
+                ..Default::default()
+            })
+            .collect(),
+        status,
+        ..Default::default()
+    }
+}
+
+fn shared_span_id(id: &[u8], service: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(id);
+    hasher.update(b"shared");
+    hasher.update(service.as_bytes());
+    hasher.finalize()[..8].to_vec()
+}
+
+/// 64-bit trace IDs are left-padded to OTLP's 128 bits.
+fn widen_trace_id(mut id: Vec<u8>) -> Vec<u8> {
+    if id.len() == 8 {
+        id.splice(0..0, [0u8; 8]);
+    }
+    id
+}
+
+fn kind_from_name(kind: &str) -> Option<SpanKind> {
+    match kind.to_ascii_uppercase().as_str() {
+        "CLIENT" => Some(SpanKind::Client),
+        "SERVER" => Some(SpanKind::Server),
+        "PRODUCER" => Some(SpanKind::Producer),
+        "CONSUMER" => Some(SpanKind::Consumer),
+        _ => None,
+    }
+}
+
+mod json {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Span {
+        pub trace_id: String,
+        pub parent_id: Option<String>,
+        pub id: String,
+        pub kind: Option<String>,
+        pub name: Option<String>,
+        pub timestamp: Option<u64>,
+        pub duration: Option<u64>,
+        pub local_endpoint: Option<Endpoint>,
+        pub remote_endpoint: Option<Endpoint>,
+        #[serde(default)]
+        pub annotations: Vec<Annotation>,
+        #[serde(default)]
+        pub tags: BTreeMap<String, serde_json::Value>,
+        #[serde(default)]
+        pub shared: bool,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Endpoint {
+        pub service_name: Option<String>,
+        pub ipv4: Option<String>,
+        pub ipv6: Option<String>,
+        pub port: Option<u16>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Annotation {
+        pub timestamp: u64,
+        pub value: String,
+    }
+}
+
+/// `zipkin.proto3`, declared by hand since only these messages are needed.
+mod proto {
+    use std::collections::HashMap;
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListOfSpans {
+        #[prost(message, repeated, tag = "1")]
+        pub spans: Vec<Span>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Span {
+        #[prost(bytes = "vec", tag = "1")]
+        pub trace_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "2")]
+        pub parent_id: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub id: Vec<u8>,
+        #[prost(int32, tag = "4")]
+        pub kind: i32,
+        #[prost(string, tag = "5")]
+        pub name: String,
+        #[prost(fixed64, tag = "6")]
+        pub timestamp: u64,
+        #[prost(uint64, tag = "7")]
+        pub duration: u64,
+        #[prost(message, optional, tag = "8")]
+        pub local_endpoint: Option<Endpoint>,
+        #[prost(message, optional, tag = "9")]
+        pub remote_endpoint: Option<Endpoint>,
+        #[prost(message, repeated, tag = "10")]
+        pub annotations: Vec<Annotation>,
+        #[prost(map = "string, string", tag = "11")]
+        pub tags: HashMap<String, String>,
+        #[prost(bool, tag = "12")]
+        pub debug: bool,
+        #[prost(bool, tag = "13")]
+        pub shared: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Endpoint {
+        #[prost(string, tag = "1")]
+        pub service_name: String,
+        #[prost(bytes = "vec", tag = "2")]
+        pub ipv4: Vec<u8>,
+        #[prost(bytes = "vec", tag = "3")]
+        pub ipv6: Vec<u8>,
+        #[prost(int32, tag = "4")]
+        pub port: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Annotation {
+        #[prost(fixed64, tag = "1")]
+        pub timestamp: u64,
+        #[prost(string, tag = "2")]
+        pub value: String,
+    }
+}
+
+fn decode_hex_id(field: &str, id: &str) -> Result<Vec<u8>, ApiError> {
+    if !id.is_ascii() || !id.len().is_multiple_of(2) || id.len() > 32 {
+        return Err(ApiError::bad_request(format!(
+            "Invalid Zipkin {field}: {id}"
+        )));
+    }
+    (0..id.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&id[index..index + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| ApiError::bad_request(format!("Invalid Zipkin {field}: {id}")))
+}
+
+impl TryFrom<json::Span> for ZipkinSpan {
+    type Error = ApiError;
+
+    fn try_from(span: json::Span) -> Result<Self, ApiError> {
+        let endpoint = |endpoint: json::Endpoint| Endpoint {
+            service_name: endpoint.service_name.unwrap_or_default(),
+            ip: endpoint.ipv6.or(endpoint.ipv4),
+            port: endpoint.port.filter(|port| *port != 0),
+        };
+
+        Ok(Self {
+            trace_id: widen_trace_id(decode_hex_id("traceId", &span.trace_id)?),
+            parent_id: match span.parent_id.as_deref() {
+                Some(parent_id) if !parent_id.is_empty() => decode_hex_id("parentId", parent_id)?,
+                _ => Vec::new(),
+            },
+            id: decode_hex_id("id", &span.id)?,
+            kind: span.kind.as_deref().and_then(kind_from_name),
+            name: span.name.unwrap_or_default(),
+            timestamp_us: span.timestamp.unwrap_or_default(),
+            duration_us: span.duration.unwrap_or_default(),
+            local_endpoint: span.local_endpoint.map(endpoint),
+            remote_endpoint: span.remote_endpoint.map(endpoint),
+            annotations: span
+                .annotations
+                .into_iter()
+                .map(|annotation| (annotation.timestamp, annotation.value))
+                .collect(),
+            tags: span
+                .tags
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key, value),
+                    other => (key, other.to_string()),
+                })
+                .collect(),
+            shared: span.shared,
+        })
+    }
+}
+
+impl TryFrom<proto::Span> for ZipkinSpan {
+    type Error = ApiError;
+
+    fn try_from(span: proto::Span) -> Result<Self, ApiError> {
+        let endpoint = |endpoint: proto::Endpoint| {
+            let ip = if let Ok(ipv6) = <[u8; 16]>::try_from(endpoint.ipv6.as_slice()) {
+                Some(Ipv6Addr::from(ipv6).to_string())
+            } else {
+                <[u8; 4]>::try_from(endpoint.ipv4.as_slice())
+                    .ok()
+                    .map(|ipv4| Ipv4Addr::from(ipv4).to_string())
+            };
+            Endpoint {
+                service_name: endpoint.service_name,
+                ip,
+                port: u16::try_from(endpoint.port).ok().filter(|port| *port != 0),
+            }
+        };
+
+        let mut tags: Vec<(String, String)> = span.tags.into_iter().collect();
+        tags.sort();
+
+        Ok(Self {
+            trace_id: widen_trace_id(span.trace_id),
+            parent_id: span.parent_id,
+            id: span.id,
+            kind: match span.kind {
+                1 => Some(SpanKind::Client),
+                2 => Some(SpanKind::Server),
+                3 => Some(SpanKind::Producer),
+                4 => Some(SpanKind::Consumer),
+                _ => None,
+            },
+            name: span.name,
+            timestamp_us: span.timestamp,
+            duration_us: span.duration,
+            local_endpoint: span.local_endpoint.map(endpoint),
+            remote_endpoint: span.remote_endpoint.map(endpoint),
+            annotations: span
+                .annotations
+                .into_iter()
+                .map(|annotation| (annotation.timestamp, annotation.value))
+                .collect(),
+            tags,
+            shared: span.shared,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry_proto::tonic::common::v1::{any_value, KeyValue};
+
+    fn string_attr<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a str> {
+        attributes
+            .iter()
+            .find(|kv| kv.key == key)
+            .and_then(|kv| kv.value.as_ref())
+            .and_then(|value| match &value.value {
+                Some(any_value::Value::StringValue(text)) => Some(text.as_str()),
+                _ => None,
+            })
+    }
+
+    #[test]
+    fn json_spans_become_otlp_spans_grouped_by_service() {
+        let body = serde_json::json!([
+            {
+                "traceId": "463ac35c9f6413ad",
+                "id": "a2fb4a1d1a96d312",
+                "kind": "CLIENT",
+                "name": "get /users",
+                "timestamp": 1_700_000_000_000_000u64,
+                "duration": 1500,
+                "localEndpoint": { "serviceName": "frontend", "ipv4": "10.0.0.1" },
+                "remoteEndpoint": { "serviceName": "users", "port": 8080 },
+                "annotations": [{ "timestamp": 1_700_000_000_000_500u64, "value": "ws" }],
+                "tags": { "http.method": "GET", "error": "timeout", "retries": 2 }
+            },
+            {
+                "traceId": "463ac35c9f6413ad",
+                "id": "a2fb4a1d1a96d312",
+                "kind": "SERVER",
+                "name": "get /users",
+                "shared": true,
+                "localEndpoint": { "serviceName": "users" }
+            }
+        ]);
+        let payload = translate("application/json", body.to_string().as_bytes())
+            .ok()
+            .unwrap();
+        let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+
+        assert_eq!(request.resource_spans.len(), 2);
+        let client = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(client.trace_id.len(), 16);
+        assert_eq!(&client.trace_id[..8], &[0; 8]);
+        assert_eq!(client.kind, SpanKind::Client as i32);
+        assert_eq!(
+            client.end_time_unix_nano - client.start_time_unix_nano,
+            1_500_000
+        );
+        assert_eq!(client.events[0].name, "ws");
+        assert_eq!(client.status.as_ref().unwrap().message, "timeout");
+        assert_eq!(
+            string_attr(&client.attributes, "peer.service"),
+            Some("users")
+        );
+        assert_eq!(string_attr(&client.attributes, "retries"), Some("2"));
+
+        let server = &request.resource_spans[1].scope_spans[0].spans[0];
+        assert_eq!(server.parent_span_id, client.span_id);
+        assert_ne!(server.span_id, client.span_id);
+    }
+
+    #[test]
+    fn protobuf_spans_are_decoded_and_bad_ids_rejected() {
+        let list = proto::ListOfSpans {
+            spans: vec![proto::Span {
+                trace_id: vec![1; 16],
+                id: vec![2; 8],
+                kind: 2,
+                name: "checkout".to_string(),
+                local_endpoint: Some(proto::Endpoint {
+                    service_name: "payments".to_string(),
+                    ipv4: vec![10, 0, 0, 2],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+        };
+        let payload = translate("application/x-protobuf", &list.encode_to_vec())
+            .ok()
+            .unwrap();
+        let request = ExportTraceServiceRequest::decode(payload.as_slice()).unwrap();
+        let span = &request.resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.kind, SpanKind::Server as i32);
+        assert_eq!(
+            string_attr(&span.attributes, "network.local.address"),
+            Some("10.0.0.2")
+        );
+
+        let bad = br#"[{"traceId":"xyz","id":"a2fb4a1d1a96d312"}]"#;
+        assert!(translate("application/json", bad).is_err());
+    }
+}
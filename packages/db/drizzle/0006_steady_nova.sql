@@ -0,0 +1,24 @@
+CREATE TABLE `org_ingest_quotas` (
+	`org_id` text NOT NULL,
+	`signal` text NOT NULL,
+	`period` text NOT NULL,
+	`limit_bytes` integer NOT NULL,
+	`soft_limit_bytes` integer,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL,
+	`created_by` text NOT NULL,
+	`updated_by` text NOT NULL,
+	PRIMARY KEY(`org_id`, `signal`, `period`)
+);
+--> statement-breakpoint
+CREATE TABLE `org_ingest_usage` (
+	`org_id` text NOT NULL,
+	`signal` text NOT NULL,
+	`period` text NOT NULL,
+	`window_start` integer NOT NULL,
+	`bytes` integer NOT NULL,
+	`updated_at` integer NOT NULL,
+	PRIMARY KEY(`org_id`, `signal`, `period`, `window_start`)
+);
+--> statement-breakpoint
+CREATE INDEX `org_ingest_usage_window_idx` ON `org_ingest_usage` (`period`,`window_start`);
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE `org_trace_sampling_policies` (
+	`org_id` text PRIMARY KEY NOT NULL,
+	`sample_ratio` real NOT NULL,
+	`keep_errors` integer DEFAULT 1 NOT NULL,
+	`keep_services_json` text,
+	`keep_min_duration_ms` integer,
+	`created_at` integer NOT NULL,
+	`updated_at` integer NOT NULL,
+	`created_by` text NOT NULL,
+	`updated_by` text NOT NULL
+);
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE `org_ingest_keys` ADD `public_key_allowed_origins_json` text;
\ No newline at end of file
@@ -1,4 +1,6 @@
 export * from "./api-keys"
 export * from "./dashboards"
 export * from "./org-ingest-keys"
+export * from "./org-ingest-quotas"
+export * from "./org-trace-sampling"
 export * from "./scrape-targets"
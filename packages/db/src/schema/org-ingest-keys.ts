@@ -22,6 +22,7 @@ export const orgIngestKeys = sqliteTable(
     updatedAt: integer("updated_at", { mode: "number" }).notNull(),
     createdBy: text("created_by").notNull(),
     updatedBy: text("updated_by").notNull(),
+    publicKeyAllowedOriginsJson: text("public_key_allowed_origins_json"),
   },
   (table) => [
     primaryKey({ columns: [table.orgId] }),
@@ -0,0 +1,38 @@
+import { index, integer, primaryKey, sqliteTable, text } from "drizzle-orm/sqlite-core"
+
+export const orgIngestQuotas = sqliteTable(
+  "org_ingest_quotas",
+  {
+    orgId: text("org_id").notNull(),
+    signal: text("signal", { enum: ["all", "traces", "logs", "metrics"] }).notNull(),
+    period: text("period", { enum: ["day", "month"] }).notNull(),
+    limitBytes: integer("limit_bytes", { mode: "number" }).notNull(),
+    softLimitBytes: integer("soft_limit_bytes", { mode: "number" }),
+    createdAt: integer("created_at", { mode: "number" }).notNull(),
+    updatedAt: integer("updated_at", { mode: "number" }).notNull(),
+    createdBy: text("created_by").notNull(),
+    updatedBy: text("updated_by").notNull(),
+  },
+  (table) => [primaryKey({ columns: [table.orgId, table.signal, table.period] })],
+)
+
+export type OrgIngestQuotaRow = typeof orgIngestQuotas.$inferSelect
+export type OrgIngestQuotaInsert = typeof orgIngestQuotas.$inferInsert
+
+export const orgIngestUsage = sqliteTable(
+  "org_ingest_usage",
+  {
+    orgId: text("org_id").notNull(),
+    signal: text("signal", { enum: ["all", "traces", "logs", "metrics"] }).notNull(),
+    period: text("period", { enum: ["day", "month"] }).notNull(),
+    windowStart: integer("window_start", { mode: "number" }).notNull(),
+    bytes: integer("bytes", { mode: "number" }).notNull(),
+    updatedAt: integer("updated_at", { mode: "number" }).notNull(),
+  },
+  (table) => [
+    primaryKey({ columns: [table.orgId, table.signal, table.period, table.windowStart] }),
+    index("org_ingest_usage_window_idx").on(table.period, table.windowStart),
+  ],
+)
+
+export type OrgIngestUsageRow = typeof orgIngestUsage.$inferSelect
@@ -0,0 +1,16 @@
+import { integer, real, sqliteTable, text } from "drizzle-orm/sqlite-core"
+
+export const orgTraceSamplingPolicies = sqliteTable("org_trace_sampling_policies", {
+  orgId: text("org_id").notNull().primaryKey(),
+  sampleRatio: real("sample_ratio").notNull(),
+  keepErrors: integer("keep_errors", { mode: "number" }).notNull().default(1),
+  keepServicesJson: text("keep_services_json"),
+  keepMinDurationMs: integer("keep_min_duration_ms", { mode: "number" }),
+  createdAt: integer("created_at", { mode: "number" }).notNull(),
+  updatedAt: integer("updated_at", { mode: "number" }).notNull(),
+  createdBy: text("created_by").notNull(),
+  updatedBy: text("updated_by").notNull(),
+})
+
+export type OrgTraceSamplingPolicyRow = typeof orgTraceSamplingPolicies.$inferSelect
+export type OrgTraceSamplingPolicyInsert = typeof orgTraceSamplingPolicies.$inferInsert